@@ -1,10 +1,10 @@
 use std::{
     iter::once,
     sync::{Arc, Mutex},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use cgmath::{InnerSpace, Point3, Vector3};
+use cgmath::{InnerSpace, Point3, Vector2, Vector3};
 use wgpu::*;
 use winit::{
     application::ApplicationHandler,
@@ -16,10 +16,26 @@ use winit::{
 };
 
 use crate::{
-    camera::{camera_control::CameraControl, movement_control::MovementControl},
-    render_target::RenderTargetConfig,
+    camera::{
+        camera::Camera,
+        camera_control::CameraControl,
+        gamepad_control::{GamepadControl, load_gamepad_config},
+        movement_config::load_movement_config,
+        movement_control::MovementControl,
+        projection::Projection,
+    },
+    frame_export::{FrameExport, load_export_config},
+    input_map::load_input_map,
+    inspector::Inspector,
+    labels::load_label_config,
+    render_target::{RenderTargetConfig, load_antialiasing_config},
     scene::Scene,
-    solar_object::solar_object::load_solar_objects,
+    screenshot::{capture, load_screenshot_config, save_screenshot},
+    simulation_clock::SimulationClock,
+    sky::load_sky,
+    solar_object::{render_solar_object::time_scaling, solar_object::load_solar_objects},
+    texture::container::MAX_TEXTURE_DIMENSION,
+    tour::load_tour,
 };
 
 pub struct App {
@@ -112,7 +128,7 @@ impl ApplicationHandler for App {
             // ignore other events
             _ => {
                 if let Some(ref mut inner) = self.inner {
-                    inner.movement_control.process_window_event(event);
+                    inner.process_window_event(event);
                 } else {
                     eprintln!("Inner app is not initialized");
                 }
@@ -127,7 +143,7 @@ impl ApplicationHandler for App {
         event: DeviceEvent,
     ) {
         if let Some(ref mut inner) = self.inner {
-            inner.movement_control.process_device_event(event);
+            inner.process_device_event(event);
         } else {
             eprintln!("Inner app is not initialized");
         }
@@ -141,7 +157,11 @@ struct AppInner {
     render_target: RenderTargetConfig<'static>,
     queue: Queue,
     scene: Scene,
+    clock: Arc<Mutex<SimulationClock>>,
     movement_control: MovementControl,
+    inspector: Inspector,
+    /// Running export of a frame sequence. It replaces drawing into the window.
+    export: Option<FrameExport>,
 }
 
 impl AppInner {
@@ -163,48 +183,100 @@ impl AppInner {
         let (device, queue) = adapter
             .request_device(&DeviceDescriptor {
                 label: None,
-                required_features: Features::empty(),
+                // adapter specific format features are needed for sample counts other than 1 and
+                // 4, compression for precompressed textures
+                required_features: adapter.features()
+                    & (Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+                        | Features::TEXTURE_COMPRESSION_BC
+                        | Features::TEXTURE_COMPRESSION_ETC2
+                        | Features::TEXTURE_COMPRESSION_ASTC),
                 memory_hints: Default::default(),
-                required_limits: Limits::default(),
+                // textures up to the size the loader uses, if the adapter has them, with buffers
+                // big enough to hold such texture uncompressed
+                required_limits: Limits {
+                    max_texture_dimension_2d: adapter
+                        .limits()
+                        .max_texture_dimension_2d
+                        .min(MAX_TEXTURE_DIMENSION),
+                    max_buffer_size: adapter
+                        .limits()
+                        .max_buffer_size
+                        .min(MAX_TEXTURE_DIMENSION as u64 * MAX_TEXTURE_DIMENSION as u64 * 4),
+                    ..Limits::default()
+                },
                 trace: Trace::Off,
             })
             .await
             .unwrap();
 
-        let render_target =
-            RenderTargetConfig::new(window.inner_size(), &device, surface, &adapter)?;
+        let render_target = RenderTargetConfig::new(
+            window.inner_size(),
+            &device,
+            surface,
+            &adapter,
+            load_antialiasing_config("data/graphics.toml"),
+        )?;
 
         let camera_control = Arc::new(Mutex::new(CameraControl::new(
             Point3::new(0.0, 100.0, -200.0),
             Vector3::new(0.0, -1.0, 2.0).normalize(),
+            load_movement_config("data/controls.toml"),
         )));
-        let movement_control = MovementControl::new(camera_control.clone(), {
-            let window = window.clone();
-            move |dragging| {
-                if dragging {
-                    match window
-                        .set_cursor_grab(CursorGrabMode::Locked)
-                        .or_else(|_e| window.set_cursor_grab(CursorGrabMode::Confined))
-                    {
-                        Ok(()) => window.set_cursor_visible(false),
-                        Err(e) => eprintln!("Failed to grab cursor: {}", e),
+        let projection = Arc::new(Mutex::new(Projection::default()));
+        let clock = Arc::new(Mutex::new(SimulationClock::new(Instant::now())));
+        let label_config = Arc::new(Mutex::new(load_label_config("data/labels.toml")));
+        let camera = Camera::new(camera_control, projection);
+        let solar_object = load_solar_objects("data/definitions.toml");
+        // the Sun and the planets can be reached by number keys
+        let fly_to_targets = once(solar_object.name.clone())
+            .chain(solar_object.children.iter().map(|child| child.name.clone()))
+            .collect();
+        let mut movement_control = MovementControl::new(
+            camera.clone(),
+            clock.clone(),
+            label_config.clone(),
+            load_input_map("data/controls.toml"),
+            GamepadControl::new(load_gamepad_config("data/controls.toml")),
+            fly_to_targets,
+            {
+                let window = window.clone();
+                move |dragging| {
+                    if dragging {
+                        match window
+                            .set_cursor_grab(CursorGrabMode::Locked)
+                            .or_else(|_e| window.set_cursor_grab(CursorGrabMode::Confined))
+                        {
+                            Ok(()) => window.set_cursor_visible(false),
+                            Err(e) => eprintln!("Failed to grab cursor: {}", e),
+                        }
+                    } else {
+                        window
+                            .set_cursor_grab(CursorGrabMode::None)
+                            .expect("Releasing cursor grab cannot fail");
+                        window.set_cursor_visible(true);
                     }
-                } else {
-                    window
-                        .set_cursor_grab(CursorGrabMode::None)
-                        .expect("Releasing cursor grab cannot fail");
-                    window.set_cursor_visible(true);
                 }
-            }
-        });
+            },
+        );
+        if let Some(tour) = load_tour("data/tour.toml") {
+            movement_control.tour_mut().load(tour);
+        }
 
+        let inspector = Inspector::new(
+            &window,
+            &device,
+            &render_target,
+            clock.clone(),
+            label_config.clone(),
+        );
         let scene = Scene::new(
             &device,
             &queue,
             &render_target,
-            Instant::now(),
-            camera_control.clone(),
-            load_solar_objects("data/definitions.toml"),
+            camera,
+            label_config,
+            solar_object,
+            load_sky("data/sky.toml"),
         );
 
         Ok(AppInner {
@@ -213,16 +285,57 @@ impl AppInner {
             render_target,
             queue,
             scene,
+            clock,
             movement_control,
+            inspector,
+            export: None,
         })
     }
 
+    /// Passes the event to the inspector first and to the movement control, if the inspector does
+    /// not use it. Releases always get through, so actions held before do not get stuck. Input
+    /// other than releases is ignored during export, so it does not change exported frames.
+    fn process_window_event(&mut self, event: WindowEvent) {
+        let consumed =
+            self.export.is_some() || self.inspector.process_window_event(&self.window, &event);
+        let released = matches!(
+            event,
+            WindowEvent::KeyboardInput {
+                event: KeyEvent {
+                    state: ElementState::Released,
+                    ..
+                },
+                ..
+            } | WindowEvent::MouseInput {
+                state: ElementState::Released,
+                ..
+            }
+        );
+        if !consumed || released {
+            self.movement_control.process_window_event(event);
+        }
+    }
+
+    /// Passes raw mouse motion to the movement control, except during export.
+    fn process_device_event(&mut self, event: DeviceEvent) {
+        if self.export.is_none() {
+            self.movement_control.process_device_event(event);
+        }
+    }
+
     fn resize(&mut self, new_size: PhysicalSize<u32>) {
         self.render_target.resize(&self.device, new_size);
-        self.scene.resize(&self.queue, new_size, Instant::now());
+        // the scene keeps the export resolution until the export ends
+        if self.export.is_none() {
+            self.scene.resize(&self.queue, new_size, Instant::now());
+        }
     }
 
     fn render(&mut self) -> Result<(), SurfaceError> {
+        if self.export.is_some() {
+            self.render_export_frame();
+            return Ok(());
+        }
         let render_target = self.render_target.next_frame()?;
 
         let mut encoder = self
@@ -231,14 +344,127 @@ impl AppInner {
                 label: Some("Render Encoder"),
             });
 
-        self.scene.update_buffers(&self.queue, Instant::now());
+        let now = Instant::now();
+        self.movement_control.update(now);
+        if let Some(request) = self.movement_control.take_pick_request() {
+            let size = self.window.inner_size();
+            let ndc = Vector2::new(
+                (request.cursor.x / size.width as f64 * 2.0 - 1.0) as f32,
+                (1.0 - request.cursor.y / size.height as f64 * 2.0) as f32,
+            );
+            let pick = self.scene.pick(now, ndc);
+            self.movement_control.picked(request, pick);
+        }
+        self.inspector.update(
+            now,
+            &self.window,
+            &mut self.scene,
+            &mut self.movement_control,
+        );
+        let time = self.clock.lock().unwrap().advance(now);
+        self.scene.update_buffers(&self.queue, now, time);
 
         self.scene
             .record_draw_commands(&mut encoder, &render_target);
+        self.inspector.record_draw_commands(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            &render_target,
+        );
 
         self.queue.submit(once(encoder.finish()));
         render_target.present();
 
+        if self.movement_control.take_screenshot_request() {
+            self.take_screenshot(now, time);
+        }
+        if self.movement_control.take_export_request() {
+            self.start_export();
+        }
         Ok(())
     }
+
+    /// Saves the frame drawn for `now` and simulation `time` as a supersampled screenshot with the
+    /// simulation day and camera pose in its metadata.
+    fn take_screenshot(&mut self, now: Instant, time: Duration) {
+        let config = load_screenshot_config("data/screenshot.toml");
+        let image = capture(
+            &self.device,
+            &self.queue,
+            &self.render_target,
+            &mut self.scene,
+            config.supersample,
+            now,
+            time,
+        );
+        let camera_control = self.scene.camera().camera_control.lock().unwrap();
+        let orientation = camera_control.orientation();
+        let direction: [f32; 3] = (orientation * -Vector3::unit_z()).into();
+        let up: [f32; 3] = (orientation * Vector3::unit_y()).into();
+        let position: [f64; 3] = camera_control.position().into();
+        let metadata = [
+            ("Software", "Solar system".to_string()),
+            (
+                "Simulation day",
+                format!("{:.4}", time_scaling(time.as_secs_f64())),
+            ),
+            (
+                "Scale model",
+                self.scene.settings().scale_model.name().to_string(),
+            ),
+            ("Camera position", format!("{position:?}")),
+            ("Camera direction", format!("{direction:?}")),
+            ("Camera up", format!("{up:?}")),
+        ];
+        save_screenshot(&config.directory, &image, &metadata);
+    }
+
+    /// Starts export of a frame sequence configured in the export file. The scene is drawn at the
+    /// export resolution until the export ends.
+    fn start_export(&mut self) {
+        let config = load_export_config("data/export.toml");
+        let now = Instant::now();
+        if config.play_tour {
+            let tour = self.movement_control.tour_mut();
+            tour.seek(now, Duration::ZERO);
+            tour.play(now);
+        }
+        self.clock.lock().unwrap().sync(now);
+        let export = FrameExport::new(&self.device, &self.render_target, config, now);
+        self.scene.resize(&self.queue, export.size(), now);
+        self.export = Some(export);
+    }
+
+    /// Draws and writes the next exported frame at its fixed time.
+    fn render_export_frame(&mut self) {
+        let Some(export) = &mut self.export else {
+            return;
+        };
+        let now = export.now();
+        self.movement_control.tour_mut().update(now);
+        let time = self.clock.lock().unwrap().advance(now);
+        self.scene.update_buffers(&self.queue, now, time);
+
+        let render_target = export.render_target();
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Export Encoder"),
+            });
+        self.scene
+            .record_draw_commands(&mut encoder, &render_target);
+        self.queue.submit(once(encoder.finish()));
+
+        if !export.write_frame(&self.device, &self.queue) {
+            if let Some(export) = self.export.take() {
+                export.finish();
+            }
+            let now = Instant::now();
+            self.scene
+                .resize(&self.queue, self.render_target.size(), now);
+            // real time continues from here, not from the time of the last exported frame
+            self.clock.lock().unwrap().sync(now);
+        }
+    }
 }
@@ -0,0 +1,217 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::*;
+
+use crate::{
+    matrix::Matrix4x4,
+    model::{Mesh, Vertex, sphere::create_sphere_mesh},
+    render_target::{RenderTarget, RenderTargetConfig, ScenePipeline},
+    scene::SceneAtmosphere,
+};
+
+/// Per body atmosphere parameters in rendered units.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct AtmosphereUniform {
+    model_matrix: Matrix4x4,
+    center: [f32; 4],
+    shell: [f32; 4],
+    scattering: [f32; 4],
+    tint: [f32; 4],
+}
+
+impl AtmosphereUniform {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        center: [f32; 3],
+        radius: f32,
+        shell_radius: f32,
+        scale_height: f32,
+        rayleigh: [f32; 3],
+        mie: f32,
+        mie_asymmetry: f32,
+        tint: [f32; 3],
+    ) -> Self {
+        AtmosphereUniform {
+            model_matrix: Matrix4x4::translate(center.into())
+                * Matrix4x4::scale([shell_radius; 3].into()),
+            center: [center[0], center[1], center[2], radius],
+            shell: [shell_radius, scale_height, mie_asymmetry, 0.0],
+            scattering: [rayleigh[0], rayleigh[1], rayleigh[2], mie],
+            tint: [tint[0], tint[1], tint[2], 1.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtmosphereBindGroupDescriptor<'a> {
+    pub layout: &'a BindGroupLayout,
+    pub atmosphere_binding: u32,
+}
+
+/// Draws atmospheres of bodies on top of already rendered opaque models.
+#[derive(Debug)]
+pub struct AtmosphereRenderPass {
+    render_pipeline: ScenePipeline,
+    camera_bind_group: BindGroup,
+    atmosphere_layout: BindGroupLayout,
+    shell_mesh: Mesh,
+}
+
+impl AtmosphereRenderPass {
+    pub fn new(
+        device: &Device,
+        render_target: &RenderTargetConfig,
+        camera_buffer: &Buffer,
+    ) -> AtmosphereRenderPass {
+        let uniform_bind_group_entry = |binding: u32| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::VERTEX_FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let camera_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("atmosphere camera layout"),
+            entries: &[uniform_bind_group_entry(0)],
+        });
+        let atmosphere_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("atmosphere layout"),
+            entries: &[uniform_bind_group_entry(0)],
+        });
+
+        let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("atmosphere camera bind group"),
+            layout: &camera_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Atmosphere Pipeline Layout"),
+            bind_group_layouts: &[&camera_layout, &atmosphere_layout],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Atmosphere Shader"),
+            source: ShaderSource::Wgsl(include_str!("atmosphere_shader.wgsl").into()),
+        });
+
+        let render_pipeline = ScenePipeline::new(render_target, |sample_count| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("Atmosphere Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[Vertex::desc().clone()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(ColorTargetState {
+                        format: render_target.target_texture_format(),
+                        // Scattered light is added to whatever is behind the atmosphere, which is
+                        // dimmed by the transmittance.
+                        blend: Some(BlendState {
+                            color: BlendComponent {
+                                src_factor: BlendFactor::One,
+                                dst_factor: BlendFactor::OneMinusSrcAlpha,
+                                operation: BlendOperation::Add,
+                            },
+                            alpha: BlendComponent::OVER,
+                        }),
+                        write_mask: ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: FrontFace::Ccw,
+                    // Only the far side of the shell is rasterized, so the atmosphere is visible
+                    // even when the camera is inside of it.
+                    cull_mode: Some(Face::Back),
+                    polygon_mode: PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(DepthStencilState {
+                    format: render_target.depth_texture_format(),
+                    depth_write_enabled: false,
+                    depth_compare: render_target.depth_compare(),
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState {
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        AtmosphereRenderPass {
+            render_pipeline,
+            camera_bind_group,
+            atmosphere_layout,
+            shell_mesh: create_sphere_mesh(device, 1.0, 32, 64),
+        }
+    }
+
+    pub fn atmosphere_layout(&self) -> AtmosphereBindGroupDescriptor<'_> {
+        AtmosphereBindGroupDescriptor {
+            layout: &self.atmosphere_layout,
+            atmosphere_binding: 0,
+        }
+    }
+
+    pub fn record_draw_commands<'a>(
+        &self,
+        encoder: &mut CommandEncoder,
+        render_target: &RenderTarget,
+        atmospheres: impl Iterator<Item = &'a SceneAtmosphere>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Atmosphere Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: render_target.scene_view(),
+                resolve_target: render_target.resolve_target(),
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: render_target.depth_texture_view(),
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        let (vertex_buffer, index_buffer) = self.shell_mesh.buffers();
+        render_pass.set_pipeline(self.render_pipeline.get(render_target));
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer);
+        render_pass.set_index_buffer(index_buffer, IndexFormat::Uint16);
+        for atmosphere in atmospheres {
+            render_pass.set_bind_group(1, &atmosphere.bind_group, &[]);
+            // Index buffer contains u16 indices stored in u8 array. The number of elements is
+            // therefore half of its size.
+            render_pass.draw_indexed(0..index_buffer.size().get() as u32 / 2, 0, 0..1);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+// Single scattering atmosphere rendered on the far side of a shell sphere around the body. The
+// fragment shader marches the view ray through the shell, so it works from both outside and inside
+// of the atmosphere.
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+    inverse_view_proj: mat4x4<f32>,
+    // position of the Sun relative to the camera
+    light_position: vec4<f32>,
+}
+
+struct Atmosphere {
+    model_mat: mat4x4<f32>,
+    // xyz centre of the body, w radius of the body
+    center: vec4<f32>,
+    // x radius of the shell, y scale height, z mie asymmetry
+    shell: vec4<f32>,
+    // rgb rayleigh coefficients, a mie coefficient
+    scattering: vec4<f32>,
+    tint: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(1) @binding(0)
+var<uniform> atmosphere: Atmosphere;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) position: vec3<f32>,
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    var world_position = atmosphere.model_mat * vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_proj * world_position;
+    out.position = world_position.xyz / world_position.w;
+    return out;
+}
+
+// Fragment shader
+
+const SUN_INTENSITY: f32 = 20.0;
+const PI: f32 = 3.14159265;
+const VIEW_SAMPLES: i32 = 12;
+const LIGHT_SAMPLES: i32 = 4;
+// Mie extinction is slightly higher than its scattering
+const MIE_EXTINCTION: f32 = 1.1;
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+}
+
+// Returns distances of both intersections along the ray, or (-1, -1) when the ray misses.
+fn ray_sphere(origin: vec3<f32>, dir: vec3<f32>, center: vec3<f32>, radius: f32) -> vec2<f32> {
+    let oc = origin - center;
+    let b = dot(oc, dir);
+    let c = dot(oc, oc) - radius * radius;
+    let d = b * b - c;
+    if d < 0.0 {
+        return vec2<f32>(-1.0, -1.0);
+    }
+    let s = sqrt(d);
+    return vec2<f32>(-b - s, -b + s);
+}
+
+fn density(point: vec3<f32>) -> f32 {
+    let height = max(length(point - atmosphere.center.xyz) - atmosphere.center.w, 0.0);
+    return exp(-height / atmosphere.shell.y);
+}
+
+fn rayleigh_phase(cos_theta: f32) -> f32 {
+    return 3.0 / (16.0 * PI) * (1.0 + cos_theta * cos_theta);
+}
+
+// Cornette-Shanks approximation of the Mie phase function
+fn mie_phase(cos_theta: f32, g: f32) -> f32 {
+    let g2 = g * g;
+    let num = 3.0 * (1.0 - g2) * (1.0 + cos_theta * cos_theta);
+    let den = 8.0 * PI * (2.0 + g2) * pow(1.0 + g2 - 2.0 * g * cos_theta, 1.5);
+    return num / den;
+}
+
+// Optical depth from the point towards the sun, or -1 if the body itself is in the way.
+fn light_optical_depth(point: vec3<f32>, light_dir: vec3<f32>) -> f32 {
+    let body = ray_sphere(point, light_dir, atmosphere.center.xyz, atmosphere.center.w);
+    if body.x > 0.0 {
+        return -1.0;
+    }
+    let shell = ray_sphere(point, light_dir, atmosphere.center.xyz, atmosphere.shell.x);
+    let step = max(shell.y, 0.0) / f32(LIGHT_SAMPLES);
+    var depth = 0.0;
+    for (var i = 0; i < LIGHT_SAMPLES; i++) {
+        depth += density(point + light_dir * step * (f32(i) + 0.5)) * step;
+    }
+    return depth;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    let origin = camera.position.xyz;
+    let dir = normalize(in.position - origin);
+
+    let shell = ray_sphere(origin, dir, atmosphere.center.xyz, atmosphere.shell.x);
+    let start = max(shell.x, 0.0);
+    var end = shell.y;
+    let body = ray_sphere(origin, dir, atmosphere.center.xyz, atmosphere.center.w);
+    if body.x > 0.0 {
+        end = min(end, body.x);
+    }
+    if end <= start {
+        discard;
+    }
+
+    let rayleigh = atmosphere.scattering.rgb;
+    let mie = atmosphere.scattering.a;
+    let step = (end - start) / f32(VIEW_SAMPLES);
+    var view_depth = 0.0;
+    var rayleigh_sum = vec3<f32>(0.0);
+    var mie_sum = vec3<f32>(0.0);
+    var light_cos = 0.0;
+    for (var i = 0; i < VIEW_SAMPLES; i++) {
+        let point = origin + dir * (start + step * (f32(i) + 0.5));
+        let local_density = density(point) * step;
+        view_depth += local_density;
+        let light_dir = normalize(camera.light_position.xyz - point);
+        light_cos += dot(dir, light_dir);
+        let light_depth = light_optical_depth(point, light_dir);
+        if light_depth < 0.0 {
+            continue;
+        }
+        let total_depth = view_depth + light_depth;
+        let attenuation = exp(-(rayleigh * total_depth + mie * MIE_EXTINCTION * total_depth));
+        rayleigh_sum += attenuation * local_density;
+        mie_sum += attenuation * local_density;
+    }
+    light_cos /= f32(VIEW_SAMPLES);
+
+    let scattered = SUN_INTENSITY
+        * (rayleigh_sum * rayleigh * rayleigh_phase(light_cos)
+            + mie_sum * mie * mie_phase(light_cos, atmosphere.shell.z))
+        * atmosphere.tint.rgb;
+    let transmittance = exp(-(rayleigh * view_depth + mie * MIE_EXTINCTION * view_depth));
+    let alpha = 1.0 - dot(transmittance, vec3<f32>(1.0 / 3.0));
+
+    // The depth of the atmosphere is the depth of the point where the ray enters it, so bodies in
+    // front of the shell still occlude it.
+    let entry = camera.view_proj * vec4<f32>(origin + dir * start, 1.0);
+    var out: FragmentOutput;
+    out.color = vec4<f32>(scattered, alpha);
+    // Depth is reversed, camera inside of the shell is on the near plane with depth 1.
+    out.depth = select(1.0, clamp(entry.z / entry.w, 0.0, 1.0), entry.w > 0.0);
+    return out;
+}
@@ -0,0 +1,106 @@
+use std::f64::consts::PI;
+
+use cgmath::{Deg, InnerSpace, Point3, Rad, Transform, Vector3};
+
+use crate::{
+    camera::camera_control::UP,
+    solar_object::{
+        render_solar_object::RenderSolarObject, scale_model::ScaleModel, solar_object::BodyKind,
+    },
+};
+
+/// Speed of light in kilometres per second.
+pub const SPEED_OF_LIGHT_KM_S: f64 = 299_792.458;
+
+/// Catalog values and live state of a body. Live values are derived from positions of the last
+/// update of the scene.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodyInfo {
+    pub name: String,
+    pub kind: BodyKind,
+    pub parent: Option<String>,
+    pub radius_km: f64,
+    pub orbital_period_days: Option<f64>,
+    pub rotation_period_days: f64,
+    pub tilt: Deg<f64>,
+    pub distance_to_parent_km: Option<f64>,
+    /// Distance from the camera to the centre of the body in rendered units.
+    pub distance_to_camera: f64,
+    /// Distance from the camera to the centre of the body, known only in linear scale models.
+    pub distance_to_camera_km: Option<f64>,
+    /// Fraction of the orbit travelled since time zero.
+    pub orbital_phase: Option<f64>,
+    /// Orbits are circular, so the true anomaly equals the mean anomaly. It is measured from the
+    /// position at time zero.
+    pub true_anomaly: Option<Deg<f64>>,
+    /// Local solar time at the point of the surface under the camera in hours of the solar day
+    /// of the body. Missing for the Sun and for the camera above a pole.
+    pub local_solar_time: Option<f64>,
+    /// Time light takes from the Sun to the body in seconds.
+    pub light_time_from_sun: Option<f64>,
+}
+
+/// Collects information about the body with `name`. The root body is the Sun.
+pub fn body_info(
+    solar_object: &RenderSolarObject,
+    name: &str,
+    camera_position: Point3<f64>,
+    scale_model: ScaleModel,
+) -> Option<BodyInfo> {
+    let body = solar_object.find(name)?;
+    let parent = solar_object.parent_of(name);
+    let is_sun = std::ptr::eq(body, solar_object);
+    let distance_to_camera = (camera_position - body.world_position).magnitude();
+    let orbital_phase = body
+        .orbital_period_days
+        .map(|_| body.orbit_angle / (2.0 * PI));
+    Some(BodyInfo {
+        name: body.name.clone(),
+        kind: body.kind,
+        parent: parent.map(|parent| parent.name.clone()),
+        radius_km: body.radius_km,
+        orbital_period_days: body.orbital_period_days,
+        rotation_period_days: body.rotation_period_days,
+        tilt: Rad(body.tilt).into(),
+        distance_to_parent_km: parent
+            .map(|parent| (body.position_km - parent.position_km).magnitude()),
+        distance_to_camera,
+        distance_to_camera_km: scale_model
+            .km_per_unit()
+            .map(|km_per_unit| distance_to_camera * km_per_unit),
+        orbital_phase,
+        true_anomaly: orbital_phase.map(|_| Rad(body.orbit_angle).into()),
+        local_solar_time: if is_sun {
+            None
+        } else {
+            local_solar_time(body, solar_object.world_position, camera_position)
+        },
+        light_time_from_sun: (!is_sun).then(|| {
+            (body.position_km - solar_object.position_km).magnitude() / SPEED_OF_LIGHT_KM_S
+        }),
+    })
+}
+
+/// Hour angle of the Sun at the meridian under the camera, turned into hours from midnight.
+fn local_solar_time(
+    body: &RenderSolarObject,
+    sun_position: Point3<f64>,
+    camera_position: Point3<f64>,
+) -> Option<f64> {
+    let up = UP.cast::<f64>().expect("f32 vector fits into f64");
+    let axis = body.world_matrix.transform_vector(up).normalize();
+    // directions projected onto the equatorial plane
+    let flatten = |direction: Vector3<f64>| direction - axis * direction.dot(axis);
+    let to_sun = flatten(sun_position - body.world_position);
+    let to_camera = flatten(camera_position - body.world_position);
+    if to_sun.magnitude2() == 0.0 || to_camera.magnitude2() == 0.0 {
+        return None;
+    }
+    // Bodies rotate towards the east, so meridians east of the noon one have afternoon.
+    // Retrograde rotation has east on the other side.
+    let hour_angle = axis
+        .dot(to_sun.cross(to_camera))
+        .atan2(to_sun.dot(to_camera))
+        * body.rotation_period_days.signum();
+    Some((12.0 + hour_angle.to_degrees() / 15.0).rem_euclid(24.0))
+}
@@ -1,4 +1,8 @@
+#[allow(clippy::module_inception)]
 pub mod camera;
 pub mod camera_control;
+pub mod easing;
+pub mod gamepad_control;
+pub mod movement_config;
 pub mod movement_control;
 pub mod projection;
@@ -3,35 +3,106 @@ use std::{
     time::Instant,
 };
 
+use bytemuck::{Pod, Zeroable};
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector2, Vector4};
 use winit::dpi::PhysicalSize;
 
 use crate::{
-    camera::{camera_control::CameraControl, projection::Projection},
+    camera::{
+        camera_control::CameraControl,
+        projection::{Projection, Tile},
+    },
     matrix::Matrix4x4,
+    picking::Ray,
 };
 
+/// Camera data shared by all render passes in bind group 0.
+///
+/// Everything is rendered relative to the camera, so the camera itself is always in the origin of
+/// the rendered space and only its orientation is part of the view matrix.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct CameraUniform {
+    view_proj: Matrix4x4,
+    position: [f32; 4],
+    inverse_view_proj: Matrix4x4,
+    light_position: [f32; 4],
+}
+
 #[derive(Debug, Clone)]
 pub struct Camera {
     pub camera_control: Arc<Mutex<CameraControl>>,
-    pub projection: Projection,
+    pub projection: Arc<Mutex<Projection>>,
+    /// Part of the view rendered by the uniform, the whole view if missing.
+    tile: Option<Tile>,
 }
 
 impl Camera {
-    pub fn new(camera_control: Arc<Mutex<CameraControl>>, projection: Projection) -> Self {
+    pub fn new(
+        camera_control: Arc<Mutex<CameraControl>>,
+        projection: Arc<Mutex<Projection>>,
+    ) -> Self {
         Self {
             camera_control,
             projection,
+            tile: None,
         }
     }
 
+    /// Restricts rendering to `tile` of the view. Matrices other than the uniform keep showing
+    /// the whole view.
+    pub fn set_tile(&mut self, tile: Option<Tile>) {
+        self.tile = tile;
+    }
+
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
-        self.projection.resize(new_size);
+        self.projection.lock().unwrap().resize(new_size);
+    }
+
+    /// View projection matrix relative to the camera position.
+    pub fn view_proj_matrix(&mut self, now: Instant) -> Matrix4<f32> {
+        let view = self.camera_control.lock().unwrap().snapshot(now);
+        self.projection.lock().unwrap().matrix(now) * view
     }
 
-    pub fn view_proj_matrix(&mut self, now: Instant) -> Matrix4x4 {
-        Matrix4x4::view_proj(
-            self.camera_control.lock().unwrap().snapshot(now),
-            self.projection.matrix(),
-        )
+    /// Ray in the world going through the point of the screen in normalized device coordinates.
+    /// Perspective rays start in the camera, orthographic ones on the near plane.
+    pub fn pick_ray(&mut self, now: Instant, ndc: Vector2<f32>) -> Ray {
+        let inverse = self
+            .view_proj_matrix(now)
+            .cast::<f64>()
+            .expect("f32 matrix fits into f64")
+            .invert()
+            .unwrap_or(Matrix4::identity());
+        let unproject = |depth: f64| {
+            let point = inverse * Vector4::new(ndc.x as f64, ndc.y as f64, depth, 1.0);
+            point.truncate() / point.w
+        };
+        // depth is reversed, 1 is on the near plane and 0.5 twice as far
+        let near = unproject(1.0);
+        let far = unproject(0.5);
+        let position = self.camera_control.lock().unwrap().position();
+        Ray {
+            origin: position + near,
+            direction: (far - near).normalize(),
+        }
+    }
+
+    pub fn uniform(&mut self, now: Instant) -> CameraUniform {
+        let view_proj = self.view_proj_matrix(now);
+        let view_proj = Matrix4x4::from(match self.tile {
+            Some(tile) => tile.clip_transform() * view_proj,
+            None => view_proj,
+        });
+        // the Sun is in the origin of the world
+        let light_position = (Point3::origin() - self.camera_control.lock().unwrap().position())
+            .cast::<f32>()
+            .expect("f64 vector fits into f32");
+        CameraUniform {
+            view_proj,
+            position: [0.0, 0.0, 0.0, 1.0],
+            inverse_view_proj: view_proj.inverse(),
+            light_position: light_position.extend(1.0).into(),
+        }
     }
 }
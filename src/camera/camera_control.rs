@@ -3,11 +3,17 @@ use std::{
     time::{Duration, Instant},
 };
 
-use cgmath::{InnerSpace, Matrix3, Matrix4, Point3, Rad, SquareMatrix, Vector3};
+use cgmath::{
+    Angle, InnerSpace, Matrix3, Matrix4, One, Point3, Quaternion, Rad, Rotation3, Vector3,
+};
+
+use crate::camera::{easing::Easing, movement_config::MovementConfig};
 
 pub const UP: Vector3<f32> = Vector3::new(0.0, 1.0, 0.0);
 // 5 pixels of movements results in 1 degree of rotation
 const ROTATION_MULTIPLIER: f32 = PI / 180.0 / 5.0;
+/// Roll speed in radians per second.
+const ROLL_SPEED: f32 = PI / 2.0;
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 enum Change {
@@ -38,6 +44,15 @@ impl Change {
         }
     }
 
+    /// Direction of the change, 1 for positive, -1 for negative and 0 for no change.
+    fn sign(&self) -> f32 {
+        match self {
+            Change::None => 0.0,
+            Change::Positive { .. } => 1.0,
+            Change::Negative { .. } => -1.0,
+        }
+    }
+
     fn take(&mut self, now: Instant, mapper: impl Fn(Duration) -> f32) -> f32 {
         match self {
             Change::None => 0.0,
@@ -56,11 +71,38 @@ impl Change {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq)]
 struct Movements {
     forward: Change,
     right: Change,
     up: Change,
+    roll: Change,
+    /// Analog movement from a gamepad, right, up and forward in range from -1 to 1. It adds up
+    /// with digital movements.
+    analog: Vector3<f32>,
+}
+
+impl Default for Movements {
+    fn default() -> Self {
+        Self {
+            forward: Change::None,
+            right: Change::None,
+            up: Change::None,
+            roll: Change::None,
+            analog: Vector3::new(0.0, 0.0, 0.0),
+        }
+    }
+}
+
+/// How the camera can be rotated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrientationMode {
+    /// The horizon is kept level with the ecliptic. Camera can look straight up or down, but not
+    /// further, and cannot roll.
+    #[default]
+    Clamped,
+    /// Spacecraft like rotation around all three camera axes.
+    Free,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
@@ -89,49 +131,441 @@ impl MovementDirection {
     }
 }
 
-fn ms_map(duration: Duration) -> f32 {
-    // map duration to seconds
-    duration.as_secs_f32().powf(5.0)
+/// Distance from the centre of the body at the end of fly-to, in radii of the body.
+const FLY_TO_DISTANCE: f64 = 4.0;
+/// Height of the eyes of an observer standing on a body, in radii of the body.
+const EYE_HEIGHT: f64 = 0.005;
+
+/// Latitude and longitude on the surface of a body.
+pub type Coordinates = (Rad<f64>, Rad<f64>);
+
+/// Position, size and orientation of a body the camera moves relative to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetFrame {
+    pub position: Point3<f64>,
+    /// Rendered radius.
+    pub radius: f64,
+    /// Rotation from the body fixed frame, with Y axis towards the north pole, to the world. It
+    /// includes spin of the body.
+    pub rotation: Quaternion<f64>,
+}
+
+/// Flight of the camera towards a body. Bodies move, so the target position is looked up every
+/// frame and the path is relative to it.
+#[derive(Debug, Clone, PartialEq)]
+struct Flight {
+    target: String,
+    start: Instant,
+    duration: Duration,
+    easing: Easing,
+    from_orientation: Quaternion<f32>,
+    /// Resolved on the first update, when position of the target is known.
+    path: Option<FlightPath>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FlightPath {
+    /// Unit vector from the target to the camera. The camera approaches along it.
+    direction: Vector3<f64>,
+    from_distance: f64,
+}
+
+/// Body the camera moves with. Camera keeps its offset from the body and can move freely around
+/// it.
+#[derive(Debug, Clone, PartialEq)]
+struct Following {
+    target: String,
+    target_position: Point3<f64>,
+}
+
+/// Observer standing on the surface of a body. The camera turns with the body, so the horizon
+/// stays in place. Any manual movement lifts the observer off and the camera follows the body.
+#[derive(Debug, Clone, PartialEq)]
+struct Ground {
+    target: String,
+    /// Latitude and longitude of the observer. Longitude grows to the east from the X axis of the
+    /// body fixed frame. Missing coordinates are resolved on the first update as the point under
+    /// the camera.
+    coordinates: Option<Coordinates>,
+    /// Horizon frame as of the last update. Rotation from the frame with X axis to the east, Y
+    /// axis to the zenith and Z axis to the south to the world.
+    horizon: Option<Quaternion<f64>>,
+    target_position: Point3<f64>,
+}
+
+/// Horizon frame at `latitude` and `longitude` in the body fixed frame. Returns the rotation from
+/// the horizon frame and the zenith.
+fn horizon_frame(latitude: Rad<f64>, longitude: Rad<f64>) -> (Quaternion<f64>, Vector3<f64>) {
+    let zenith = Vector3::new(
+        latitude.cos() * longitude.cos(),
+        latitude.sin(),
+        -latitude.cos() * longitude.sin(),
+    );
+    // bodies rotate counter-clockwise around their north pole, which is towards the east
+    let east = Vector3::new(-longitude.sin(), 0.0, -longitude.cos());
+    let north = zenith.cross(east);
+    (
+        Quaternion::from(Matrix3::from_cols(east, zenith, -north)).normalize(),
+        zenith,
+    )
+}
+
+fn roll_map(duration: Duration) -> f32 {
+    duration.as_secs_f32() * ROLL_SPEED
+}
+
+/// Rotation of the camera looking towards `forward`. Camera up vector is the first of `up_hints`,
+/// which is not parallel to `forward`, made perpendicular to it.
+pub fn look_rotation(forward: Vector3<f32>, up_hints: &[Vector3<f32>]) -> Quaternion<f32> {
+    let forward = forward.normalize();
+    let right = up_hints
+        .iter()
+        .map(|up| forward.cross(*up))
+        .find(|right| right.magnitude2() > 1e-6)
+        .unwrap_or_else(Vector3::unit_x)
+        .normalize();
+    let up = right.cross(forward);
+    // camera looks along its negative Z axis
+    Quaternion::from(Matrix3::from_cols(right, up, -forward)).normalize()
 }
 
 /// Camera control struct.
 ///
-/// Movements are time based. When movements begin, its timestamp is remembered. Each time the
-/// camera is materialized into a view matrix, movement is computed based on elapsed time since the
-/// movement began.
+/// Movements are time based. Held movement keys accelerate the camera, which keeps its velocity
+/// between updates. Each time the camera is materialized into a view matrix, velocity and position
+/// are integrated over elapsed time since the last update. See [`MovementConfig`] for the velocity
+/// model.
 ///
-/// Camera view direction is based on azimuth and zenith. Since those are controlled by mouse
-/// movements, where the same logic as for the movements does not apply and even if it's controlled
-/// by keyboard, computing rotation with movement together is non-trivial. Therefore camera is
-/// rotation is instantaneous.
+/// Camera orientation is a rotation from camera space, where camera looks along negative Z axis
+/// with Y axis up, to the world. Since it is controlled by mouse movements, where the same logic
+/// as for the movements does not apply, rotation is instantaneous. Only roll, which is controlled
+/// by keyboard, is time based.
 #[derive(Debug, Clone, PartialEq)]
 pub struct CameraControl {
-    // current world position
-    position: Point3<f32>,
-    // current view direction
-    view_direction: Vector3<f32>,
+    // current world position, double precision is needed for distances in the solar system
+    position: Point3<f64>,
+    orientation: Quaternion<f32>,
+    orientation_mode: OrientationMode,
     // relative to camera view vector
     movements: Movements,
+    velocity: Vector3<f64>,
+    last_update: Option<Instant>,
+    config: MovementConfig,
+    speed_multiplier: f64,
+    /// Distance to the nearest body surface, cruise speed is proportional to it.
+    surface_distance: f64,
+    flight: Option<Flight>,
+    following: Option<Following>,
+    ground: Option<Ground>,
 }
 
 impl CameraControl {
-    pub fn new(position: Point3<f32>, view_direction: Vector3<f32>) -> Self {
+    pub fn new(
+        position: Point3<f64>,
+        view_direction: Vector3<f32>,
+        config: MovementConfig,
+    ) -> Self {
         Self {
             position,
-            view_direction,
-            movements: Movements::default(),
+            orientation: look_rotation(view_direction, &[UP, -Vector3::unit_z()]),
+            config,
+            ..Default::default()
         }
     }
 
     /// Integrates all movement changes based on current time and returns the resulting view matrix.
+    /// The view matrix is relative to the camera position, so it contains only the rotation.
     pub fn snapshot(&mut self, now: Instant) -> Matrix4<f32> {
         self.materialize_movements(now);
-        Matrix4::look_to_rh(self.position, self.view_direction, UP)
+        Matrix4::from(self.orientation.conjugate())
+    }
+
+    pub fn position(&self) -> Point3<f64> {
+        self.position
+    }
+
+    /// Updates distance to the nearest body surface. Surface distance is negative inside a body.
+    pub fn set_surface_distance(&mut self, surface_distance: f64) {
+        self.surface_distance = surface_distance;
+    }
+
+    /// Multiplies cruise speed by `steps` multiplier steps. Negative steps slow the camera down.
+    pub fn scale_speed(&mut self, steps: f32) {
+        let (min, max) = self.config.multiplier_range;
+        self.speed_multiplier = (self.speed_multiplier
+            * self.config.multiplier_step.powf(steps as f64))
+        .clamp(min, max);
+    }
+
+    /// Speed the camera accelerates to while movement keys are held.
+    fn cruise_speed(&self) -> f64 {
+        (self.surface_distance * self.config.speed_per_distance)
+            .clamp(self.config.min_speed, self.config.max_speed)
+            * self.speed_multiplier
+    }
+
+    /// Rotation from camera space to the world.
+    pub fn orientation(&self) -> Quaternion<f32> {
+        self.orientation
+    }
+
+    pub fn view_direction(&self) -> Vector3<f32> {
+        self.orientation * -Vector3::unit_z()
+    }
+
+    fn camera_up(&self) -> Vector3<f32> {
+        self.orientation * Vector3::unit_y()
+    }
+
+    fn camera_right(&self) -> Vector3<f32> {
+        self.orientation * Vector3::unit_x()
+    }
+
+    /// Direction, which is up in clamped mode. It is the zenith for an observer on the ground.
+    fn reference_up(&self) -> Vector3<f32> {
+        self.ground
+            .as_ref()
+            .and_then(|ground| ground.horizon)
+            .map_or(UP, |horizon| {
+                (horizon * Vector3::unit_y())
+                    .cast()
+                    .expect("f64 vector fits into f32")
+            })
+    }
+
+    pub fn orientation_mode(&self) -> OrientationMode {
+        self.orientation_mode
+    }
+
+    /// Switching to clamped mode levels the horizon, but keeps the view direction.
+    pub fn set_orientation_mode(&mut self, now: Instant, orientation_mode: OrientationMode) {
+        self.materialize_movements(now);
+        self.orientation_mode = orientation_mode;
+        self.movements.roll = Change::None;
+        self.orientation = self.orientation_towards(self.view_direction());
+    }
+
+    /// Orientation looking towards `view_direction`. Clamped mode keeps the horizon level, free
+    /// mode keeps the camera up vector as close to the current one as possible.
+    fn orientation_towards(&self, view_direction: Vector3<f32>) -> Quaternion<f32> {
+        let up_hint = match self.orientation_mode {
+            OrientationMode::Clamped => self.reference_up(),
+            OrientationMode::Free => self.camera_up(),
+        };
+        // When looking straight up or down, the current view direction becomes top of the screen.
+        look_rotation(
+            view_direction,
+            &[up_hint, self.view_direction(), self.camera_up()],
+        )
+    }
+
+    /// Moves camera to `position` and turns it towards `view_direction`. Stops any flight,
+    /// following or standing on the ground.
+    pub fn look_from(&mut self, now: Instant, position: Point3<f64>, view_direction: Vector3<f32>) {
+        self.materialize_movements(now);
+        self.position = position;
+        self.velocity = Vector3::new(0.0, 0.0, 0.0);
+        self.flight = None;
+        self.following = None;
+        self.ground = None;
+        self.orientation = self.orientation_towards(view_direction);
+    }
+
+    /// Moves camera to `position` with `orientation`. Stops any flight, following or standing on
+    /// the ground.
+    pub fn set_pose(&mut self, now: Instant, position: Point3<f64>, orientation: Quaternion<f32>) {
+        self.materialize_movements(now);
+        self.position = position;
+        self.orientation = orientation.normalize();
+        self.velocity = Vector3::new(0.0, 0.0, 0.0);
+        self.flight = None;
+        self.following = None;
+        self.ground = None;
+    }
+
+    /// Starts flight towards body named `target`. The flight ends with the body centred in the
+    /// view at distance given by its rendered radius and the camera follows the body afterwards.
+    /// Any manual movement stops the flight, but not following.
+    ///
+    /// Position of the target is resolved in [`CameraControl::update_target`].
+    pub fn fly_to(&mut self, now: Instant, target: &str, duration: Duration, easing: Easing) {
+        self.materialize_movements(now);
+        self.velocity = Vector3::new(0.0, 0.0, 0.0);
+        self.following = None;
+        self.ground = None;
+        self.flight = Some(Flight {
+            target: target.to_string(),
+            start: now,
+            duration,
+            easing,
+            from_orientation: self.orientation,
+            path: None,
+        });
+    }
+
+    /// Stops following a body or standing on it.
+    pub fn stop_following(&mut self) {
+        self.following = None;
+        self.ground = None;
+    }
+
+    /// Places the observer on the surface of body named `target` at `coordinates`, latitude and
+    /// longitude, or under the camera. The observer looks to the north at the horizon.
+    ///
+    /// Position of the target is resolved in [`CameraControl::update_target`].
+    pub fn stand_on(&mut self, now: Instant, target: &str, coordinates: Option<Coordinates>) {
+        self.materialize_movements(now);
+        self.velocity = Vector3::new(0.0, 0.0, 0.0);
+        self.flight = None;
+        self.following = None;
+        self.ground = Some(Ground {
+            target: target.to_string(),
+            coordinates,
+            horizon: None,
+            target_position: self.position,
+        });
+    }
+
+    /// Body the observer stands on and its latitude and longitude, once resolved.
+    pub fn ground(&self) -> Option<(&str, Option<Coordinates>)> {
+        self.ground
+            .as_ref()
+            .map(|ground| (ground.target.as_str(), ground.coordinates))
+    }
+
+    /// Manual movement of an observer on the ground starts following the body instead.
+    fn lift_off(&mut self) {
+        if let Some(ground) = self.ground.take() {
+            self.following = Some(Following {
+                target: ground.target,
+                target_position: ground.target_position,
+            });
+        }
+    }
+
+    /// Moves the camera along current flight, with the followed body or on the ground.
+    /// `target_lookup` returns frame of the body with given name.
+    ///
+    /// Distance to the target is interpolated in logarithmic space, so the camera crosses empty
+    /// space quickly and slows down near the target. Orientation turns towards the target at the
+    /// same time.
+    pub fn update_target(
+        &mut self,
+        now: Instant,
+        target_lookup: impl Fn(&str) -> Option<TargetFrame>,
+    ) {
+        if let Some(following) = &mut self.following {
+            match target_lookup(&following.target) {
+                Some(frame) => {
+                    self.position += frame.position - following.target_position;
+                    following.target_position = frame.position;
+                }
+                None => self.following = None,
+            }
+        }
+        if let Some(ground) = &mut self.ground {
+            let Some(frame) = target_lookup(&ground.target) else {
+                eprintln!("Cannot stand on unknown body {}", ground.target);
+                self.ground = None;
+                return;
+            };
+            let position = self.position;
+            let (latitude, longitude) = *ground.coordinates.get_or_insert_with(|| {
+                let local = frame.rotation.conjugate() * (position - frame.position);
+                let latitude = Rad((local.y / local.magnitude()).clamp(-1.0, 1.0).asin());
+                (latitude, Rad((-local.z).atan2(local.x)))
+            });
+            let (local_horizon, zenith) = horizon_frame(latitude, longitude);
+            let horizon = frame.rotation * local_horizon;
+            self.position =
+                frame.position + frame.rotation * zenith * frame.radius * (1.0 + EYE_HEIGHT);
+            // the view turns with the body
+            let turn = match ground.horizon.replace(horizon) {
+                Some(previous) => horizon * previous.conjugate(),
+                None => {
+                    horizon
+                        * self
+                            .orientation
+                            .cast()
+                            .expect("f32 fits into f64")
+                            .conjugate()
+                }
+            };
+            self.orientation =
+                (turn.cast().expect("f64 fits into f32") * self.orientation).normalize();
+            ground.target_position = frame.position;
+            return;
+        }
+        let Some(flight) = &mut self.flight else {
+            return;
+        };
+        let Some(TargetFrame {
+            position: target,
+            radius,
+            ..
+        }) = target_lookup(&flight.target)
+        else {
+            eprintln!("Cannot fly to unknown body {}", flight.target);
+            self.flight = None;
+            return;
+        };
+        let position = self.position;
+        let view_direction = self.orientation * -Vector3::unit_z();
+        let path = *flight.path.get_or_insert_with(|| {
+            let offset = position - target;
+            let direction = if offset.magnitude2() > 0.0 {
+                offset.normalize()
+            } else {
+                -view_direction.cast().expect("f32 vector fits into f64")
+            };
+            FlightPath {
+                direction,
+                from_distance: offset.magnitude().max(radius),
+            }
+        });
+
+        let t =
+            (now - flight.start).as_secs_f32() / flight.duration.as_secs_f32().max(f32::EPSILON);
+        let progress = flight.easing.apply(t);
+        let to_distance = radius * FLY_TO_DISTANCE;
+        let distance = (path.from_distance.ln()
+            + (to_distance.ln() - path.from_distance.ln()) * progress as f64)
+            .exp();
+        let from_orientation = flight.from_orientation;
+        let finished = t >= 1.0;
+
+        let mut to_orientation = self.orientation_towards(
+            -path
+                .direction
+                .cast::<f32>()
+                .expect("f64 vector fits into f32"),
+        );
+        // the same rotation has two quaternions, the closer one gives the shorter path
+        if from_orientation.dot(to_orientation) < 0.0 {
+            to_orientation = -to_orientation;
+        }
+        let orientation = from_orientation.slerp(to_orientation, progress);
+        self.position = target + path.direction * distance;
+        self.orientation = match self.orientation_mode {
+            // interpolation may roll the camera slightly, which clamped mode does not allow
+            OrientationMode::Clamped => self.orientation_towards(orientation * -Vector3::unit_z()),
+            OrientationMode::Free => orientation,
+        };
+        if finished {
+            self.following = self.flight.take().map(|flight| Following {
+                target: flight.target,
+                target_position: target,
+            });
+        }
     }
 
     /// Forward is positive, backwards is negative
     pub fn move_forw_backw(&mut self, now: Instant, direction: MovementDirection) {
         self.materialize_movements(now);
+        self.flight = None;
+        if direction != MovementDirection::None {
+            self.lift_off();
+        }
         match direction {
             MovementDirection::None => self.movements.forward = Change::None,
             MovementDirection::Positive => self.movements.forward = Change::positive(now),
@@ -142,6 +576,10 @@ impl CameraControl {
     /// Right is positive, left is negative
     pub fn move_sideways(&mut self, now: Instant, direction: MovementDirection) {
         self.materialize_movements(now);
+        self.flight = None;
+        if direction != MovementDirection::None {
+            self.lift_off();
+        }
         match direction {
             MovementDirection::None => self.movements.right = Change::None,
             MovementDirection::Positive => self.movements.right = Change::positive(now),
@@ -152,6 +590,10 @@ impl CameraControl {
     // Up is positive, down is negative
     pub fn move_vertical(&mut self, now: Instant, direction: MovementDirection) {
         self.materialize_movements(now);
+        self.flight = None;
+        if direction != MovementDirection::None {
+            self.lift_off();
+        }
         match direction {
             MovementDirection::None => self.movements.up = Change::None,
             MovementDirection::Positive => self.movements.up = Change::positive(now),
@@ -159,32 +601,100 @@ impl CameraControl {
         }
     }
 
-    pub fn rotate(&mut self, now: Instant, delta_x: f32, delta_y: f32) {
+    /// Clockwise is positive, counter-clockwise is negative. Camera rolls only in free mode.
+    pub fn roll(&mut self, now: Instant, direction: MovementDirection) {
         self.materialize_movements(now);
+        if self.orientation_mode != OrientationMode::Free {
+            return;
+        }
+        self.flight = None;
+        match direction {
+            MovementDirection::None => self.movements.roll = Change::None,
+            MovementDirection::Positive => self.movements.roll = Change::positive(now),
+            MovementDirection::Negative => self.movements.roll = Change::negative(now),
+        }
+    }
 
-        let delta_x = delta_x * ROTATION_MULTIPLIER;
-        let delta_y = delta_y * ROTATION_MULTIPLIER;
+    /// Analog movement in range from -1 to 1 for each axis, `x` is right, `y` up and `z` forward.
+    /// Partial deflection limits speed to the same fraction of the cruise speed.
+    pub fn move_analog(&mut self, now: Instant, movement: Vector3<f32>) {
+        self.materialize_movements(now);
+        if movement.magnitude2() > 0.0 {
+            self.flight = None;
+            self.lift_off();
+        }
+        self.movements.analog = movement;
+    }
 
-        let right = self.view_direction.cross(UP).normalize();
+    /// Rotates the camera by mouse movement in pixels.
+    pub fn rotate(&mut self, now: Instant, delta_x: f32, delta_y: f32) {
+        self.turn(
+            now,
+            Rad(delta_x * ROTATION_MULTIPLIER),
+            Rad(delta_y * ROTATION_MULTIPLIER),
+        );
+    }
 
-        // zenith needs special treatment since it cannot exceed bounds
-        let current_zen = PI * 0.5 - self.view_direction.y.acos();
-        let new_zen = (current_zen + delta_y).clamp(-PI * 0.49, PI * 0.49);
-        let zen_change = new_zen - current_zen;
+    /// Turns the camera left by `delta_x` and up by `delta_y`.
+    pub fn turn(&mut self, now: Instant, Rad(delta_x): Rad<f32>, Rad(delta_y): Rad<f32>) {
+        self.materialize_movements(now);
+        self.flight = None;
 
-        self.view_direction = Matrix3::identity()
-            * Matrix3::from_angle_y(Rad(delta_x))
-            * Matrix3::from_axis_angle(right, Rad(zen_change))
-            * self.view_direction;
+        self.orientation = match self.orientation_mode {
+            OrientationMode::Clamped => {
+                let up = self.reference_up();
+                // pitch needs special treatment since it cannot exceed bounds
+                let current_pitch = self.view_direction().dot(up).clamp(-1.0, 1.0).asin();
+                let new_pitch = (current_pitch + delta_y).clamp(-PI * 0.5, PI * 0.5);
+                // yaw is around the up axis, pitch around the camera right axis
+                Quaternion::from_axis_angle(up, Rad(delta_x))
+                    * self.orientation
+                    * Quaternion::from_angle_x(Rad(new_pitch - current_pitch))
+            }
+            // both rotations are around camera axes
+            OrientationMode::Free => {
+                self.orientation
+                    * Quaternion::from_angle_y(Rad(delta_x))
+                    * Quaternion::from_angle_x(Rad(delta_y))
+            }
+        }
+        .normalize();
     }
 
-    /// updates self position based on current movements and their durations
+    /// updates self velocity and position based on current movements and time since last update
     fn materialize_movements(&mut self, now: Instant) {
-        let right = self.view_direction.cross(UP).normalize();
-        self.position = self.position
-            + self.view_direction * self.movements.forward.take(now, ms_map)
-            + right * self.movements.right.take(now, ms_map)
-            + UP * self.movements.up.take(now, ms_map);
+        let elapsed = self.last_update.map_or(0.0, |last| {
+            now.saturating_duration_since(last).as_secs_f64()
+        });
+        self.last_update = Some(now);
+
+        let up = match self.orientation_mode {
+            OrientationMode::Clamped => self.reference_up(),
+            OrientationMode::Free => self.camera_up(),
+        };
+        let analog = self.movements.analog;
+        let axis = |change: &Change, analog: f32| (change.sign() + analog).clamp(-1.0, 1.0);
+        let thrust = (self.view_direction() * axis(&self.movements.forward, analog.z)
+            + self.camera_right() * axis(&self.movements.right, analog.x)
+            + up * axis(&self.movements.up, analog.y))
+        .cast::<f64>()
+        .expect("f32 vector fits into f64");
+        // diagonal movement is as fast as straight one
+        let strength = thrust.magnitude().min(1.0);
+        let mut max_speed = self.cruise_speed();
+        self.velocity *= (-self.config.damping * elapsed).exp();
+        if strength > 0.0 {
+            max_speed *= strength;
+            self.velocity += thrust.normalize() * max_speed * self.config.acceleration * elapsed;
+        }
+        // cruise speed drops when approaching a body, so the camera brakes as well
+        if self.velocity.magnitude2() > max_speed * max_speed {
+            self.velocity = self.velocity.normalize_to(max_speed);
+        }
+        self.position += self.velocity * elapsed;
+        let roll = self.movements.roll.take(now, roll_map);
+        // clockwise roll is negative rotation around the camera Z axis, which points backwards
+        self.orientation = (self.orientation * Quaternion::from_angle_z(Rad(-roll))).normalize();
     }
 }
 
@@ -192,8 +702,17 @@ impl Default for CameraControl {
     fn default() -> Self {
         Self {
             position: Point3::new(0.0, 0.0, 0.0),
-            view_direction: Vector3::new(0.0, 0.0, -1.0),
+            orientation: Quaternion::one(),
+            orientation_mode: OrientationMode::default(),
             movements: Movements::default(),
+            velocity: Vector3::new(0.0, 0.0, 0.0),
+            last_update: None,
+            config: MovementConfig::default(),
+            speed_multiplier: 1.0,
+            surface_distance: 0.0,
+            flight: None,
+            following: None,
+            ground: None,
         }
     }
 }
@@ -0,0 +1,229 @@
+use std::{fs, time::Instant};
+
+use cgmath::{Deg, Rad, Vector2, Vector3};
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs};
+use serde::Deserialize;
+
+use crate::{camera::camera_control::CameraControl, input_map::Action};
+
+/// Actions of gamepad buttons. Sticks and triggers move the camera directly.
+const BUTTON_ACTIONS: &[(Button, Action)] = &[
+    (Button::LeftTrigger, Action::FollowPrevious),
+    (Button::RightTrigger, Action::FollowNext),
+    (Button::DPadLeft, Action::RollLeft),
+    (Button::DPadRight, Action::RollRight),
+    (Button::North, Action::ToggleOrientationMode),
+    (Button::West, Action::ToggleOrthographic),
+    (Button::Select, Action::ShowMap),
+    (Button::Start, Action::TimePause),
+];
+
+/// Response of gamepad sticks and triggers.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    /// Radial dead zone of the sticks as a fraction of full deflection.
+    pub stick_dead_zone: f32,
+    /// Dead zone of the analog triggers as a fraction of full pull.
+    pub trigger_dead_zone: f32,
+    /// Exponent of the response curve applied after the dead zone. 1 is linear, greater values
+    /// give finer control near the centre.
+    pub response_exponent: f32,
+    /// Turn rate at full deflection of the look stick in degrees per second.
+    pub look_speed: f32,
+    pub invert_look: bool,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            stick_dead_zone: 0.15,
+            trigger_dead_zone: 0.05,
+            response_exponent: 2.0,
+            look_speed: 90.0,
+            invert_look: false,
+        }
+    }
+}
+
+impl GamepadConfig {
+    /// Removes the dead zone from deflection of magnitude up to 1 and applies the response curve.
+    /// Remaining deflection is rescaled, so the response starts at 0 on the edge of the dead zone.
+    fn response(&self, deflection: f32, dead_zone: f32) -> f32 {
+        let magnitude = deflection.abs();
+        if magnitude <= dead_zone {
+            return 0.0;
+        }
+        let magnitude = ((magnitude - dead_zone) / (1.0 - dead_zone)).min(1.0);
+        magnitude.powf(self.response_exponent).copysign(deflection)
+    }
+
+    /// Applies radial dead zone and response curve to stick deflection, keeping its direction.
+    fn stick_response(&self, stick: Vector2<f32>) -> Vector2<f32> {
+        let magnitude = (stick.x * stick.x + stick.y * stick.y).sqrt();
+        if magnitude == 0.0 {
+            return stick;
+        }
+        stick * (self.response(magnitude, self.stick_dead_zone) / magnitude)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ControlsRaw {
+    #[serde(default)]
+    gamepad: GamepadConfig,
+}
+
+/// Loads the `[gamepad]` table of the controls file. Missing values take their defaults.
+pub fn load_gamepad_config(path: &str) -> GamepadConfig {
+    let toml_str = fs::read_to_string(path).unwrap();
+    let raw: ControlsRaw = toml::from_str(&toml_str).unwrap();
+    raw.gamepad
+}
+
+/// Camera control by gamepad. The left stick moves the camera horizontally, triggers move it down
+/// and up and the right stick turns it. The gamepad, which was used last, is in control.
+#[derive(Debug)]
+pub struct GamepadControl {
+    gilrs: Gilrs,
+    active: Option<GamepadId>,
+    config: GamepadConfig,
+    last_update: Option<Instant>,
+}
+
+impl GamepadControl {
+    /// Returns `None` when gamepads are not supported on the platform.
+    pub fn new(config: GamepadConfig) -> Option<Self> {
+        let gilrs = Gilrs::new()
+            .inspect_err(|e| eprintln!("Gamepad support is not available: {e}"))
+            .ok()?;
+        Some(Self {
+            gilrs,
+            active: None,
+            config,
+            last_update: None,
+        })
+    }
+
+    /// Moves and turns the camera by sticks and triggers. Returns actions of pressed and
+    /// released buttons, `true` for pressed ones.
+    pub fn update(
+        &mut self,
+        now: Instant,
+        camera_control: &mut CameraControl,
+    ) -> Vec<(Action, bool)> {
+        let mut actions = Vec::new();
+        while let Some(event) = self.gilrs.next_event() {
+            let (button, pressed) = match event.event {
+                EventType::ButtonPressed(button, _) => (button, true),
+                EventType::ButtonReleased(button, _) => (button, false),
+                EventType::Disconnected if self.active == Some(event.id) => {
+                    self.active = None;
+                    camera_control.move_analog(now, Vector3::new(0.0, 0.0, 0.0));
+                    continue;
+                }
+                EventType::Disconnected => continue,
+                _ => {
+                    self.active = Some(event.id);
+                    continue;
+                }
+            };
+            self.active = Some(event.id);
+            if let Some((_, action)) = BUTTON_ACTIONS.iter().find(|(b, _)| *b == button) {
+                actions.push((*action, pressed));
+            }
+        }
+
+        let elapsed = self.last_update.map_or(0.0, |last| {
+            now.saturating_duration_since(last).as_secs_f32()
+        });
+        self.last_update = Some(now);
+        let Some(gamepad) = self.active.map(|id| self.gilrs.gamepad(id)) else {
+            return actions;
+        };
+
+        let config = &self.config;
+        let trigger = |button| {
+            let value = gamepad.button_data(button).map_or(0.0, |data| data.value());
+            config.response(value, config.trigger_dead_zone)
+        };
+        let movement = config.stick_response(Vector2::new(
+            gamepad.value(Axis::LeftStickX),
+            gamepad.value(Axis::LeftStickY),
+        ));
+        camera_control.move_analog(
+            now,
+            Vector3::new(
+                movement.x,
+                trigger(Button::RightTrigger2) - trigger(Button::LeftTrigger2),
+                movement.y,
+            ),
+        );
+
+        let look = config.stick_response(Vector2::new(
+            gamepad.value(Axis::RightStickX),
+            gamepad.value(Axis::RightStickY),
+        ));
+        if look != Vector2::new(0.0, 0.0) {
+            let turn = Rad::from(Deg(config.look_speed)) * elapsed;
+            let pitch = if config.invert_look { -look.y } else { look.y };
+            // stick to the right turns right
+            camera_control.turn(now, turn * -look.x, turn * pitch);
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINEAR: GamepadConfig = GamepadConfig {
+        stick_dead_zone: 0.2,
+        trigger_dead_zone: 0.1,
+        response_exponent: 1.0,
+        look_speed: 90.0,
+        invert_look: false,
+    };
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn dead_zone() {
+        for deflection in [0.0, 0.05, -0.1, 0.1] {
+            assert_eq!(LINEAR.response(deflection, 0.1), 0.0);
+        }
+        // response starts at 0 on the edge of the dead zone and reaches 1 at full deflection
+        assert_close(LINEAR.response(0.55, 0.1), 0.5);
+        assert_close(LINEAR.response(-0.55, 0.1), -0.5);
+        assert_close(LINEAR.response(1.0, 0.1), 1.0);
+        assert_close(LINEAR.response(1.2, 0.1), 1.0);
+    }
+
+    #[test]
+    fn response_curve() {
+        let config = GamepadConfig {
+            response_exponent: 2.0,
+            ..LINEAR
+        };
+        assert_close(config.response(0.55, 0.1), 0.25);
+        assert_close(config.response(-0.55, 0.1), -0.25);
+    }
+
+    #[test]
+    fn radial_stick_dead_zone() {
+        // each axis is inside the dead zone, but the deflection is not
+        let stick = LINEAR.stick_response(Vector2::new(0.18, 0.18));
+        assert!(stick.x > 0.0 && stick.y > 0.0);
+        assert_close(stick.x, stick.y);
+        assert_eq!(
+            LINEAR.stick_response(Vector2::new(0.12, -0.12)),
+            Vector2::new(0.0, 0.0)
+        );
+        let stick = LINEAR.stick_response(Vector2::new(0.0, -0.6));
+        assert_close(stick.x, 0.0);
+        assert_close(stick.y, -0.5);
+    }
+}
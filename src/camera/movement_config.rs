@@ -0,0 +1,56 @@
+use std::fs;
+
+use serde::Deserialize;
+
+/// Tunables of the camera velocity model.
+///
+/// Cruise speed of the camera is proportional to the distance to the nearest body surface, so the
+/// camera crosses empty space quickly and slows down on approach. Held movement keys accelerate
+/// the camera towards the cruise speed, velocity decays when the keys are released.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct MovementConfig {
+    /// Cruise speed in distances to the nearest body surface per second.
+    pub speed_per_distance: f64,
+    /// Lower bound of the cruise speed in scene units per second.
+    pub min_speed: f64,
+    /// Upper bound of the cruise speed in scene units per second.
+    pub max_speed: f64,
+    /// Acceleration in cruise speeds per second. It has to exceed damping for the camera to reach
+    /// the cruise speed.
+    pub acceleration: f64,
+    /// Exponential decay rate of velocity per second. It applies even when the camera
+    /// accelerates, so it also turns the camera velocity into the direction of movement.
+    pub damping: f64,
+    /// Cruise speed multiplier applied by single scroll wheel step.
+    pub multiplier_step: f64,
+    /// Bounds of the cruise speed multiplier.
+    pub multiplier_range: (f64, f64),
+}
+
+impl Default for MovementConfig {
+    fn default() -> Self {
+        Self {
+            speed_per_distance: 0.5,
+            min_speed: 0.001,
+            max_speed: 500.0,
+            acceleration: 6.0,
+            damping: 3.0,
+            multiplier_step: 1.2,
+            multiplier_range: (0.01, 100.0),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ControlsRaw {
+    #[serde(default)]
+    movement: MovementConfig,
+}
+
+/// Loads the `[movement]` table of the controls file. Missing values take their defaults.
+pub fn load_movement_config(path: &str) -> MovementConfig {
+    let toml_str = fs::read_to_string(path).unwrap();
+    let raw: ControlsRaw = toml::from_str(&toml_str).unwrap();
+    raw.movement
+}
@@ -1,36 +1,124 @@
 use std::{
     fmt::Debug,
     sync::{Arc, Mutex},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
 use winit::{
-    event::{DeviceEvent, ElementState, KeyEvent, MouseButton, WindowEvent},
-    keyboard::{KeyCode, PhysicalKey},
+    dpi::PhysicalPosition,
+    event::{DeviceEvent, ElementState, KeyEvent, MouseScrollDelta, WindowEvent},
+    keyboard::PhysicalKey,
 };
 
-use crate::camera::camera_control::{CameraControl, MovementDirection};
+use crate::{
+    camera::{
+        camera::Camera,
+        camera_control::{CameraControl, Coordinates, MovementDirection, OrientationMode},
+        easing::Easing,
+        gamepad_control::GamepadControl,
+        projection::{Projection, ProjectionMode},
+    },
+    input_map::{Action, Input, InputMap},
+    labels::LabelConfig,
+    picking::Pick,
+    simulation_clock::SimulationClock,
+    solar_object::solar_object::BodyKind,
+    tour::{SEEK_STEP, TourPlayer},
+};
+
+/// Field of view change of a single zoom step.
+const ZOOM_STEP: f32 = 1.1;
+/// Distance of the camera above the Sun in the orrery map view.
+const MAP_HEIGHT: f64 = 2000.0;
+const FLY_TO_DURATION: Duration = Duration::from_secs(3);
+
+/// Request to pick the body under the cursor. Bodies are owned by the scene, so picking is done
+/// outside and its result is passed back to [`MovementControl::picked`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickRequest {
+    pub cursor: PhysicalPosition<f64>,
+    /// Fly to the picked body and follow it.
+    pub follow: bool,
+}
 
+/// Translates user input into camera movements and other actions through the [`InputMap`].
 pub struct MovementControl {
     camera_control: Arc<Mutex<CameraControl>>,
+    projection: Arc<Mutex<Projection>>,
+    clock: Arc<Mutex<SimulationClock>>,
+    label_config: Arc<Mutex<LabelConfig>>,
+    input_map: InputMap,
+    gamepad: Option<GamepadControl>,
+    /// Bodies selected by number keys. The first one is selected by 0, the next ones by 1 to 9.
+    /// Selection cycles through them as well.
+    fly_to_targets: Vec<String>,
+    /// Name of the selected body. Picking can select bodies outside of fly-to targets.
+    selected: Option<String>,
+    cursor: PhysicalPosition<f64>,
+    pick_request: Option<PickRequest>,
+    export_requested: bool,
+    screenshot_requested: bool,
+    /// Bodies picked for measurement in measurement mode, the observer first.
+    measured: Option<Vec<String>>,
+    inspector_visible: bool,
+    tour: TourPlayer,
     mouse_pressed: bool,
     mouse_dragged_fn: Box<dyn Fn(bool)>,
 }
 
 impl MovementControl {
     pub fn new(
-        camera_control: Arc<Mutex<CameraControl>>,
+        camera: Camera,
+        clock: Arc<Mutex<SimulationClock>>,
+        label_config: Arc<Mutex<LabelConfig>>,
+        input_map: InputMap,
+        gamepad: Option<GamepadControl>,
+        fly_to_targets: Vec<String>,
         mouse_dragged_fn: impl Fn(bool) + 'static,
     ) -> Self {
+        for conflict in input_map.conflict_report() {
+            eprintln!("Input bindings: {conflict}");
+        }
         MovementControl {
-            camera_control,
+            tour: TourPlayer::new(&camera, clock.clone(), label_config.clone()),
+            camera_control: camera.camera_control,
+            projection: camera.projection,
+            clock,
+            label_config,
+            input_map,
+            gamepad,
+            fly_to_targets,
+            selected: None,
+            cursor: PhysicalPosition::new(0.0, 0.0),
+            pick_request: None,
+            export_requested: false,
+            screenshot_requested: false,
+            measured: None,
+            inspector_visible: false,
             mouse_pressed: false,
             mouse_dragged_fn: Box::new(mouse_dragged_fn),
         }
     }
 
+    /// Advances tour playback and applies continuous input of the gamepad.
+    pub fn update(&mut self, now: Instant) {
+        self.tour.update(now);
+        let Some(gamepad) = &mut self.gamepad else {
+            return;
+        };
+        let actions = gamepad.update(now, &mut self.camera_control.lock().unwrap());
+        for (action, pressed) in actions {
+            if action.is_held() {
+                self.hold(action, pressed);
+            } else if pressed {
+                self.trigger(action);
+            }
+        }
+    }
+
     pub fn process_window_event(&mut self, event: WindowEvent) {
-        match event {
+        let (input, state) = match event {
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
@@ -40,72 +128,339 @@ impl MovementControl {
                         ..
                     },
                 ..
-            } => {
-                use KeyCode::*;
-                let pressed = state == ElementState::Pressed;
-                match key {
-                    KeyW => self
-                        .camera_control
-                        .lock()
-                        .unwrap()
-                        .move_forw_backw(Instant::now(), MovementDirection::positive(pressed)),
-                    KeyS => self
-                        .camera_control
-                        .lock()
-                        .unwrap()
-                        .move_forw_backw(Instant::now(), MovementDirection::negative(pressed)),
-                    KeyA => self
-                        .camera_control
-                        .lock()
-                        .unwrap()
-                        .move_sideways(Instant::now(), MovementDirection::negative(pressed)),
-                    KeyD => self
-                        .camera_control
-                        .lock()
-                        .unwrap()
-                        .move_sideways(Instant::now(), MovementDirection::positive(pressed)),
-                    Space => self
-                        .camera_control
-                        .lock()
-                        .unwrap()
-                        .move_vertical(Instant::now(), MovementDirection::positive(pressed)),
-                    ControlLeft => self
-                        .camera_control
-                        .lock()
-                        .unwrap()
-                        .move_vertical(Instant::now(), MovementDirection::negative(pressed)),
-                    _ => {}
+            } => (Input::Key(key), state),
+            WindowEvent::MouseInput { state, button, .. } => (Input::Mouse(button), state),
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = position;
+                return;
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let steps = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    // roughly one step per line of text
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 20.0,
+                };
+                match self.input_map.scroll() {
+                    Some(Action::ScrollZoom) => {
+                        self.projection.lock().unwrap().zoom(ZOOM_STEP.powf(steps))
+                    }
+                    Some(Action::ScrollSpeed) => {
+                        self.camera_control.lock().unwrap().scale_speed(steps)
+                    }
+                    Some(action) => self.trigger(action),
+                    None => {}
                 }
+                return;
             }
-            WindowEvent::MouseInput { state, button, .. } => match (state, button) {
-                (ElementState::Pressed, MouseButton::Right) => {
-                    self.mouse_pressed = true;
-                    (self.mouse_dragged_fn)(true);
+            _ => return,
+        };
+        match state {
+            ElementState::Pressed => {
+                if let Some(action) = self.input_map.press(input) {
+                    if action.is_held() {
+                        self.hold(action, true);
+                    } else {
+                        self.trigger(action);
+                    }
                 }
-                (ElementState::Released, MouseButton::Right) => {
-                    self.mouse_pressed = false;
-                    (self.mouse_dragged_fn)(false);
+            }
+            ElementState::Released => {
+                for action in self.input_map.release(input) {
+                    self.hold(action, false);
                 }
-                _ => {}
-            },
+            }
+        }
+    }
+
+    /// Starts or stops held action.
+    fn hold(&mut self, action: Action, pressed: bool) {
+        let now = Instant::now();
+        // manual movement takes the camera over from the tour
+        if pressed && action != Action::Look {
+            self.tour.pause(now);
+        }
+        let mut camera_control = self.camera_control.lock().unwrap();
+        match action {
+            Action::MoveForward => {
+                camera_control.move_forw_backw(now, MovementDirection::positive(pressed))
+            }
+            Action::MoveBackward => {
+                camera_control.move_forw_backw(now, MovementDirection::negative(pressed))
+            }
+            Action::MoveLeft => {
+                camera_control.move_sideways(now, MovementDirection::negative(pressed))
+            }
+            Action::MoveRight => {
+                camera_control.move_sideways(now, MovementDirection::positive(pressed))
+            }
+            Action::MoveUp => {
+                camera_control.move_vertical(now, MovementDirection::positive(pressed))
+            }
+            Action::MoveDown => {
+                camera_control.move_vertical(now, MovementDirection::negative(pressed))
+            }
+            Action::RollLeft => camera_control.roll(now, MovementDirection::negative(pressed)),
+            Action::RollRight => camera_control.roll(now, MovementDirection::positive(pressed)),
+            Action::Look => {
+                self.mouse_pressed = pressed;
+                (self.mouse_dragged_fn)(pressed);
+            }
             _ => {}
         }
     }
 
-    pub fn process_device_event(&mut self, event: DeviceEvent) {
-        match event {
-            DeviceEvent::MouseMotion {
-                delta: (delta_x, delta_y),
-            } => {
-                if self.mouse_pressed {
-                    self.camera_control.lock().unwrap().rotate(
-                        Instant::now(),
-                        delta_x as f32,
-                        delta_y as f32,
-                    );
+    /// Performs action triggered by a single press.
+    fn trigger(&mut self, action: Action) {
+        match action {
+            Action::ZoomIn => self.projection.lock().unwrap().zoom(ZOOM_STEP),
+            Action::ZoomOut => self.projection.lock().unwrap().zoom(1.0 / ZOOM_STEP),
+            Action::ToggleOrthographic => self.toggle_orthographic(),
+            Action::ToggleOrientationMode => self.toggle_orientation_mode(),
+            Action::ShowMap => self.show_map(),
+            Action::Pick | Action::PickAndFollow => {
+                self.pick_request = Some(PickRequest {
+                    cursor: self.cursor,
+                    follow: action == Action::PickAndFollow,
+                })
+            }
+            Action::TimeFaster => self.clock.lock().unwrap().faster(),
+            Action::TimeSlower => self.clock.lock().unwrap().slower(),
+            Action::TimePause => self.clock.lock().unwrap().toggle_pause(),
+            Action::SelectNext => self.select_offset(1),
+            Action::SelectPrevious => self.select_offset(-1),
+            Action::FlyToSelected => self.fly_to_selected(),
+            Action::FollowNext => {
+                self.select_offset(1);
+                self.fly_to_selected();
+            }
+            Action::FollowPrevious => {
+                self.select_offset(-1);
+                self.fly_to_selected();
+            }
+            Action::StopFollowing => self.camera_control.lock().unwrap().stop_following(),
+            Action::StandOnSelected => self.stand_on_selected(None),
+            Action::ToggleLabels => self.label_config.lock().unwrap().toggle(None),
+            Action::ToggleStarLabels => self
+                .label_config
+                .lock()
+                .unwrap()
+                .toggle(Some(BodyKind::Star)),
+            Action::TogglePlanetLabels => self
+                .label_config
+                .lock()
+                .unwrap()
+                .toggle(Some(BodyKind::Planet)),
+            Action::ToggleDwarfPlanetLabels => self
+                .label_config
+                .lock()
+                .unwrap()
+                .toggle(Some(BodyKind::DwarfPlanet)),
+            Action::ToggleMoonLabels => self
+                .label_config
+                .lock()
+                .unwrap()
+                .toggle(Some(BodyKind::Moon)),
+            Action::ToggleInspector => self.inspector_visible = !self.inspector_visible,
+            Action::ToggleMeasurement => {
+                self.measured = match self.measured {
+                    Some(_) => None,
+                    None => Some(Vec::new()),
                 }
             }
-            _ => {}
+            Action::ToggleTourPlayback => self.tour.toggle_playback(Instant::now()),
+            Action::SeekTourBackward => {
+                let now = Instant::now();
+                let time = self.tour.time().saturating_sub(SEEK_STEP);
+                self.tour.seek(now, time);
+            }
+            Action::SeekTourForward => {
+                let now = Instant::now();
+                let time = self.tour.time() + SEEK_STEP;
+                self.tour.seek(now, time);
+            }
+            Action::ToggleTourRecording => self.tour.toggle_recording(Instant::now()),
+            Action::RecordKeyframe => self.tour.record_keyframe(Instant::now()),
+            Action::ExportFrames => self.export_requested = true,
+            Action::Screenshot => self.screenshot_requested = true,
+            action => {
+                if let Some(index) = action.fly_to_index() {
+                    self.fly_to(index);
+                }
+            }
+        }
+    }
+
+    /// Takes pending request to pick the body under the cursor.
+    pub fn take_pick_request(&mut self) -> Option<PickRequest> {
+        self.pick_request.take()
+    }
+
+    /// Takes pending request to export a frame sequence.
+    pub fn take_export_request(&mut self) -> bool {
+        std::mem::take(&mut self.export_requested)
+    }
+
+    /// Takes pending request to save a screenshot.
+    pub fn take_screenshot_request(&mut self) -> bool {
+        std::mem::take(&mut self.screenshot_requested)
+    }
+
+    /// Selects the picked body, picking empty space clears the selection. In measurement mode the
+    /// body is measured instead.
+    pub fn picked(&mut self, request: PickRequest, pick: Option<Pick>) {
+        if let Some(measured) = &mut self.measured {
+            if let Some(pick) = pick {
+                // the third body starts a new measurement
+                if measured.len() == 2 {
+                    measured.clear();
+                }
+                measured.push(pick.name);
+            }
+            return;
+        }
+        match pick {
+            Some(pick) => {
+                self.selected = Some(pick.name);
+                if request.follow {
+                    self.fly_to_selected();
+                }
+            }
+            None => self.selected = None,
+        }
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.selected.as_deref()
+    }
+
+    pub fn select(&mut self, name: Option<String>) {
+        self.selected = name;
+    }
+
+    /// Bodies picked in measurement mode, the observer first. `None` outside of measurement mode.
+    pub fn measured(&self) -> Option<&[String]> {
+        self.measured.as_deref()
+    }
+
+    pub fn inspector_visible(&self) -> bool {
+        self.inspector_visible
+    }
+
+    pub fn tour_mut(&mut self) -> &mut TourPlayer {
+        &mut self.tour
+    }
+
+    /// Moves selection by `offset` fly-to targets, wrapping around the list.
+    fn select_offset(&mut self, offset: isize) {
+        if self.fly_to_targets.is_empty() {
+            return;
+        }
+        let current = self
+            .selected
+            .as_ref()
+            .and_then(|selected| self.fly_to_targets.iter().position(|t| t == selected));
+        let index = match current {
+            Some(current) => {
+                (current as isize + offset).rem_euclid(self.fly_to_targets.len() as isize) as usize
+            }
+            None => 0,
+        };
+        self.selected = Some(self.fly_to_targets[index].clone());
+    }
+
+    pub fn fly_to_selected(&mut self) {
+        if let Some(target) = &self.selected {
+            self.camera_control.lock().unwrap().fly_to(
+                Instant::now(),
+                target,
+                FLY_TO_DURATION,
+                Easing::EaseInOut,
+            );
+        }
+    }
+
+    /// Places the observer on the selected body at `coordinates`, latitude and longitude, or
+    /// under the camera.
+    pub fn stand_on_selected(&mut self, coordinates: Option<Coordinates>) {
+        if let Some(target) = &self.selected {
+            self.camera_control
+                .lock()
+                .unwrap()
+                .stand_on(Instant::now(), target, coordinates);
+        }
+    }
+
+    /// Body the observer stands on and its latitude and longitude, once resolved.
+    pub fn ground(&self) -> Option<(String, Option<Coordinates>)> {
+        self.camera_control
+            .lock()
+            .unwrap()
+            .ground()
+            .map(|(target, coordinates)| (target.to_string(), coordinates))
+    }
+
+    fn fly_to(&mut self, index: usize) {
+        if let Some(target) = self.fly_to_targets.get(index) {
+            self.selected = Some(target.clone());
+            self.fly_to_selected();
+        }
+    }
+
+    /// Switches between perspective and orthographic projection. Orthographic projection keeps the
+    /// scale of the scene at the distance of the Sun.
+    fn toggle_orthographic(&self) {
+        let now = Instant::now();
+        let distance = self
+            .camera_control
+            .lock()
+            .unwrap()
+            .position()
+            .to_vec()
+            .magnitude();
+        let mut projection = self.projection.lock().unwrap();
+        let mode = match projection.mode() {
+            ProjectionMode::Perspective => ProjectionMode::Orthographic,
+            ProjectionMode::Orthographic => ProjectionMode::Perspective,
+        };
+        projection.set_mode(now, mode, distance as f32);
+    }
+
+    /// Switches between clamped and free spacecraft like camera rotation.
+    fn toggle_orientation_mode(&self) {
+        let mut camera_control = self.camera_control.lock().unwrap();
+        let mode = match camera_control.orientation_mode() {
+            OrientationMode::Clamped => OrientationMode::Free,
+            OrientationMode::Free => OrientationMode::Clamped,
+        };
+        camera_control.set_orientation_mode(Instant::now(), mode);
+    }
+
+    /// Orrery map preset. Looks down the ecliptic pole at the Sun with orthographic projection.
+    fn show_map(&self) {
+        let now = Instant::now();
+        self.camera_control.lock().unwrap().look_from(
+            now,
+            Point3::new(0.0, MAP_HEIGHT, 0.0),
+            -Vector3::unit_y(),
+        );
+        self.projection.lock().unwrap().set_mode(
+            now,
+            ProjectionMode::Orthographic,
+            MAP_HEIGHT as f32,
+        );
+    }
+
+    pub fn process_device_event(&mut self, event: DeviceEvent) {
+        if let DeviceEvent::MouseMotion {
+            delta: (delta_x, delta_y),
+        } = event
+            && self.mouse_pressed
+        {
+            self.camera_control.lock().unwrap().rotate(
+                Instant::now(),
+                delta_x as f32,
+                delta_y as f32,
+            );
         }
     }
 }
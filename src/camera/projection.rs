@@ -1,30 +1,88 @@
-use cgmath::{Deg, Matrix4, perspective};
+use std::time::{Duration, Instant};
+
+use cgmath::{Deg, Matrix4, Rad, Vector3};
 use winit::dpi::PhysicalSize;
 
-#[rustfmt::skip]
-pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
-    1.0, 0.0, 0.0, 0.0,
-    0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.5,
-    0.0, 0.0, 0.0, 1.0,
-);
+use crate::camera::easing::Easing;
+
+/// Narrowest and widest allowed field of view in degrees.
+const FOV_RANGE: (f32, f32) = (0.5, 120.0);
+/// Depth range of orthographic projection. Unlike perspective one, it cannot be infinite.
+const ORTHOGRAPHIC_FAR: f32 = 100000.0;
+const TRANSITION_DURATION: Duration = Duration::from_millis(800);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProjectionMode {
+    #[default]
+    Perspective,
+    Orthographic,
+}
+
+/// Animated change of projection mode. Stores how much orthographic the projection was when the
+/// transition began.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Transition {
+    start: Instant,
+    from: f32,
+}
+
+/// Part of the view drawn on its own, when the whole view does not fit into a texture. The view is
+/// split into `columns` × `rows` equal tiles, row 0 is at the top.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub columns: u32,
+    pub rows: u32,
+    pub column: u32,
+    pub row: u32,
+}
+
+impl Tile {
+    /// Scale and offset mapping clip space X and Y of the whole view to clip space of the tile.
+    pub fn clip_scale_offset(&self) -> [f32; 4] {
+        let (columns, rows) = (self.columns as f32, self.rows as f32);
+        // centre of the tile in the whole view
+        let x = -1.0 + (2 * self.column + 1) as f32 / columns;
+        let y = 1.0 - (2 * self.row + 1) as f32 / rows;
+        [columns, rows, -columns * x, -rows * y]
+    }
+
+    pub fn clip_transform(&self) -> Matrix4<f32> {
+        let [scale_x, scale_y, offset_x, offset_y] = self.clip_scale_offset();
+        Matrix4::from_translation(Vector3::new(offset_x, offset_y, 0.0))
+            * Matrix4::from_nonuniform_scale(scale_x, scale_y, 1.0)
+    }
+}
+
+/// Scale and offset of clip space without tiles.
+pub const NO_TILE: [f32; 4] = [1.0, 1.0, 0.0, 0.0];
 
+/// Perspective or orthographic projection with reversed depth. Depth is 1 on the near plane and
+/// approaches 0 with distance. Perspective projection has no far plane, which together with
+/// floating point depth buffer keeps precision at both close and astronomical distances.
+///
+/// Orthographic projection shows the same area as perspective one does at the focus distance.
+/// Projections are interpolated during transitions, so the focus plane keeps its size and the
+/// scene does not jump.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Projection {
     aspect_ratio: f32,
     fov: f32,
     near: f32,
-    far: f32,
+    focus_distance: f32,
+    mode: ProjectionMode,
+    transition: Option<Transition>,
 }
 
 impl Projection {
-    pub fn new(size: PhysicalSize<u32>, fov: f32, near: f32, far: f32) -> Self {
+    pub fn new(size: PhysicalSize<u32>, fov: f32, near: f32) -> Self {
         let aspect_ratio = size.width as f32 / size.height as f32;
         Projection {
             aspect_ratio,
             fov,
             near,
-            far,
+            focus_distance: 1.0,
+            mode: ProjectionMode::Perspective,
+            transition: None,
         }
     }
 
@@ -32,13 +90,76 @@ impl Projection {
         self.aspect_ratio = size.width as f32 / size.height as f32;
     }
 
-    pub fn matrix(&self) -> Matrix4<f32> {
-        OPENGL_TO_WGPU_MATRIX * perspective(Deg(self.fov), self.aspect_ratio, self.near, self.far)
+    pub fn mode(&self) -> ProjectionMode {
+        self.mode
+    }
+
+    /// Narrows field of view for `factor` greater than 1, widens it otherwise. Orthographic
+    /// projection is zoomed the same way.
+    pub fn zoom(&mut self, factor: f32) {
+        let half_fov = Rad::from(Deg(self.fov)) / 2.0;
+        let half_fov = Rad((half_fov.0.tan() / factor).atan());
+        self.fov = Deg::from(half_fov * 2.0).0.clamp(FOV_RANGE.0, FOV_RANGE.1);
+    }
+
+    /// Switches to `mode` with animated transition. Orthographic projection shows the area which
+    /// perspective projection shows at `focus_distance`. Focus distance is kept when switching
+    /// back to perspective, so the transition mirrors the previous one.
+    pub fn set_mode(&mut self, now: Instant, mode: ProjectionMode, focus_distance: f32) {
+        if mode == ProjectionMode::Orthographic {
+            self.focus_distance = focus_distance.max(self.near);
+        }
+        if mode == self.mode {
+            return;
+        }
+        self.transition = Some(Transition {
+            start: now,
+            from: self.orthographic_weight(now),
+        });
+        self.mode = mode;
+    }
+
+    /// How much orthographic the projection is. 0 is perspective and 1 orthographic projection.
+    fn orthographic_weight(&self, now: Instant) -> f32 {
+        let to = match self.mode {
+            ProjectionMode::Perspective => 0.0,
+            ProjectionMode::Orthographic => 1.0,
+        };
+        match self.transition {
+            Some(Transition { start, from }) => {
+                let t = Easing::EaseInOut
+                    .apply((now - start).as_secs_f32() / TRANSITION_DURATION.as_secs_f32());
+                from + (to - from) * t
+            }
+            None => to,
+        }
+    }
+
+    pub fn matrix(&self, now: Instant) -> Matrix4<f32> {
+        let focal_length = 1.0 / (Rad::from(Deg(self.fov)) / 2.0).0.tan();
+        #[rustfmt::skip]
+        let perspective = Matrix4::new(
+            focal_length / self.aspect_ratio, 0.0, 0.0, 0.0,
+            0.0, focal_length, 0.0, 0.0,
+            0.0, 0.0, 0.0, -1.0,
+            0.0, 0.0, self.near, 0.0,
+        );
+        let scale = focal_length / self.focus_distance;
+        let depth_range = ORTHOGRAPHIC_FAR - self.near;
+        #[rustfmt::skip]
+        let orthographic = Matrix4::new(
+            scale / self.aspect_ratio, 0.0, 0.0, 0.0,
+            0.0, scale, 0.0, 0.0,
+            0.0, 0.0, 1.0 / depth_range, 0.0,
+            0.0, 0.0, ORTHOGRAPHIC_FAR / depth_range, 1.0,
+        );
+        let weight = self.orthographic_weight(now);
+        perspective * (1.0 - weight) + orthographic * weight
     }
 }
 
 impl Default for Projection {
     fn default() -> Self {
-        Projection::new(PhysicalSize::new(1, 1), 45.0, 0.1, 100.0)
+        Projection::new(PhysicalSize::new(1, 1), 45.0, 0.001)
     }
 }
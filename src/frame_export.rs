@@ -0,0 +1,173 @@
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Child, ChildStdin, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use wgpu::{Device, Queue};
+use winit::dpi::PhysicalSize;
+
+use crate::render_target::{OffscreenTarget, RenderTarget, RenderTargetConfig};
+
+/// Settings of the frame sequence export.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    /// Length of the export in frames.
+    pub frames: u32,
+    /// Directory numbered PNG frames are written to.
+    pub directory: String,
+    /// Writes PNG frames. Disabling it makes sense only with a video.
+    pub png: bool,
+    /// Video file frames are encoded to, if ffmpeg is installed.
+    pub video: Option<String>,
+    /// Plays the loaded tour from its start. Runs without a tour start from the current state, so
+    /// they are identical only if the state is.
+    pub play_tour: bool,
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self {
+        Self {
+            width: 1920,
+            height: 1080,
+            fps: 30,
+            frames: 300,
+            directory: "export".to_string(),
+            png: true,
+            video: None,
+            play_tour: true,
+        }
+    }
+}
+
+pub fn load_export_config(path: &str) -> ExportConfig {
+    let toml_str = fs::read_to_string(path).unwrap();
+    toml::from_str(&toml_str).unwrap()
+}
+
+/// Export of a frame sequence. Time of each frame is the start of the export plus a fixed step, so
+/// the result does not depend on how long rendering takes. Frames are drawn offscreen at the export
+/// resolution and read back.
+#[derive(Debug)]
+pub struct FrameExport {
+    config: ExportConfig,
+    target: OffscreenTarget,
+    start: Instant,
+    frame_step: Duration,
+    /// Index of the frame being drawn.
+    frame: u32,
+    ffmpeg: Option<(Child, ChildStdin)>,
+}
+
+impl FrameExport {
+    /// Starts export at `now`, which becomes time of the first frame.
+    pub fn new(
+        device: &Device,
+        render_target: &RenderTargetConfig,
+        config: ExportConfig,
+        now: Instant,
+    ) -> Self {
+        if config.png
+            && let Err(e) = fs::create_dir_all(&config.directory)
+        {
+            eprintln!(
+                "Failed to create export directory {}: {e}",
+                config.directory
+            );
+        }
+        let size = PhysicalSize::new(config.width, config.height);
+        let ffmpeg = config
+            .video
+            .as_ref()
+            .and_then(|video| spawn_ffmpeg(video, size, config.fps));
+        println!(
+            "Exporting {} frames at {}x{}, {} FPS",
+            config.frames, config.width, config.height, config.fps
+        );
+        Self {
+            target: OffscreenTarget::new(device, render_target, size),
+            start: now,
+            frame_step: Duration::from_secs(1) / config.fps.max(1),
+            frame: 0,
+            ffmpeg,
+            config,
+        }
+    }
+
+    /// Time of the frame being drawn.
+    pub fn now(&self) -> Instant {
+        self.start + self.frame_step * self.frame
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.target.size()
+    }
+
+    pub fn render_target(&self) -> RenderTarget {
+        self.target.render_target()
+    }
+
+    /// Writes the drawn frame and moves to the next one. Returns `false`, when all frames are
+    /// written.
+    pub fn write_frame(&mut self, device: &Device, queue: &Queue) -> bool {
+        let image = self.target.read(device, queue);
+        if self.config.png {
+            let path = PathBuf::from(&self.config.directory).join(format!("{:06}.png", self.frame));
+            if let Err(e) = image.save(&path) {
+                eprintln!("Failed to write frame {}: {e}", path.display());
+            }
+        }
+        if let Some((_, stdin)) = &mut self.ffmpeg
+            && let Err(e) = stdin.write_all(image.as_raw())
+        {
+            eprintln!("Failed to pipe frame to ffmpeg, video is not encoded further: {e}");
+            self.ffmpeg = None;
+        }
+        self.frame += 1;
+        self.frame < self.config.frames
+    }
+
+    /// Closes the video and waits for ffmpeg to encode it.
+    pub fn finish(self) {
+        if let Some((mut child, stdin)) = self.ffmpeg {
+            drop(stdin);
+            match child.wait() {
+                Ok(status) if status.success() => {}
+                Ok(status) => eprintln!("ffmpeg failed with {status}"),
+                Err(e) => eprintln!("Failed to wait for ffmpeg: {e}"),
+            }
+        }
+        println!("Exported {} frames", self.frame);
+    }
+}
+
+/// Starts ffmpeg encoding raw RGBA frames from its input to `video`. Missing ffmpeg is reported and
+/// only PNG frames are written.
+fn spawn_ffmpeg(video: &str, size: PhysicalSize<u32>, fps: u32) -> Option<(Child, ChildStdin)> {
+    let mut child = Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+        ])
+        .args(["-s", &format!("{}x{}", size.width, size.height)])
+        .args(["-r", &fps.to_string(), "-i", "-"])
+        .args(["-c:v", "libx264", "-pix_fmt", "yuv420p", video])
+        .stdin(Stdio::piped())
+        .spawn()
+        .inspect_err(|e| eprintln!("Failed to start ffmpeg, video {video} is not encoded: {e}"))
+        .ok()?;
+    let stdin = child.stdin.take()?;
+    Some((child, stdin))
+}
@@ -0,0 +1,73 @@
+// Fast approximate anti-aliasing. Blurs the finished frame along edges found by luma contrast.
+// Used when multisampling is not available or in addition to it.
+
+@group(0) @binding(0)
+var frame_tex: texture_2d<f32>;
+@group(0) @binding(1)
+var frame_sampler: sampler;
+
+// edges with lower contrast relative to the brightest neighbour are kept
+const EDGE_THRESHOLD: f32 = 0.125;
+// dark edges with lower absolute contrast are kept
+const EDGE_THRESHOLD_MIN: f32 = 0.0312;
+const REDUCE_MUL: f32 = 0.125;
+const SPAN_MAX: f32 = 8.0;
+
+struct FxaaOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Single triangle covering the whole screen.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> FxaaOutput {
+    let ndc = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u)) * 2.0 - 1.0;
+    var out: FxaaOutput;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    out.uv = vec2<f32>(ndc.x, -ndc.y) * 0.5 + 0.5;
+    return out;
+}
+
+fn luma(color: vec3<f32>) -> f32 {
+    return dot(color, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn sample(uv: vec2<f32>) -> vec3<f32> {
+    return textureSampleLevel(frame_tex, frame_sampler, uv, 0.0).rgb;
+}
+
+@fragment
+fn fs_main(in: FxaaOutput) -> @location(0) vec4<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(frame_tex));
+    let center = sample(in.uv);
+    let luma_m = luma(center);
+    let luma_nw = luma(sample(in.uv + vec2<f32>(-1.0, -1.0) * texel));
+    let luma_ne = luma(sample(in.uv + vec2<f32>(1.0, -1.0) * texel));
+    let luma_sw = luma(sample(in.uv + vec2<f32>(-1.0, 1.0) * texel));
+    let luma_se = luma(sample(in.uv + vec2<f32>(1.0, 1.0) * texel));
+    let luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    let luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+    if luma_max - luma_min < max(EDGE_THRESHOLD_MIN, luma_max * EDGE_THRESHOLD) {
+        return vec4<f32>(center, 1.0);
+    }
+
+    // blur direction is along the edge, perpendicular to the luma gradient
+    var direction = vec2<f32>(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se),
+    );
+    let reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, 1.0 / 128.0);
+    let scale = 1.0 / (min(abs(direction.x), abs(direction.y)) + reduce);
+    direction = clamp(direction * scale, vec2<f32>(-SPAN_MAX), vec2<f32>(SPAN_MAX)) * texel;
+
+    let near = 0.5 * (
+        sample(in.uv + direction * (1.0 / 3.0 - 0.5)) + sample(in.uv + direction * (2.0 / 3.0 - 0.5))
+    );
+    let far = near * 0.5 + 0.25 * (sample(in.uv - direction * 0.5) + sample(in.uv + direction * 0.5));
+    // the far samples may cross another edge, the near ones are used then
+    let luma_far = luma(far);
+    if luma_far < luma_min || luma_far > luma_max {
+        return vec4<f32>(near, 1.0);
+    }
+    return vec4<f32>(far, 1.0);
+}
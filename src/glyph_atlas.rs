@@ -0,0 +1,157 @@
+use std::{collections::HashMap, fs};
+
+use ab_glyph::{Font, FontVec, GlyphId, OutlinedGlyph, PxScale, ScaleFont, point};
+use image::{GrayImage, Luma};
+
+const ATLAS_WIDTH: u32 = 512;
+/// Empty space around glyphs, so linear filtering does not bleed neighbours in.
+const PADDING: u32 = 1;
+/// Drawn for characters, which are not in the atlas.
+const FALLBACK: char = '?';
+
+/// Glyph rasterized into the atlas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AtlasGlyph {
+    /// Top left corner relative to the pen position on the baseline, in pixels.
+    offset: [f32; 2],
+    size: [f32; 2],
+    /// Texture coordinates of the top left and bottom right corners.
+    uv: [f32; 4],
+}
+
+/// Glyph with its top left corner in the atlas. It is rasterized once the atlas size is known.
+struct Placement {
+    outlined: OutlinedGlyph,
+    x: u32,
+    y: u32,
+}
+
+/// Glyph of laid out text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphQuad {
+    /// Top left corner relative to the top left corner of the text, in whole pixels.
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub uv: [f32; 4],
+}
+
+/// Characters of a font rasterized at single size into one coverage texture. Glyphs are packed in
+/// rows in the order of characters.
+#[derive(Debug)]
+pub struct GlyphAtlas {
+    font: FontVec,
+    scale: PxScale,
+    /// Characters without outline, e.g. space, take only advance and have no glyph.
+    glyphs: HashMap<char, Option<AtlasGlyph>>,
+    image: GrayImage,
+}
+
+impl GlyphAtlas {
+    /// Rasterizes `chars` of the font at `path` with `size` pixels between ascender and
+    /// descender.
+    pub fn new(path: &str, size: f32, chars: impl IntoIterator<Item = char>) -> Self {
+        let font = FontVec::try_from_vec(fs::read(path).unwrap()).expect("Font is valid");
+        let scale = PxScale::from(size);
+        let mut chars = chars.into_iter().chain([FALLBACK]).collect::<Vec<_>>();
+        chars.sort();
+        chars.dedup();
+
+        let mut placed: Vec<(char, Option<Placement>)> = Vec::new();
+        let (mut x, mut y, mut row_height) = (PADDING, PADDING, 0);
+        for c in chars {
+            let glyph = font
+                .glyph_id(c)
+                .with_scale_and_position(scale, point(0.0, 0.0));
+            let Some(outlined) = font.outline_glyph(glyph) else {
+                placed.push((c, None));
+                continue;
+            };
+            let bounds = outlined.px_bounds();
+            let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+            if x + width + PADDING > ATLAS_WIDTH {
+                x = PADDING;
+                y += row_height + PADDING;
+                row_height = 0;
+            }
+            placed.push((c, Some(Placement { outlined, x, y })));
+            x += width + PADDING;
+            row_height = row_height.max(height);
+        }
+
+        let atlas_height = (y + row_height + PADDING).next_power_of_two();
+        let mut image = GrayImage::new(ATLAS_WIDTH, atlas_height);
+        let (atlas_width, atlas_height) = (ATLAS_WIDTH as f32, atlas_height as f32);
+        let glyphs = placed
+            .into_iter()
+            .map(|(c, placement)| {
+                let glyph = placement.map(|Placement { outlined, x, y }| {
+                    outlined.draw(|gx, gy, coverage| {
+                        image.put_pixel(x + gx, y + gy, Luma([(coverage * 255.0) as u8]));
+                    });
+                    let bounds = outlined.px_bounds();
+                    let (x, y) = (x as f32, y as f32);
+                    AtlasGlyph {
+                        offset: [bounds.min.x, bounds.min.y],
+                        size: [bounds.width(), bounds.height()],
+                        uv: [
+                            x / atlas_width,
+                            y / atlas_height,
+                            (x + bounds.width()) / atlas_width,
+                            (y + bounds.height()) / atlas_height,
+                        ],
+                    }
+                });
+                (c, glyph)
+            })
+            .collect();
+
+        Self {
+            font,
+            scale,
+            glyphs,
+            image,
+        }
+    }
+
+    pub fn image(&self) -> &GrayImage {
+        &self.image
+    }
+
+    /// Height of a line of text in pixels.
+    pub fn line_height(&self) -> f32 {
+        self.font.as_scaled(self.scale).height().ceil()
+    }
+
+    fn glyph(&self, c: char) -> (char, Option<AtlasGlyph>) {
+        match self.glyphs.get(&c) {
+            Some(glyph) => (c, *glyph),
+            None => (FALLBACK, self.glyphs[&FALLBACK]),
+        }
+    }
+
+    /// Lays out single line of text and returns its glyphs and width in pixels.
+    pub fn layout(&self, text: &str) -> (Vec<GlyphQuad>, f32) {
+        let font = self.font.as_scaled(self.scale);
+        let ascent = font.ascent().round();
+        let mut quads = Vec::with_capacity(text.len());
+        let mut pen = 0.0;
+        let mut previous: Option<GlyphId> = None;
+        for c in text.chars() {
+            let (c, glyph) = self.glyph(c);
+            let id = font.glyph_id(c);
+            if let Some(previous) = previous {
+                pen += font.kern(previous, id);
+            }
+            if let Some(glyph) = glyph {
+                quads.push(GlyphQuad {
+                    position: [(pen + glyph.offset[0]).round(), ascent + glyph.offset[1]],
+                    size: glyph.size,
+                    uv: glyph.uv,
+                });
+            }
+            pen += font.h_advance(id);
+            previous = Some(id);
+        }
+        (quads, pen.ceil())
+    }
+}
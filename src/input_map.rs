@@ -0,0 +1,541 @@
+use std::{collections::BTreeMap, fs};
+
+use serde::{
+    Deserialize,
+    de::{IntoDeserializer, value::Error},
+};
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+/// Actions triggered by user input. Bindings refer to them by their names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    RollLeft,
+    RollRight,
+    /// Rotates the camera by mouse movement while held.
+    Look,
+    /// Selects the body under the cursor.
+    Pick,
+    /// Selects the body under the cursor, flies to it and follows it.
+    PickAndFollow,
+    ZoomIn,
+    ZoomOut,
+    /// Zooms by scroll wheel, can be bound only to the wheel.
+    ScrollZoom,
+    /// Changes camera speed by scroll wheel, can be bound only to the wheel.
+    ScrollSpeed,
+    ToggleOrthographic,
+    ToggleOrientationMode,
+    ShowMap,
+    TimeFaster,
+    TimeSlower,
+    TimePause,
+    SelectNext,
+    SelectPrevious,
+    FlyToSelected,
+    /// Selects the next body, flies to it and follows it.
+    FollowNext,
+    FollowPrevious,
+    StopFollowing,
+    /// Places the observer on the surface of the selected body under the camera.
+    StandOnSelected,
+    /// Shows or hides all labels.
+    ToggleLabels,
+    ToggleStarLabels,
+    TogglePlanetLabels,
+    ToggleDwarfPlanetLabels,
+    ToggleMoonLabels,
+    /// Shows or hides the inspector window.
+    ToggleInspector,
+    /// Starts or ends measurement mode, in which picking chooses two bodies to measure.
+    ToggleMeasurement,
+    /// Plays or pauses the loaded tour. Manual movement pauses it as well.
+    ToggleTourPlayback,
+    SeekTourBackward,
+    SeekTourForward,
+    /// Starts recording of a new tour, or stops it and saves the tour.
+    ToggleTourRecording,
+    /// Adds the current state as a keyframe of the recorded tour.
+    RecordKeyframe,
+    /// Starts export of a frame sequence configured in the export file.
+    ExportFrames,
+    /// Saves the current view as a supersampled PNG.
+    Screenshot,
+    FlyTo0,
+    FlyTo1,
+    FlyTo2,
+    FlyTo3,
+    FlyTo4,
+    FlyTo5,
+    FlyTo6,
+    FlyTo7,
+    FlyTo8,
+    FlyTo9,
+}
+
+impl Action {
+    /// Held actions last while their binding is pressed, other actions trigger once on press.
+    pub fn is_held(self) -> bool {
+        use Action::*;
+        matches!(
+            self,
+            MoveForward
+                | MoveBackward
+                | MoveLeft
+                | MoveRight
+                | MoveUp
+                | MoveDown
+                | RollLeft
+                | RollRight
+                | Look
+        )
+    }
+
+    /// Index of the fly-to target for `FlyTo*` actions.
+    pub fn fly_to_index(self) -> Option<usize> {
+        use Action::*;
+        [
+            FlyTo0, FlyTo1, FlyTo2, FlyTo3, FlyTo4, FlyTo5, FlyTo6, FlyTo7, FlyTo8, FlyTo9,
+        ]
+        .iter()
+        .position(|action| *action == self)
+    }
+}
+
+/// Bundled controls file, whose bindings are used for actions, which are not listed in the loaded
+/// controls file.
+const DEFAULT_CONTROLS: &str = include_str!("../data/controls.toml");
+
+/// Single key, mouse button or modifier. Modifiers match keys on both sides of the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Wheel,
+    Ctrl,
+    Shift,
+    Alt,
+    Super,
+}
+
+impl Input {
+    /// Whether the pressed `input` satisfies this input of a binding.
+    fn matches(self, input: Input) -> bool {
+        use KeyCode::*;
+        match (self, input) {
+            (Input::Ctrl, Input::Key(ControlLeft | ControlRight))
+            | (Input::Shift, Input::Key(ShiftLeft | ShiftRight))
+            | (Input::Alt, Input::Key(AltLeft | AltRight))
+            | (Input::Super, Input::Key(SuperLeft | SuperRight)) => true,
+            _ => self == input,
+        }
+    }
+
+    /// Whether some pressed input satisfies both inputs.
+    fn overlaps(self, other: Input) -> bool {
+        self.matches(other) || other.matches(self)
+    }
+}
+
+fn parse_input(name: &str) -> Option<Input> {
+    use KeyCode::*;
+    let lower = name.to_ascii_lowercase();
+    // winit names of letter and digit keys are accepted as well
+    let short = lower
+        .strip_prefix("key")
+        .or_else(|| lower.strip_prefix("digit"))
+        .filter(|rest| rest.len() == 1)
+        .unwrap_or(&lower);
+    if let [c] = short.as_bytes() {
+        const LETTERS: [KeyCode; 26] = [
+            KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM, KeyN,
+            KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ,
+        ];
+        const DIGITS: [KeyCode; 10] = [
+            Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+        ];
+        return match c {
+            b'a'..=b'z' => Some(Input::Key(LETTERS[(c - b'a') as usize])),
+            b'0'..=b'9' => Some(Input::Key(DIGITS[(c - b'0') as usize])),
+            _ => None,
+        };
+    }
+    if let Some(number) = short
+        .strip_prefix('f')
+        .and_then(|n| n.parse::<usize>().ok())
+    {
+        const FUNCTION_KEYS: [KeyCode; 12] = [F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12];
+        return FUNCTION_KEYS
+            .get(number.checked_sub(1)?)
+            .map(|key| Input::Key(*key));
+    }
+    if let Some(number) = short
+        .strip_prefix("numpad")
+        .and_then(|n| n.parse::<usize>().ok())
+    {
+        const NUMPAD_DIGITS: [KeyCode; 10] = [
+            Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8,
+            Numpad9,
+        ];
+        return NUMPAD_DIGITS.get(number).map(|key| Input::Key(*key));
+    }
+    let key = match short {
+        "ctrl" | "control" => return Some(Input::Ctrl),
+        "shift" => return Some(Input::Shift),
+        "alt" => return Some(Input::Alt),
+        "super" | "meta" => return Some(Input::Super),
+        "wheel" => return Some(Input::Wheel),
+        "mouseleft" => return Some(Input::Mouse(MouseButton::Left)),
+        "mouseright" => return Some(Input::Mouse(MouseButton::Right)),
+        "mousemiddle" => return Some(Input::Mouse(MouseButton::Middle)),
+        "mouseback" => return Some(Input::Mouse(MouseButton::Back)),
+        "mouseforward" => return Some(Input::Mouse(MouseButton::Forward)),
+        "space" => Space,
+        "enter" => Enter,
+        "escape" | "esc" => Escape,
+        "tab" => Tab,
+        "backspace" => Backspace,
+        "delete" => Delete,
+        "insert" => Insert,
+        "home" => Home,
+        "end" => End,
+        "pageup" => PageUp,
+        "pagedown" => PageDown,
+        "up" | "arrowup" => ArrowUp,
+        "down" | "arrowdown" => ArrowDown,
+        "left" | "arrowleft" => ArrowLeft,
+        "right" | "arrowright" => ArrowRight,
+        "minus" => Minus,
+        "equal" => Equal,
+        "comma" => Comma,
+        "period" => Period,
+        "slash" => Slash,
+        "backslash" => Backslash,
+        "semicolon" => Semicolon,
+        "quote" => Quote,
+        "backquote" => Backquote,
+        "bracketleft" => BracketLeft,
+        "bracketright" => BracketRight,
+        "capslock" => CapsLock,
+        "shiftleft" => ShiftLeft,
+        "shiftright" => ShiftRight,
+        "controlleft" => ControlLeft,
+        "controlright" => ControlRight,
+        "altleft" => AltLeft,
+        "altright" => AltRight,
+        "superleft" => SuperLeft,
+        "superright" => SuperRight,
+        "numpadadd" => NumpadAdd,
+        "numpadsubtract" => NumpadSubtract,
+        "numpadmultiply" => NumpadMultiply,
+        "numpaddivide" => NumpadDivide,
+        "numpaddecimal" => NumpadDecimal,
+        "numpadenter" => NumpadEnter,
+        _ => return None,
+    };
+    Some(Input::Key(key))
+}
+
+/// Inputs pressed together, written as `Ctrl+Shift+W`. The last input triggers the chord, the
+/// preceding ones have to be held at that moment.
+#[derive(Debug, Clone, PartialEq)]
+struct Chord {
+    held: Vec<Input>,
+    trigger: Input,
+}
+
+impl Chord {
+    fn parse(text: &str) -> Result<Chord, String> {
+        let mut inputs = text
+            .split('+')
+            .map(|name| parse_input(name.trim()).ok_or_else(|| format!("unknown input `{name}`")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let trigger = inputs.pop().ok_or("empty binding")?;
+        if inputs.contains(&Input::Wheel) {
+            return Err("wheel can only be the last input".to_string());
+        }
+        Ok(Chord {
+            held: inputs,
+            trigger,
+        })
+    }
+
+    /// Whether both chords can be triggered by the same inputs.
+    fn overlaps(&self, other: &Chord) -> bool {
+        self.trigger.overlaps(other.trigger)
+            && self.held.len() == other.held.len()
+            && self
+                .held
+                .iter()
+                .all(|held| other.held.iter().any(|other| held.overlaps(*other)))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    action: Action,
+    chord: Chord,
+    text: String,
+}
+
+/// Maps pressed keys and mouse buttons to actions.
+///
+/// When more bindings match pressed inputs, the one with the most held inputs wins, so `Shift+Tab`
+/// takes precedence over `Tab`. Held actions end when their trigger is released.
+#[derive(Debug, Clone)]
+pub struct InputMap {
+    bindings: Vec<Binding>,
+    /// Problems found while loading bindings.
+    errors: Vec<String>,
+    pressed: Vec<Input>,
+    active: Vec<(Action, Input)>,
+}
+
+impl InputMap {
+    fn new(bindings: BTreeMap<Action, Vec<String>>, mut errors: Vec<String>) -> Self {
+        let mut parsed = Vec::new();
+        for (action, texts) in bindings {
+            for text in texts {
+                match Chord::parse(&text) {
+                    Ok(chord) if chord.trigger == Input::Wheel && action.is_held() => {
+                        errors.push(format!("{action:?} cannot be bound to wheel in `{text}`"));
+                    }
+                    Ok(chord) => parsed.push(Binding {
+                        action,
+                        chord,
+                        text,
+                    }),
+                    Err(e) => errors.push(format!("Invalid binding `{text}` of {action:?}: {e}")),
+                }
+            }
+        }
+        Self {
+            bindings: parsed,
+            errors,
+            pressed: Vec::new(),
+            active: Vec::new(),
+        }
+    }
+
+    /// Records pressed `input` and returns the triggered action.
+    pub fn press(&mut self, input: Input) -> Option<Action> {
+        if !self.pressed.contains(&input) {
+            self.pressed.push(input);
+        }
+        let action = self.matching(input)?;
+        if action.is_held() {
+            self.active.push((action, input));
+        }
+        Some(action)
+    }
+
+    /// Records released `input` and returns held actions, which ended.
+    pub fn release(&mut self, input: Input) -> Vec<Action> {
+        self.pressed.retain(|pressed| *pressed != input);
+        let (ended, active) = self
+            .active
+            .drain(..)
+            .partition(|(_, trigger)| *trigger == input);
+        self.active = active;
+        ended.into_iter().map(|(action, _)| action).collect()
+    }
+
+    /// Returns action of the scroll wheel with currently held inputs.
+    pub fn scroll(&self) -> Option<Action> {
+        self.matching(Input::Wheel)
+    }
+
+    /// Action of the most specific binding triggered by `input`.
+    fn matching(&self, input: Input) -> Option<Action> {
+        self.bindings
+            .iter()
+            .filter(|binding| {
+                binding.chord.trigger.matches(input)
+                    && binding.chord.held.iter().all(|held| {
+                        self.pressed
+                            .iter()
+                            .any(|pressed| *pressed != input && held.matches(*pressed))
+                    })
+            })
+            // first binding wins among equally specific ones
+            .rev()
+            .max_by_key(|binding| binding.chord.held.len())
+            .map(|binding| binding.action)
+    }
+
+    /// Describes invalid bindings, bindings shared by more actions and held inputs of chords,
+    /// which trigger other actions on their own.
+    pub fn conflict_report(&self) -> Vec<String> {
+        let mut report = self.errors.clone();
+        for (i, binding) in self.bindings.iter().enumerate() {
+            for other in &self.bindings[i + 1..] {
+                if binding.action != other.action && binding.chord.overlaps(&other.chord) {
+                    report.push(format!(
+                        "`{}` of {:?} conflicts with `{}` of {:?}, only {:?} is used",
+                        binding.text, binding.action, other.text, other.action, binding.action
+                    ));
+                }
+            }
+            for held in &binding.chord.held {
+                for other in &self.bindings {
+                    if other.chord.held.is_empty()
+                        && other.action.is_held()
+                        && other.chord.trigger.overlaps(*held)
+                    {
+                        report.push(format!(
+                            "Holding `{}` for `{}` of {:?} also triggers {:?}",
+                            other.text, binding.text, binding.action, other.action
+                        ));
+                    }
+                }
+            }
+        }
+        report
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ControlsRaw {
+    #[serde(default)]
+    bindings: BTreeMap<String, Vec<String>>,
+}
+
+/// Bindings of the `[bindings]` table of a controls file and names of unknown actions.
+fn parse_bindings(toml_str: &str) -> (BTreeMap<Action, Vec<String>>, Vec<String>) {
+    let raw: ControlsRaw = toml::from_str(toml_str).unwrap();
+    let mut bindings = BTreeMap::new();
+    let mut errors = Vec::new();
+    for (name, texts) in raw.bindings {
+        match Action::deserialize(IntoDeserializer::<Error>::into_deserializer(name.as_str())) {
+            Ok(action) => {
+                bindings.insert(action, texts);
+            }
+            Err(_) => errors.push(format!("Unknown action {name}")),
+        }
+    }
+    (bindings, errors)
+}
+
+/// Loads the `[bindings]` table of the controls file. Actions, which are not listed, keep their
+/// bindings of the bundled controls file, an empty list unbinds the action.
+pub fn load_input_map(path: &str) -> InputMap {
+    let (mut bindings, _) = parse_bindings(DEFAULT_CONTROLS);
+    let (loaded, errors) = parse_bindings(&fs::read_to_string(path).unwrap());
+    bindings.extend(loaded);
+    InputMap::new(bindings, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input_map(bindings: &[(Action, &str)]) -> InputMap {
+        let mut map = BTreeMap::<Action, Vec<String>>::new();
+        for (action, text) in bindings {
+            map.entry(*action).or_default().push(text.to_string());
+        }
+        InputMap::new(map, Vec::new())
+    }
+
+    #[test]
+    fn parses_chords() {
+        assert_eq!(
+            Chord::parse("Ctrl+Shift+S"),
+            Ok(Chord {
+                held: vec![Input::Ctrl, Input::Shift],
+                trigger: Input::Key(KeyCode::KeyS),
+            })
+        );
+        assert_eq!(
+            Chord::parse(" alt + KeyW "),
+            Ok(Chord {
+                held: vec![Input::Alt],
+                trigger: Input::Key(KeyCode::KeyW),
+            })
+        );
+        assert_eq!(
+            Chord::parse("Digit7").map(|chord| chord.trigger),
+            Ok(Input::Key(KeyCode::Digit7))
+        );
+        assert_eq!(
+            Chord::parse("Shift+MouseLeft").map(|chord| chord.trigger),
+            Ok(Input::Mouse(MouseButton::Left))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_inputs() {
+        for text in [
+            "", "Ctrl+", "Hyper+W", "WW", "F13", "F0", "Numpad10", "Wheel+W",
+        ] {
+            assert!(Chord::parse(text).is_err(), "{text} is invalid");
+        }
+        let map = input_map(&[(Action::MoveForward, "Wheel"), (Action::Pick, "Foo")]);
+        assert!(map.bindings.is_empty());
+        assert_eq!(map.conflict_report().len(), 2);
+    }
+
+    #[test]
+    fn reports_conflicting_bindings() {
+        let map = input_map(&[
+            (Action::Screenshot, "Ctrl+S"),
+            (Action::ExportFrames, "ControlRight+S"),
+            (Action::MoveBackward, "S"),
+        ]);
+        let report = map.conflict_report();
+        assert_eq!(report.len(), 1, "{report:?}");
+        assert!(report[0].contains("ExportFrames"), "{report:?}");
+
+        // holding the move down key of a chord moves the camera as well
+        let map = input_map(&[
+            (Action::MoveDown, "ControlLeft"),
+            (Action::Screenshot, "Ctrl+S"),
+        ]);
+        assert_eq!(map.conflict_report().len(), 1);
+    }
+
+    #[test]
+    fn most_specific_chord_wins() {
+        let mut map = input_map(&[
+            (Action::SelectNext, "Tab"),
+            (Action::SelectPrevious, "Shift+Tab"),
+        ]);
+        assert_eq!(
+            map.press(Input::Key(KeyCode::Tab)),
+            Some(Action::SelectNext)
+        );
+        map.release(Input::Key(KeyCode::Tab));
+        assert_eq!(map.press(Input::Key(KeyCode::ShiftRight)), None);
+        assert_eq!(
+            map.press(Input::Key(KeyCode::Tab)),
+            Some(Action::SelectPrevious)
+        );
+    }
+
+    #[test]
+    fn held_actions_end_on_release_of_trigger() {
+        let mut map = input_map(&[(Action::MoveForward, "W")]);
+        assert_eq!(
+            map.press(Input::Key(KeyCode::KeyW)),
+            Some(Action::MoveForward)
+        );
+        assert!(map.release(Input::Key(KeyCode::KeyS)).is_empty());
+        assert_eq!(
+            map.release(Input::Key(KeyCode::KeyW)),
+            vec![Action::MoveForward]
+        );
+    }
+
+    #[test]
+    fn default_controls_are_valid() {
+        let (bindings, errors) = parse_bindings(DEFAULT_CONTROLS);
+        assert!(errors.is_empty(), "{errors:?}");
+        let report = InputMap::new(bindings, errors).conflict_report();
+        assert!(report.is_empty(), "{report:?}");
+    }
+}
@@ -0,0 +1,636 @@
+use std::{
+    collections::VecDeque,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use cgmath::Deg;
+use egui::{
+    Align2, ClippedPrimitive, Color32, ComboBox, Context, DragValue, Grid, Id, Pos2, Sense, Slider,
+    Stroke, TexturesDelta, Ui, ViewportId, Window, collapsing_header::CollapsingState, vec2,
+};
+use egui_wgpu::{Renderer, ScreenDescriptor};
+use egui_winit::State;
+use wgpu::*;
+use winit::{event::WindowEvent, window::Window as WinitWindow};
+
+use crate::{
+    body_info::{BodyInfo, body_info},
+    camera::movement_control::MovementControl,
+    labels::LabelConfig,
+    measurement::Measurement,
+    render_target::{RenderTarget, RenderTargetConfig},
+    scene::Scene,
+    simulation_clock::{SPEED_RANGE, SimulationClock},
+    solar_object::{
+        render_solar_object::{RenderSolarObject, time_scaling},
+        scale_model::ScaleModel,
+        solar_object::BodyKind,
+    },
+    tour::TourPlayer,
+};
+
+/// Frames shown in the frame time graph.
+const FRAME_HISTORY: usize = 240;
+/// Frame time at the top of the frame time graph in milliseconds.
+const GRAPH_MAX_MS: f32 = 50.0;
+const GRAPH_HEIGHT: f32 = 60.0;
+/// Shortest orbital or rotation period, which can be set. Zero period would spin infinitely fast.
+const MIN_PERIOD_DAYS: f64 = 1e-3;
+
+/// User interface of the last update, drawn with the next frame.
+struct InspectorFrame {
+    paint_jobs: Vec<ClippedPrimitive>,
+    textures_delta: TexturesDelta,
+    pixels_per_point: f32,
+}
+
+/// Windows for inspecting and tweaking the scene while it runs, and information about the selected
+/// body. They are drawn over the scene and take input under them before [`MovementControl`].
+pub struct Inspector {
+    context: Context,
+    state: State,
+    renderer: Renderer,
+    clock: Arc<Mutex<SimulationClock>>,
+    label_config: Arc<Mutex<LabelConfig>>,
+    /// Durations of the last frames in milliseconds, the newest last.
+    frame_times: VecDeque<f32>,
+    last_frame: Option<Instant>,
+    /// Latitude and longitude in degrees to place the ground observer at.
+    ground_coordinates: (f64, f64),
+    /// Some window was shown in the last update.
+    active: bool,
+    frame: Option<InspectorFrame>,
+}
+
+impl Inspector {
+    pub fn new(
+        window: &WinitWindow,
+        device: &Device,
+        render_target: &RenderTargetConfig,
+        clock: Arc<Mutex<SimulationClock>>,
+        label_config: Arc<Mutex<LabelConfig>>,
+    ) -> Self {
+        let context = Context::default();
+        let state = State::new(
+            context.clone(),
+            ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            window.theme(),
+            Some(device.limits().max_texture_dimension_2d as usize),
+        );
+        let renderer = Renderer::new(
+            device,
+            render_target.target_texture_format(),
+            None,
+            1,
+            false,
+        );
+        Self {
+            context,
+            state,
+            renderer,
+            clock,
+            label_config,
+            frame_times: VecDeque::with_capacity(FRAME_HISTORY),
+            last_frame: None,
+            ground_coordinates: (0.0, 0.0),
+            active: false,
+            frame: None,
+        }
+    }
+
+    /// Passes the event to the user interface. Returns `true`, if the user interface used it and
+    /// it should not be handled by anything else. Hidden user interface uses nothing.
+    pub fn process_window_event(&mut self, window: &WinitWindow, event: &WindowEvent) -> bool {
+        self.active && self.state.on_window_event(window, event).consumed
+    }
+
+    /// Builds the user interface for the frame at `now` and applies changes made in it.
+    pub fn update(
+        &mut self,
+        now: Instant,
+        window: &WinitWindow,
+        scene: &mut Scene,
+        movement_control: &mut MovementControl,
+    ) {
+        if let Some(last_frame) = self.last_frame {
+            if self.frame_times.len() == FRAME_HISTORY {
+                self.frame_times.pop_front();
+            }
+            let frame_time = now.saturating_duration_since(last_frame);
+            self.frame_times
+                .push_back(frame_time.as_secs_f32() * 1000.0);
+        }
+        self.last_frame = Some(now);
+
+        let info = movement_control.selected().and_then(|name| {
+            body_info(
+                scene.solar_object(),
+                name,
+                scene.camera_position(),
+                scene.settings().scale_model,
+            )
+        });
+        let measurement = movement_control.measured().map(|measured| match measured {
+            [observer, target] => scene.measure(observer, target).ok_or(measured.len()),
+            _ => Err(measured.len()),
+        });
+        let inspector_visible = movement_control.inspector_visible();
+        self.active = inspector_visible || info.is_some() || measurement.is_some();
+        if !self.active {
+            self.frame = None;
+            return;
+        }
+        let input = self.state.take_egui_input(window);
+        let context = self.context.clone();
+        let output = context.run(input, |context| {
+            if inspector_visible {
+                Window::new("Inspector")
+                    .default_width(300.0)
+                    .show(context, |ui| self.show(ui, now, scene, movement_control));
+            }
+            if let Some(info) = &info {
+                Window::new(&info.name)
+                    .id(Id::new("body info"))
+                    .anchor(Align2::RIGHT_TOP, vec2(-10.0, 10.0))
+                    .resizable(false)
+                    .show(context, |ui| show_body_info(ui, info));
+            }
+            if let Some(measurement) = &measurement {
+                Window::new("Measurement")
+                    .anchor(Align2::LEFT_BOTTOM, vec2(10.0, -10.0))
+                    .resizable(false)
+                    .show(context, |ui| show_measurement(ui, measurement));
+            }
+        });
+        self.state
+            .handle_platform_output(window, output.platform_output);
+        self.frame = Some(InspectorFrame {
+            paint_jobs: context.tessellate(output.shapes, output.pixels_per_point),
+            textures_delta: output.textures_delta,
+            pixels_per_point: output.pixels_per_point,
+        });
+    }
+
+    fn show(
+        &mut self,
+        ui: &mut Ui,
+        now: Instant,
+        scene: &mut Scene,
+        movement_control: &mut MovementControl,
+    ) {
+        self.show_frame_times(ui);
+        ui.collapsing("Time", |ui| self.show_time(ui));
+        ui.collapsing("Tour", |ui| show_tour(ui, now, movement_control.tour_mut()));
+        ui.collapsing("Rendering", |ui| self.show_rendering(ui, scene));
+        ui.collapsing("Bodies", |ui| {
+            if let Some(name) = body_tree(ui, scene.solar_object(), movement_control.selected()) {
+                movement_control.select(Some(name));
+            }
+            ui.horizontal(|ui| {
+                let selected = movement_control.selected().is_some();
+                if ui
+                    .add_enabled(selected, egui::Button::new("Fly to"))
+                    .clicked()
+                {
+                    movement_control.fly_to_selected();
+                }
+                if ui
+                    .add_enabled(selected, egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    movement_control.select(None);
+                }
+            });
+        });
+        ui.collapsing("Ground observer", |ui| {
+            self.show_ground(ui, movement_control)
+        });
+        let selected = movement_control
+            .selected()
+            .and_then(|name| scene.solar_object_mut().find_mut(name));
+        if let Some(body) = selected {
+            ui.collapsing(format!("Orbit of {}", body.name), |ui| show_orbit(ui, body));
+        }
+    }
+
+    fn show_ground(&mut self, ui: &mut Ui, movement_control: &mut MovementControl) {
+        match movement_control.ground() {
+            Some((target, Some((latitude, longitude)))) => ui.label(format!(
+                "Standing on {target} at {}, {}",
+                format_angle(Deg::from(latitude).0, 'N', 'S'),
+                format_angle(Deg::from(longitude).0, 'E', 'W')
+            )),
+            Some((target, None)) => ui.label(format!("Standing on {target}")),
+            None => ui.label("Not on the ground"),
+        };
+        let (latitude, longitude) = &mut self.ground_coordinates;
+        Grid::new("ground coordinates").show(ui, |ui| {
+            ui.label("Latitude");
+            ui.add(DragValue::new(latitude).range(-90.0..=90.0).suffix("°"));
+            ui.end_row();
+            ui.label("Longitude");
+            ui.add(DragValue::new(longitude).range(-180.0..=180.0).suffix("°"));
+            ui.end_row();
+        });
+        ui.horizontal(|ui| {
+            let selected = movement_control.selected().is_some();
+            if ui
+                .add_enabled(selected, egui::Button::new("Stand on"))
+                .clicked()
+            {
+                movement_control
+                    .stand_on_selected(Some((Deg(*latitude).into(), Deg(*longitude).into())));
+            }
+            if ui
+                .add_enabled(selected, egui::Button::new("Stand under camera"))
+                .clicked()
+            {
+                movement_control.stand_on_selected(None);
+            }
+        });
+    }
+
+    fn show_frame_times(&self, ui: &mut Ui) {
+        let average = self.frame_times.iter().sum::<f32>() / self.frame_times.len().max(1) as f32;
+        ui.label(format!(
+            "{:.0} FPS, {average:.1} ms per frame",
+            1000.0 / average.max(f32::EPSILON)
+        ));
+        let (response, painter) =
+            ui.allocate_painter(vec2(ui.available_width(), GRAPH_HEIGHT), Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 0.0, Color32::from_black_alpha(128));
+        let y = |ms: f32| rect.bottom() - (ms / GRAPH_MAX_MS).min(1.0) * rect.height();
+        // frame time of 60 FPS
+        painter.hline(
+            rect.x_range(),
+            y(1000.0 / 60.0),
+            Stroke::new(1.0, Color32::DARK_GRAY),
+        );
+        let step = rect.width() / (FRAME_HISTORY - 1) as f32;
+        let start = FRAME_HISTORY - self.frame_times.len();
+        let points = self
+            .frame_times
+            .iter()
+            .enumerate()
+            .map(|(i, ms)| Pos2::new(rect.left() + (start + i) as f32 * step, y(*ms)))
+            .collect();
+        painter.line(points, Stroke::new(1.0, Color32::LIGHT_GREEN));
+    }
+
+    fn show_time(&self, ui: &mut Ui) {
+        let mut clock = self.clock.lock().unwrap();
+        ui.label(format!(
+            "Simulated day {:.1}",
+            time_scaling(clock.time().as_secs_f64())
+        ));
+        let mut paused = clock.is_paused();
+        if ui.checkbox(&mut paused, "Paused").changed() {
+            clock.toggle_pause();
+        }
+        let mut speed = clock.speed();
+        let slider = Slider::new(&mut speed, SPEED_RANGE.0..=SPEED_RANGE.1)
+            .logarithmic(true)
+            .suffix("x")
+            .text("Speed");
+        if ui.add(slider).changed() {
+            clock.set_speed(speed);
+        }
+    }
+
+    fn show_rendering(&self, ui: &mut Ui, scene: &mut Scene) {
+        let settings = scene.settings_mut();
+        ComboBox::from_label("Scale model")
+            .selected_text(settings.scale_model.name())
+            .show_ui(ui, |ui| {
+                for scale_model in ScaleModel::ALL {
+                    ui.selectable_value(&mut settings.scale_model, scale_model, scale_model.name());
+                }
+            });
+        ui.checkbox(&mut settings.clouds, "Clouds");
+        ui.checkbox(&mut settings.rings, "Rings");
+        ui.checkbox(&mut settings.atmospheres, "Atmospheres");
+
+        let mut label_config = self.label_config.lock().unwrap();
+        ui.checkbox(&mut label_config.visible, "Labels");
+        ui.add_enabled_ui(label_config.visible, |ui| {
+            ui.indent("label kinds", |ui| {
+                for (kind, name) in [
+                    (BodyKind::Star, "Stars"),
+                    (BodyKind::Planet, "Planets"),
+                    (BodyKind::DwarfPlanet, "Dwarf planets"),
+                    (BodyKind::Moon, "Moons"),
+                ] {
+                    let mut visible = label_config.style(kind).visible;
+                    if ui.checkbox(&mut visible, name).changed() {
+                        label_config.toggle(Some(kind));
+                    }
+                }
+            });
+        });
+    }
+
+    /// Draws the user interface of the last update over the frame.
+    pub fn record_draw_commands(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        render_target: &RenderTarget,
+    ) {
+        let Some(frame) = self.frame.take() else {
+            return;
+        };
+        for (id, image_delta) in &frame.textures_delta.set {
+            self.renderer
+                .update_texture(device, queue, *id, image_delta);
+        }
+        let size = render_target.size();
+        let screen = ScreenDescriptor {
+            size_in_pixels: [size.width, size.height],
+            pixels_per_point: frame.pixels_per_point,
+        };
+        // there are no paint callbacks, which would need their own command buffers
+        self.renderer
+            .update_buffers(device, queue, encoder, &frame.paint_jobs, &screen);
+
+        let render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Inspector Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: render_target.color_view(),
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+        self.renderer.render(
+            &mut render_pass.forget_lifetime(),
+            &frame.paint_jobs,
+            &screen,
+        );
+
+        for id in &frame.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+    }
+}
+
+/// Shows `body` and its descendants as a tree. Returns name of the clicked body.
+fn body_tree(ui: &mut Ui, body: &RenderSolarObject, selected: Option<&str>) -> Option<String> {
+    let mut clicked = None;
+    let mut label = |ui: &mut Ui| {
+        if ui
+            .selectable_label(selected == Some(body.name.as_str()), &body.name)
+            .clicked()
+        {
+            clicked = Some(body.name.clone());
+        }
+    };
+    if body.children.is_empty() {
+        label(ui);
+        return clicked;
+    }
+    let mut child_clicked = None;
+    let id = ui.make_persistent_id(&body.name);
+    // moons are collapsed, so the planets fit in
+    CollapsingState::load_with_default_open(ui.ctx(), id, body.kind == BodyKind::Star)
+        .show_header(ui, label)
+        .body(|ui| {
+            for child in &body.children {
+                child_clicked = body_tree(ui, child, selected).or(child_clicked.take());
+            }
+        });
+    clicked.or(child_clicked)
+}
+
+/// Editable catalog values of the body, which drive its motion.
+fn show_orbit(ui: &mut Ui, body: &mut RenderSolarObject) {
+    Grid::new("orbit").num_columns(2).show(ui, |ui| {
+        ui.label("Radius");
+        ui.add(
+            proportional_drag(&mut body.radius_km)
+                .range(1.0..=f64::MAX)
+                .suffix(" km"),
+        );
+        ui.end_row();
+
+        ui.label("Distance from parent");
+        ui.add(
+            proportional_drag(&mut body.distance_from_parent_km)
+                .range(0.0..=f64::MAX)
+                .suffix(" km"),
+        );
+        ui.end_row();
+
+        if let Some(orbital_period_days) = &mut body.orbital_period_days {
+            ui.label("Orbital period");
+            period_drag(ui, orbital_period_days);
+            ui.end_row();
+        }
+
+        ui.label("Rotation period");
+        period_drag(ui, &mut body.rotation_period_days);
+        ui.end_row();
+
+        ui.label("Tilt");
+        let mut tilt = body.tilt.to_degrees();
+        if ui
+            .add(
+                DragValue::new(&mut tilt)
+                    .speed(0.1)
+                    .range(-180.0..=180.0)
+                    .suffix("°"),
+            )
+            .changed()
+        {
+            body.tilt = tilt.to_radians();
+        }
+        ui.end_row();
+    });
+}
+
+fn show_body_info(ui: &mut Ui, info: &BodyInfo) {
+    Grid::new("body info").num_columns(2).show(ui, |ui| {
+        let mut row = |name: &str, value: String| {
+            ui.label(name);
+            ui.label(value);
+            ui.end_row();
+        };
+        let kind = match info.kind {
+            BodyKind::Star => "Star",
+            BodyKind::Planet => "Planet",
+            BodyKind::DwarfPlanet => "Dwarf planet",
+            BodyKind::Moon => "Moon",
+        };
+        row("Kind", kind.to_string());
+        if let Some(parent) = &info.parent {
+            row("Parent", parent.clone());
+        }
+        row("Radius", format!("{:.0} km", info.radius_km));
+        if let Some(orbital_period_days) = info.orbital_period_days {
+            row("Orbital period", format!("{orbital_period_days:.2} d"));
+        }
+        let retrograde = if info.rotation_period_days < 0.0 {
+            ", retrograde"
+        } else {
+            ""
+        };
+        row(
+            "Rotation period",
+            format!("{:.3} d{retrograde}", info.rotation_period_days.abs()),
+        );
+        row("Tilt", format!("{:.2}°", info.tilt.0));
+
+        if let Some(distance_to_parent_km) = info.distance_to_parent_km {
+            row(
+                "Distance to parent",
+                format!("{distance_to_parent_km:.0} km"),
+            );
+        }
+        let distance_to_camera = match info.distance_to_camera_km {
+            Some(km) => format!("{km:.0} km"),
+            None => format!("{:.3} units", info.distance_to_camera),
+        };
+        row("Distance to camera", distance_to_camera);
+        if let (Some(phase), Some(true_anomaly)) = (info.orbital_phase, info.true_anomaly) {
+            row("Orbital phase", format!("{:.1} %", phase * 100.0));
+            row("True anomaly", format!("{:.1}°", true_anomaly.0));
+        }
+        if let Some(hours) = info.local_solar_time {
+            let minutes = (hours * 60.0) as u32;
+            row(
+                "Local solar time",
+                format!("{:02}:{:02}", minutes / 60, minutes % 60),
+            );
+        }
+        if let Some(seconds) = info.light_time_from_sun {
+            let seconds = seconds.round() as u64;
+            row(
+                "Light time from the Sun",
+                format!("{} min {} s", seconds / 60, seconds % 60),
+            );
+        }
+    });
+}
+
+/// Shows the measurement or what to pick next, when there are not enough bodies picked.
+fn show_measurement(ui: &mut Ui, measurement: &Result<Measurement, usize>) {
+    let measurement = match measurement {
+        Ok(measurement) => measurement,
+        Err(0) => {
+            ui.label("Pick the observer");
+            return;
+        }
+        Err(_) => {
+            ui.label("Pick the target");
+            return;
+        }
+    };
+    ui.label(format!(
+        "{} seen from {}",
+        measurement.target, measurement.observer
+    ));
+    Grid::new("measurement").num_columns(2).show(ui, |ui| {
+        ui.label("Separation");
+        ui.vertical(|ui| {
+            ui.label(format!("{:.0} km", measurement.separation_km));
+            ui.label(format!("{:.4} AU", measurement.separation_au()));
+            ui.label(format!(
+                "{:.2} light minutes",
+                measurement.separation_light_minutes()
+            ));
+        });
+        ui.end_row();
+        if let Some(phase_angle) = measurement.phase_angle {
+            ui.label("Phase angle");
+            ui.label(format!("{:.2}°", phase_angle.0));
+            ui.end_row();
+        }
+        ui.label("Angular size from the surface");
+        ui.label(format!("{:.4}°", measurement.angular_size.0));
+        ui.end_row();
+    });
+}
+
+/// Dragging by a pixel changes the value by a percent.
+fn proportional_drag(value: &mut f64) -> DragValue<'_> {
+    let speed = (value.abs() * 0.01).max(0.01);
+    DragValue::new(value).speed(speed)
+}
+
+/// Edits a period in days, which divides time, by its length and direction. Negative period is
+/// retrograde.
+fn period_drag(ui: &mut Ui, period_days: &mut f64) {
+    ui.horizontal(|ui| {
+        let mut length = period_days.abs().max(MIN_PERIOD_DAYS);
+        let mut retrograde = period_days.is_sign_negative();
+        let length_changed = ui
+            .add(
+                proportional_drag(&mut length)
+                    .range(MIN_PERIOD_DAYS..=f64::MAX)
+                    .suffix(" d"),
+            )
+            .changed();
+        if ui.checkbox(&mut retrograde, "Retrograde").changed() || length_changed {
+            *period_days = if retrograde { -length } else { length };
+        }
+    });
+}
+
+impl Debug for Inspector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Inspector")
+            .field("frame_times", &self.frame_times)
+            .finish()
+    }
+}
+
+fn show_tour(ui: &mut Ui, now: Instant, tour: &mut TourPlayer) {
+    let duration = tour.tour().duration().as_secs_f64();
+    ui.label(format!("{} keyframes", tour.tour().keyframes.len()));
+    let mut time = tour.time().as_secs_f64();
+    let slider = Slider::new(&mut time, 0.0..=duration)
+        .suffix(" s")
+        .text("Time");
+    if ui.add_enabled(!tour.is_recording(), slider).changed() {
+        tour.seek(now, Duration::from_secs_f64(time));
+    }
+    ui.horizontal(|ui| {
+        let label = if tour.is_playing() { "Pause" } else { "Play" };
+        if ui
+            .add_enabled(!tour.is_recording(), egui::Button::new(label))
+            .clicked()
+        {
+            tour.toggle_playback(now);
+        }
+        let label = if tour.is_recording() {
+            "Stop recording"
+        } else {
+            "Record"
+        };
+        if ui.button(label).clicked() {
+            tour.toggle_recording(now);
+        }
+        if ui
+            .add_enabled(tour.is_recording(), egui::Button::new("Add keyframe"))
+            .clicked()
+        {
+            tour.record_keyframe(now);
+        }
+    });
+}
+
+/// Formats signed angle in degrees with hemisphere letters, e.g. 50.1° N.
+fn format_angle(degrees: f64, positive: char, negative: char) -> String {
+    let hemisphere = if degrees < 0.0 { negative } else { positive };
+    format!("{:.1}° {hemisphere}", degrees.abs())
+}
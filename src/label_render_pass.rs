@@ -0,0 +1,318 @@
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable, cast_slice};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    camera::projection::{NO_TILE, Tile},
+    glyph_atlas::GlyphAtlas,
+    labels::ScreenLabel,
+    render_target::{RenderTarget, RenderTargetConfig, ScenePipeline},
+};
+
+/// Glyphs drawn in a single frame including their shadows. Glyphs over the limit are dropped.
+const MAX_GLYPHS: usize = 4096;
+/// Offset of the dark shadow, which keeps labels readable over bright bodies.
+const SHADOW_OFFSET: f32 = 1.0;
+const SHADOW_ALPHA: f32 = 0.8;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct LabelUniform {
+    viewport: [f32; 4],
+    tile: [f32; 4],
+}
+
+impl LabelUniform {
+    fn new(size: PhysicalSize<u32>, tile: Option<Tile>) -> Self {
+        LabelUniform {
+            viewport: [size.width as f32, size.height as f32, 0.0, 0.0],
+            tile: tile.map_or(NO_TILE, |tile| tile.clip_scale_offset()),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct GlyphInstance {
+    /// Top left corner and size in pixels.
+    rect: [f32; 4],
+    uv: [f32; 4],
+    color: [f32; 4],
+}
+
+impl GlyphInstance {
+    const ATTRIBUTES: [VertexAttribute; 3] = [
+        VertexAttribute {
+            offset: 0,
+            shader_location: 0,
+            format: VertexFormat::Float32x4,
+        },
+        VertexAttribute {
+            offset: size_of::<[f32; 4]>() as BufferAddress,
+            shader_location: 1,
+            format: VertexFormat::Float32x4,
+        },
+        VertexAttribute {
+            offset: size_of::<[f32; 8]>() as BufferAddress,
+            shader_location: 2,
+            format: VertexFormat::Float32x4,
+        },
+    ];
+
+    fn desc() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: size_of::<GlyphInstance>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Draws text of labels over the finished frame. Runs after all other passes and does not use
+/// depth buffer, hidden labels are left out before.
+#[derive(Debug)]
+pub struct LabelRenderPass {
+    atlas: GlyphAtlas,
+    render_pipeline: ScenePipeline,
+    label_buffer: Buffer,
+    bind_group: BindGroup,
+    instance_buffer: Buffer,
+    glyph_count: u32,
+}
+
+impl LabelRenderPass {
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        render_target: &RenderTargetConfig,
+        atlas: GlyphAtlas,
+    ) -> LabelRenderPass {
+        let image = atlas.image();
+        let size = Extent3d {
+            width: image.width(),
+            height: image.height(),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("glyph atlas"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            image.as_raw(),
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(size.width),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("label layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let label_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("label buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            contents: cast_slice(&[LabelUniform::new(render_target.size(), None)]),
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("label bind group"),
+            layout: &layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: label_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Label Shader"),
+            source: ShaderSource::Wgsl(include_str!("label_shader.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Label Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = ScenePipeline::new(render_target, |sample_count| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("Label Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[GlyphInstance::desc()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(ColorTargetState {
+                        format: render_target.target_texture_format(),
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleStrip,
+                    strip_index_format: None,
+                    front_face: FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: MultisampleState {
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        let instance_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("glyph buffer"),
+            size: (MAX_GLYPHS * size_of::<GlyphInstance>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        LabelRenderPass {
+            atlas,
+            render_pipeline,
+            label_buffer,
+            bind_group,
+            instance_buffer,
+            glyph_count: 0,
+        }
+    }
+
+    /// Width and height of the text in pixels.
+    pub fn measure(&self, text: &str) -> [f32; 2] {
+        [self.atlas.layout(text).1, self.atlas.line_height()]
+    }
+
+    /// Updates the viewport of labels and the `tile` of it drawn next.
+    pub fn resize(&self, queue: &Queue, new_size: PhysicalSize<u32>, tile: Option<Tile>) {
+        queue.write_buffer(
+            &self.label_buffer,
+            0,
+            cast_slice(&[LabelUniform::new(new_size, tile)]),
+        );
+    }
+
+    /// Lays out glyphs of the labels for the next frame.
+    pub fn update(&mut self, queue: &Queue, labels: &[ScreenLabel]) {
+        let mut instances = Vec::new();
+        for label in labels {
+            let (quads, _) = self.atlas.layout(&label.text);
+            let [x, y] = label.position;
+            let shadow = [0.0, 0.0, 0.0, label.color[3] * SHADOW_ALPHA];
+            // shadows go first, so they do not cover neighbouring glyphs
+            for (offset, color) in [(SHADOW_OFFSET, shadow), (0.0, label.color)] {
+                instances.extend(quads.iter().map(|quad| GlyphInstance {
+                    rect: [
+                        x + quad.position[0] + offset,
+                        y + quad.position[1] + offset,
+                        quad.size[0],
+                        quad.size[1],
+                    ],
+                    uv: quad.uv,
+                    color,
+                }));
+            }
+        }
+        instances.truncate(MAX_GLYPHS);
+        queue.write_buffer(&self.instance_buffer, 0, cast_slice(&instances));
+        self.glyph_count = instances.len() as u32;
+    }
+
+    pub fn record_draw_commands(&self, encoder: &mut CommandEncoder, render_target: &RenderTarget) {
+        if self.glyph_count == 0 {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Label Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: render_target.scene_view(),
+                resolve_target: render_target.resolve_target(),
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(self.render_pipeline.get(render_target));
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        render_pass.draw(0..4, 0..self.glyph_count);
+    }
+}
@@ -0,0 +1,51 @@
+// Text of body labels. Glyphs are quads in pixel coordinates sampling coverage from the glyph
+// atlas. It is drawn last and without depth, over everything else.
+
+struct Label {
+    // viewport width and height in pixels
+    viewport: vec4<f32>,
+    // scale and offset of clip space of the drawn tile relative to the whole view
+    tile: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> label: Label;
+@group(0) @binding(1)
+var atlas_tex: texture_2d<f32>;
+@group(0) @binding(2)
+var atlas_sampler: sampler;
+
+struct GlyphInput {
+    // top left corner and size in pixels
+    @location(0) rect: vec4<f32>,
+    // top left and bottom right texture coordinates
+    @location(1) uv: vec4<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct GlyphOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+// Each glyph is a quad drawn as triangle strip.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, glyph: GlyphInput) -> GlyphOutput {
+    let corner = vec2<f32>(f32(index & 1u), f32((index >> 1u) & 1u));
+    let pixel = glyph.rect.xy + corner * glyph.rect.zw;
+    // pixels grow downwards, clip space upwards
+    let ndc = vec2<f32>(pixel.x, -pixel.y) / label.viewport.xy * 2.0 + vec2<f32>(-1.0, 1.0);
+
+    var out: GlyphOutput;
+    out.clip_position = vec4<f32>(ndc * label.tile.xy + label.tile.zw, 0.0, 1.0);
+    out.uv = mix(glyph.uv.xy, glyph.uv.zw, corner);
+    out.color = glyph.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: GlyphOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(atlas_tex, atlas_sampler, in.uv).r;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
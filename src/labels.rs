@@ -0,0 +1,222 @@
+use std::{cmp::Ordering, fs};
+
+use cgmath::{InnerSpace, Matrix4, Point3, Vector3};
+use serde::Deserialize;
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    picking::Ray,
+    solar_object::{render_solar_object::RenderSolarObject, solar_object::BodyKind},
+};
+
+/// Gap between the edge of the body and its label in pixels.
+const LABEL_GAP: f32 = 4.0;
+/// Labels closer than this many pixels are considered overlapping.
+const LABEL_MARGIN: f32 = 2.0;
+
+/// Appearance of labels of one kind of bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct LabelStyle {
+    pub visible: bool,
+    /// Distance from the camera in scene units, where the label starts to fade out and where it
+    /// disappears. Labels without fade distance are always shown.
+    #[serde(default)]
+    pub fade_distance: Option<[f64; 2]>,
+    pub color: [f32; 3],
+}
+
+impl LabelStyle {
+    /// Opacity of the label at `distance` from the camera.
+    fn opacity(&self, distance: f64) -> f32 {
+        match self.fade_distance {
+            Some([start, end]) if end > start => {
+                (1.0 - ((distance - start) / (end - start)).clamp(0.0, 1.0)) as f32
+            }
+            Some([_, end]) if distance > end => 0.0,
+            _ => 1.0,
+        }
+    }
+}
+
+/// Name labels of bodies.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LabelConfig {
+    pub visible: bool,
+    /// Path to the font file.
+    pub font: String,
+    /// Font size in pixels.
+    pub font_size: f32,
+    pub star: LabelStyle,
+    pub planet: LabelStyle,
+    pub dwarf_planet: LabelStyle,
+    pub moon: LabelStyle,
+}
+
+impl LabelConfig {
+    pub fn style(&self, kind: BodyKind) -> &LabelStyle {
+        match kind {
+            BodyKind::Star => &self.star,
+            BodyKind::Planet => &self.planet,
+            BodyKind::DwarfPlanet => &self.dwarf_planet,
+            BodyKind::Moon => &self.moon,
+        }
+    }
+
+    /// Shows or hides labels of `kind` of bodies, or all labels for `None`.
+    pub fn toggle(&mut self, kind: Option<BodyKind>) {
+        let visible = match kind {
+            Some(BodyKind::Star) => &mut self.star.visible,
+            Some(BodyKind::Planet) => &mut self.planet.visible,
+            Some(BodyKind::DwarfPlanet) => &mut self.dwarf_planet.visible,
+            Some(BodyKind::Moon) => &mut self.moon.visible,
+            None => &mut self.visible,
+        };
+        *visible = !*visible;
+    }
+}
+
+pub fn load_label_config(path: &str) -> LabelConfig {
+    let toml_str = fs::read_to_string(path).unwrap();
+    toml::from_str(&toml_str).unwrap()
+}
+
+/// Label placed on the screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenLabel {
+    pub text: String,
+    /// Top left corner in pixels.
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+/// Body, which passed visibility tests, before decluttering.
+struct Candidate<'a> {
+    body: &'a RenderSolarObject,
+    distance: f64,
+    /// Top left and bottom right corners in pixels.
+    rect: [f32; 4],
+    opacity: f32,
+}
+
+impl Candidate<'_> {
+    fn overlaps(&self, other: &Candidate) -> bool {
+        self.rect[0] < other.rect[2] + LABEL_MARGIN
+            && other.rect[0] < self.rect[2] + LABEL_MARGIN
+            && self.rect[1] < other.rect[3] + LABEL_MARGIN
+            && other.rect[1] < self.rect[3] + LABEL_MARGIN
+    }
+
+    /// Bigger kinds of bodies and closer bodies keep their labels when labels overlap.
+    fn priority(&self, other: &Candidate) -> Ordering {
+        let rank = |kind: BodyKind| match kind {
+            BodyKind::Star => 0,
+            BodyKind::Planet => 1,
+            BodyKind::DwarfPlanet => 2,
+            BodyKind::Moon => 3,
+        };
+        rank(self.body.kind)
+            .cmp(&rank(other.body.kind))
+            .then(self.distance.total_cmp(&other.distance))
+    }
+}
+
+/// Places labels of visible bodies next to them. Labels of bodies hidden behind other bodies are
+/// left out, as well as labels overlapping labels of more important bodies. `measure` returns
+/// width and height of text in pixels.
+pub fn layout_labels(
+    config: &LabelConfig,
+    solar_object: &RenderSolarObject,
+    camera_position: Point3<f64>,
+    view_proj: Matrix4<f32>,
+    viewport: PhysicalSize<u32>,
+    measure: impl Fn(&str) -> [f32; 2],
+) -> Vec<ScreenLabel> {
+    if !config.visible {
+        return Vec::new();
+    }
+    let bodies = solar_object.bodies();
+    let viewport = [viewport.width as f32, viewport.height as f32];
+    // projects point relative to the camera to pixels
+    let project = |offset: Vector3<f64>| {
+        let clip = view_proj * offset.cast::<f32>()?.extend(1.0);
+        let depth = clip.z / clip.w;
+        if clip.w <= 0.0 || !(0.0..=1.0).contains(&depth) {
+            return None;
+        }
+        Some([
+            (clip.x / clip.w * 0.5 + 0.5) * viewport[0],
+            (0.5 - clip.y / clip.w * 0.5) * viewport[1],
+        ])
+    };
+
+    let mut candidates = bodies
+        .iter()
+        .filter_map(|body| {
+            let style = config.style(body.kind);
+            let offset = body.world_position - camera_position;
+            let distance = offset.magnitude();
+            let radius = body.rendered_radius();
+            let opacity = style.opacity(distance);
+            if !style.visible || opacity <= 0.0 || distance <= radius {
+                return None;
+            }
+            let centre = project(offset)?;
+            // projected radius, measured perpendicular to the view ray
+            let side = offset.cross(Vector3::unit_y());
+            let side = if side.magnitude2() > 0.0 {
+                side.normalize()
+            } else {
+                Vector3::unit_x()
+            };
+            let edge = project(offset + side * radius)?;
+            let radius_px = (edge[0] - centre[0]).hypot(edge[1] - centre[1]);
+
+            let ray = Ray {
+                origin: camera_position,
+                direction: offset / distance,
+            };
+            let occluded = bodies.iter().any(|other| {
+                !std::ptr::eq(*other, *body)
+                    && ray
+                        .intersect_sphere(other.world_position, other.rendered_radius())
+                        .is_some_and(|t| t < distance - radius)
+            });
+            if occluded {
+                return None;
+            }
+
+            let [width, height] = measure(&body.name);
+            let left = (centre[0] + radius_px + LABEL_GAP).round();
+            let top = (centre[1] - height / 2.0).round();
+            Some(Candidate {
+                body,
+                distance,
+                rect: [left, top, left + width, top + height],
+                opacity,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    candidates.sort_by(|a, b| a.priority(b));
+    let mut placed: Vec<Candidate> = Vec::new();
+    for candidate in candidates {
+        let on_screen = candidate.rect[2] > 0.0
+            && candidate.rect[0] < viewport[0]
+            && candidate.rect[3] > 0.0
+            && candidate.rect[1] < viewport[1];
+        if on_screen && !placed.iter().any(|other| other.overlaps(&candidate)) {
+            placed.push(candidate);
+        }
+    }
+    placed
+        .into_iter()
+        .map(|label| {
+            let [r, g, b] = config.style(label.body.kind).color;
+            ScreenLabel {
+                text: label.body.name.clone(),
+                position: [label.rect[0], label.rect[1]],
+                color: [r, g, b, label.opacity],
+            }
+        })
+        .collect()
+}
@@ -2,14 +2,31 @@ use app::App;
 use winit::event_loop::{ControlFlow, EventLoop};
 
 mod app;
+mod atmosphere_render_pass;
+mod body_info;
 mod camera;
+mod frame_export;
+mod fxaa_render_pass;
+mod glyph_atlas;
+mod input_map;
+mod inspector;
+mod label_render_pass;
+mod labels;
 mod matrix;
+mod measurement;
 mod model;
 mod model_render_pass;
+mod picking;
 mod render_target;
+mod ring_render_pass;
 mod scene;
+mod screenshot;
+mod simulation_clock;
+mod sky;
+mod sky_render_pass;
 mod solar_object;
 mod texture;
+mod tour;
 
 pub async fn run() {
     let event_loop = EventLoop::new().unwrap();
@@ -19,5 +36,10 @@ pub async fn run() {
 }
 
 fn main() {
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("convert-textures") {
+        let success = texture::convert::run(args);
+        std::process::exit(if success { 0 } else { 1 });
+    }
     pollster::block_on(run());
 }
@@ -1,7 +1,9 @@
 use std::{f32::consts::PI, ops::Mul};
 
 use bytemuck::{Pod, Zeroable};
-use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, Rad, SquareMatrix, Vector3};
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix, Matrix3, Matrix4, Point3, Rad, SquareMatrix, Vector3,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Matrix4x4 {
@@ -38,13 +40,45 @@ impl Matrix4x4 {
         }
     }
 
-    pub fn view_proj(camera: Matrix4<f32>, projection: Matrix4<f32>) -> Self {
+    /// Converts world transformation into transformation relative to `origin`. The subtraction is
+    /// done in double precision, so the result is precise close to the origin no matter how far
+    /// from the centre of the world it is.
+    pub fn relative_to(world: Matrix4<f64>, origin: Point3<f64>) -> Self {
         Matrix4x4 {
-            data: (projection * camera).into(),
+            data: (Matrix4::from_translation(-origin.to_vec()) * world)
+                .cast::<f32>()
+                .expect("f64 matrix fits into f32")
+                .into(),
+        }
+    }
+
+    /// Inverse of the matrix. Singular matrices have no inverse and return identity.
+    pub fn inverse(&self) -> Self {
+        Matrix4x4 {
+            data: Matrix4::from(self.data)
+                .invert()
+                .unwrap_or(Matrix4::identity())
+                .into(),
         }
     }
 }
 
+impl From<Matrix4<f32>> for Matrix4x4 {
+    fn from(matrix: Matrix4<f32>) -> Self {
+        Matrix4x4 {
+            data: matrix.into(),
+        }
+    }
+}
+
+impl From<Matrix4x4> for Matrix4<f64> {
+    fn from(matrix: Matrix4x4) -> Self {
+        Matrix4::from(matrix.data)
+            .cast()
+            .expect("f32 matrix fits into f64")
+    }
+}
+
 impl Mul for Matrix4x4 {
     type Output = Matrix4x4;
 
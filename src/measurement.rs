@@ -0,0 +1,65 @@
+use cgmath::{Deg, InnerSpace, Rad};
+
+use crate::{body_info::SPEED_OF_LIGHT_KM_S, solar_object::render_solar_object::RenderSolarObject};
+
+/// Kilometres in an astronomical unit.
+pub const KM_PER_AU: f64 = 149_597_870.7;
+
+/// Geometry of two bodies at the time of the last update of the scene. It uses real positions,
+/// so it does not depend on the scale model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub observer: String,
+    pub target: String,
+    /// Distance between centres of the bodies.
+    pub separation_km: f64,
+    /// Angle between the Sun and the observer as seen from the target. It is 0° for fully lit and
+    /// 180° for unlit target. Missing, if either of the bodies is the Sun.
+    pub phase_angle: Option<Deg<f64>>,
+    /// Apparent diameter of the target seen from the point of the observer's surface closest to
+    /// it.
+    pub angular_size: Deg<f64>,
+}
+
+impl Measurement {
+    pub fn separation_au(&self) -> f64 {
+        self.separation_km / KM_PER_AU
+    }
+
+    pub fn separation_light_minutes(&self) -> f64 {
+        self.separation_km / SPEED_OF_LIGHT_KM_S / 60.0
+    }
+}
+
+/// Measures the `target` body seen from the `observer` body. The root body is the Sun.
+pub fn measure(
+    solar_object: &RenderSolarObject,
+    observer: &str,
+    target: &str,
+) -> Option<Measurement> {
+    let observer = solar_object.find(observer)?;
+    let target = solar_object.find(target)?;
+    let to_observer = observer.position_km - target.position_km;
+    let separation_km = to_observer.magnitude();
+
+    let is_sun = |body: &RenderSolarObject| std::ptr::eq(body, solar_object);
+    let to_sun = solar_object.position_km - target.position_km;
+    let phase_angle = (!is_sun(observer) && !is_sun(target) && separation_km > 0.0)
+        .then(|| to_sun.angle(to_observer).into());
+
+    let surface_distance = separation_km - observer.radius_km;
+    let angular_size = if surface_distance > target.radius_km {
+        Rad(2.0 * (target.radius_km / surface_distance).asin()).into()
+    } else {
+        // bodies touch or overlap, the target fills half of the sky
+        Deg(180.0)
+    };
+
+    Some(Measurement {
+        observer: observer.name.clone(),
+        target: target.name.clone(),
+        separation_km,
+        phase_angle,
+        angular_size,
+    })
+}
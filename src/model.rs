@@ -3,6 +3,7 @@ use wgpu::*;
 
 use crate::{matrix::Matrix4x4, texture::texture::RgbaTexture};
 
+pub mod ring;
 pub mod sphere;
 // pub mod sprite;
 
@@ -12,6 +13,9 @@ pub struct Vertex {
     position: [f32; 3],
     tex_coords: [f32; 2],
     normal: [f32; 3],
+    /// Direction of increasing U texture coordinate. W is the sign of the bitangent, which is
+    /// computed as `cross(normal, tangent) * w` and points towards decreasing V coordinate.
+    tangent: [f32; 4],
 }
 
 unsafe impl Pod for Vertex {}
@@ -38,6 +42,11 @@ impl Vertex {
                     shader_location: 2,
                     format: VertexFormat::Float32x3,
                 },
+                VertexAttribute {
+                    offset: size_of::<[f32; 8]>() as BufferAddress,
+                    shader_location: 3,
+                    format: VertexFormat::Float32x4,
+                },
             ],
         }
     }
@@ -51,8 +60,9 @@ pub struct Mesh {
 
 #[derive(Debug)]
 pub struct Model {
+    /// Textures bound to the texture bind group, in the order of its bindings.
     #[allow(unused)]
-    texture: RgbaTexture,
+    textures: Vec<RgbaTexture>,
     texture_bind_group: BindGroup,
     model_matrix: Matrix4x4,
     meshes: Vec<Mesh>,
@@ -73,6 +83,12 @@ pub struct MeshBuffers<'a> {
     pub index_format: IndexFormat,
 }
 
+impl Mesh {
+    pub fn buffers(&self) -> (BufferSlice<'_>, BufferSlice<'_>) {
+        (self.vertex_buffer.slice(..), self.index_buffer.slice(..))
+    }
+}
+
 impl<'a> Model {
     pub fn model_matrix(&self) -> &Matrix4x4 {
         &self.model_matrix
@@ -0,0 +1,93 @@
+use std::f32::consts::PI;
+
+use bytemuck::cast_slice;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
+
+use crate::{
+    matrix::Matrix4x4,
+    texture::texture::{RgbaTexture, TextureBindGroupDescriptor},
+};
+
+use super::{Mesh, Model, Vertex};
+
+/// Generates flat annulus in XZ plane. The U texture coordinate goes radially from inner edge (0)
+/// to outer edge (1), the V coordinate goes around the ring.
+fn generate_buffers(
+    inner_radius: f32,
+    outer_radius: f32,
+    segments: u32,
+) -> (Vec<Vertex>, Vec<u16>) {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    for x in 0..=segments {
+        let phi = 2.0 * PI * (x as f32) / (segments as f32);
+        let sin_phi = phi.sin();
+        let cos_phi = phi.cos();
+        let v = x as f32 / segments as f32;
+        for (radius, u) in [(inner_radius, 0.0), (outer_radius, 1.0)] {
+            vertices.push(Vertex {
+                position: [cos_phi * radius, 0.0, sin_phi * radius],
+                tex_coords: [u, v],
+                normal: [0.0, 1.0, 0.0],
+                tangent: [cos_phi, 0.0, sin_phi, 1.0],
+            });
+        }
+    }
+
+    for x in 0..segments {
+        let i0 = 2 * x;
+        let i1 = i0 + 1;
+        let i2 = i0 + 2;
+        let i3 = i0 + 3;
+
+        indices.push(i0 as u16);
+        indices.push(i1 as u16);
+        indices.push(i2 as u16);
+
+        indices.push(i2 as u16);
+        indices.push(i1 as u16);
+        indices.push(i3 as u16);
+    }
+
+    (vertices, indices)
+}
+
+pub fn create_ring(
+    device: &Device,
+    textures: Vec<RgbaTexture>,
+    texture_layout: TextureBindGroupDescriptor,
+    inner_radius: f32,
+    outer_radius: f32,
+    segments: u32,
+    model_matrix: Matrix4x4,
+) -> Model {
+    let (vertices, indices) = generate_buffers(inner_radius, outer_radius, segments);
+
+    let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Ring Vertex Buffer"),
+        contents: cast_slice(&vertices),
+        usage: BufferUsages::VERTEX,
+    });
+
+    let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Ring Index Buffer"),
+        contents: cast_slice(&indices),
+        usage: BufferUsages::INDEX,
+    });
+
+    let texture_bind_group = texture_layout.create_bind_group(device, &textures);
+
+    Model {
+        textures,
+        texture_bind_group,
+        model_matrix,
+        meshes: vec![Mesh {
+            vertex_buffer,
+            index_buffer,
+        }],
+    }
+}
@@ -38,6 +38,9 @@ fn generate_buffers(radius: f32, lat_segments: u32, long_segments: u32) -> (Vec<
                     y as f32 / lat_segments as f32,
                 ],
                 normal: [px, py, pz],
+                // V coordinate grows from north to south, so the bitangent is flipped to point
+                // north.
+                tangent: [-sin_phi, 0.0, cos_phi, -1.0],
             });
         }
     }
@@ -63,15 +66,14 @@ fn generate_buffers(radius: f32, lat_segments: u32, long_segments: u32) -> (Vec<
     (vertices, indices)
 }
 
-pub fn create_sphere(
+/// Creates sphere mesh without any material. Used by passes, which bring their own shading, such
+/// as atmosphere shells.
+pub fn create_sphere_mesh(
     device: &Device,
-    texture: RgbaTexture,
-    texture_layout: TextureBindGroupDescriptor,
     radius: f32,
     lat_segments: u32,
     long_segments: u32,
-    model_matrix: Matrix4x4,
-) -> Model {
+) -> Mesh {
     let (vertices, indices) = generate_buffers(radius, lat_segments, long_segments);
 
     let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
@@ -86,28 +88,29 @@ pub fn create_sphere(
         usage: BufferUsages::INDEX,
     });
 
-    let texture_bind_group = device.create_bind_group(&BindGroupDescriptor {
-        label: Some("Texture Bind Group"),
-        entries: &[
-            BindGroupEntry {
-                binding: texture_layout.binding_view,
-                resource: BindingResource::TextureView(&texture.view),
-            },
-            BindGroupEntry {
-                binding: texture_layout.binding_sampler,
-                resource: BindingResource::Sampler(&texture.sampler),
-            },
-        ],
-        layout: &texture_layout.layout,
-    });
+    Mesh {
+        vertex_buffer,
+        index_buffer,
+    }
+}
+
+pub fn create_sphere(
+    device: &Device,
+    textures: Vec<RgbaTexture>,
+    texture_layout: TextureBindGroupDescriptor,
+    radius: f32,
+    lat_segments: u32,
+    long_segments: u32,
+    model_matrix: Matrix4x4,
+) -> Model {
+    let mesh = create_sphere_mesh(device, radius, lat_segments, long_segments);
+
+    let texture_bind_group = texture_layout.create_bind_group(device, &textures);
 
     Model {
-        texture,
+        textures,
         texture_bind_group,
         model_matrix,
-        meshes: vec![Mesh {
-            vertex_buffer,
-            index_buffer,
-        }],
+        meshes: vec![mesh],
     }
 }
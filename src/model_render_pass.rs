@@ -1,75 +1,165 @@
-use wgpu::*;
+use bytemuck::cast_slice;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
 
 use crate::{
     model::{MeshBuffers, ModelNormalBindGroupDescriptor, Vertex},
-    render_target::{RenderTarget, RenderTargetConfig},
+    render_target::{DEPTH_CLEAR_VALUE, RenderTarget, RenderTargetConfig, ScenePipeline},
     scene::SceneModel,
-    texture::texture::TextureBindGroupDescriptor,
+    texture::texture::{RgbaTexture, TextureBindGroupDescriptor, TextureBinding},
 };
 
+/// Texture slots of models. Base colour is lit by the sun, night texture is emitted on the unlit
+/// side, specular texture masks reflective surfaces, such as oceans, and normal texture holds
+/// tangent space normals of the surface relief.
+pub const BASE_COLOR_SLOT: usize = 0;
+pub const NIGHT_SLOT: usize = 1;
+pub const SPECULAR_SLOT: usize = 2;
+pub const NORMAL_SLOT: usize = 3;
+pub const TEXTURE_SLOTS: usize = 4;
+const TEXTURE_BINDINGS: [TextureBinding; TEXTURE_SLOTS] = [
+    TextureBinding {
+        binding_view: 0,
+        binding_sampler: 1,
+    },
+    TextureBinding {
+        binding_view: 2,
+        binding_sampler: 3,
+    },
+    TextureBinding {
+        binding_view: 4,
+        binding_sampler: 5,
+    },
+    TextureBinding {
+        binding_view: 6,
+        binding_sampler: 7,
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingShadowBindGroupDescriptor<'a> {
+    pub layout: &'a BindGroupLayout,
+    pub radii_binding: u32,
+    pub binding_view: u32,
+    pub binding_sampler: u32,
+}
+
+/// Creates bind group with rings casting shadow on their body. Radii are in units of the body
+/// radius.
+pub fn create_ring_shadow_bind_group(
+    device: &Device,
+    ring_shadow_layout: RingShadowBindGroupDescriptor,
+    ring_texture: &RgbaTexture,
+    inner_radius: f32,
+    outer_radius: f32,
+) -> BindGroup {
+    let radii_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("ring radii buffer"),
+        usage: BufferUsages::UNIFORM,
+        contents: cast_slice(&[inner_radius, outer_radius, 0.0, 0.0]),
+    });
+    device.create_bind_group(&BindGroupDescriptor {
+        label: Some("ring shadow bind group"),
+        layout: ring_shadow_layout.layout,
+        entries: &[
+            BindGroupEntry {
+                binding: ring_shadow_layout.radii_binding,
+                resource: radii_buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: ring_shadow_layout.binding_view,
+                resource: BindingResource::TextureView(&ring_texture.view),
+            },
+            BindGroupEntry {
+                binding: ring_shadow_layout.binding_sampler,
+                resource: BindingResource::Sampler(&ring_texture.sampler),
+            },
+        ],
+    })
+}
+
 #[derive(Debug)]
 pub struct ModelRenderPass {
-    render_pipeline: RenderPipeline,
+    render_pipeline: ScenePipeline,
+    // semi-transparent layers, such as clouds, drawn on top of opaque models
+    layer_pipeline: ScenePipeline,
     view_proj_bind_group: BindGroup,
     model_normal_mat_layout: BindGroupLayout,
     texture_bind_group_layout: BindGroupLayout,
+    ring_shadow_layout: BindGroupLayout,
+    // used by bodies without rings
+    no_ring_shadow_bind_group: BindGroup,
 }
 
 impl ModelRenderPass {
     pub fn new(
         device: &Device,
+        queue: &Queue,
         render_target: &RenderTargetConfig,
-        view_proj_buffer: &Buffer,
+        camera_buffer: &Buffer,
     ) -> ModelRenderPass {
-        let vertex_bind_group_entry = |binding: u32| BindGroupLayoutEntry {
-            binding,
-            visibility: ShaderStages::VERTEX,
-            ty: BindingType::Buffer {
-                ty: BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: None,
-            },
-            count: None,
-        };
+        let uniform_bind_group_entry =
+            |binding: u32, visibility: ShaderStages| BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            };
         let view_proj_mat_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("view-proj layout"),
-            entries: &[vertex_bind_group_entry(0)],
+            entries: &[uniform_bind_group_entry(0, ShaderStages::VERTEX_FRAGMENT)],
         });
 
         let model_normal_mat_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("model-normal layout"),
-            entries: &[vertex_bind_group_entry(0), vertex_bind_group_entry(1)],
+            entries: &[
+                // model matrix is needed by fragment shader to find shadows of rings
+                uniform_bind_group_entry(0, ShaderStages::VERTEX_FRAGMENT),
+                uniform_bind_group_entry(1, ShaderStages::VERTEX),
+            ],
         });
 
         let texture_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
                 label: Some("Texture Bind Group Layout"),
-                entries: &[
-                    BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Texture {
-                            sample_type: TextureSampleType::Float { filterable: true },
-                            view_dimension: TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                    BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: ShaderStages::FRAGMENT,
-                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
+                entries: &TextureBindGroupDescriptor::layout_entries(&TEXTURE_BINDINGS),
             });
 
+        let ring_shadow_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("ring shadow layout"),
+            entries: &[
+                uniform_bind_group_entry(0, ShaderStages::FRAGMENT),
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
         let view_proj_bind_group = device.create_bind_group(&BindGroupDescriptor {
             label: Some("view-proj bind group"),
             layout: &view_proj_mat_layout,
             entries: &[BindGroupEntry {
                 binding: 0,
-                resource: view_proj_buffer.as_entire_binding(),
+                resource: camera_buffer.as_entire_binding(),
             }],
         });
 
@@ -81,6 +171,8 @@ impl ModelRenderPass {
                 // model and normal matrix
                 &model_normal_mat_layout,
                 &texture_bind_group_layout,
+                // shadows cast by rings
+                &ring_shadow_layout,
             ],
             push_constant_ranges: &[],
         });
@@ -90,77 +182,114 @@ impl ModelRenderPass {
             source: ShaderSource::Wgsl(include_str!("model_shader.wgsl").into()),
         });
 
-        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[Vertex::desc().clone()],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(ColorTargetState {
-                    format: render_target.target_texture_format(),
-                    blend: Some(BlendState {
-                        color: BlendComponent::REPLACE,
-                        alpha: BlendComponent::REPLACE,
+        let create_pipeline = |label: &str,
+                               fragment_entry_point: &str,
+                               blend: BlendState,
+                               depth_write_enabled: bool| {
+            ScenePipeline::new(render_target, |sample_count| {
+                device.create_render_pipeline(&RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&render_pipeline_layout),
+                    vertex: VertexState {
+                        module: &shader,
+                        entry_point: Some("vs_main"),
+                        buffers: &[Vertex::desc().clone()],
+                        compilation_options: Default::default(),
+                    },
+                    fragment: Some(FragmentState {
+                        module: &shader,
+                        entry_point: Some(fragment_entry_point),
+                        targets: &[Some(ColorTargetState {
+                            format: render_target.target_texture_format(),
+                            blend: Some(blend),
+                            write_mask: ColorWrites::ALL,
+                        })],
+                        compilation_options: Default::default(),
                     }),
-                    write_mask: ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: FrontFace::Ccw,
-                cull_mode: None,
-                // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
-                // or Features::POLYGON_MODE_POINT
-                polygon_mode: PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
+                    primitive: PrimitiveState {
+                        topology: PrimitiveTopology::TriangleList,
+                        strip_index_format: None,
+                        front_face: FrontFace::Ccw,
+                        cull_mode: None,
+                        // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
+                        // or Features::POLYGON_MODE_POINT
+                        polygon_mode: PolygonMode::Fill,
+                        // Requires Features::DEPTH_CLIP_CONTROL
+                        unclipped_depth: false,
+                        // Requires Features::CONSERVATIVE_RASTERIZATION
+                        conservative: false,
+                    },
+                    depth_stencil: Some(DepthStencilState {
+                        format: render_target.depth_texture_format(),
+                        depth_write_enabled,
+                        depth_compare: render_target.depth_compare(),
+                        stencil: StencilState::default(),
+                        bias: DepthBiasState {
+                            // Negative bias pushes fragments away, as depth is reversed
+                            constant: -2, // Corresponds to bilinear filtering
+                            slope_scale: -2.0,
+                            clamp: 0.0,
+                        },
+                    }),
+                    multisample: MultisampleState {
+                        count: sample_count,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    // If the pipeline will be used with a multiview render pass, this
+                    // indicates how many array layers the attachments will have.
+                    multiview: None,
+                    // Useful for optimizing shader compilation on Android
+                    cache: None,
+                })
+            })
+        };
+
+        let render_pipeline = create_pipeline(
+            "Render Pipeline",
+            "fs_main",
+            BlendState {
+                color: BlendComponent::REPLACE,
+                alpha: BlendComponent::REPLACE,
             },
-            depth_stencil: Some(DepthStencilState {
-                format: render_target.depth_texture_format(),
-                depth_write_enabled: true,
-                depth_compare: CompareFunction::Less,
-                stencil: StencilState::default(),
-                bias: DepthBiasState {
-                    constant: 2, // Corresponds to bilinear filtering
-                    slope_scale: 2.0,
-                    clamp: 0.0,
-                },
-            }),
-            multisample: MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
+            true,
+        );
+        // Layers are drawn above their body and do not hide each other.
+        let layer_pipeline = create_pipeline(
+            "Layer Pipeline",
+            "fs_layer",
+            BlendState::ALPHA_BLENDING,
+            false,
+        );
+
+        let no_ring_shadow_bind_group = create_ring_shadow_bind_group(
+            device,
+            RingShadowBindGroupDescriptor {
+                layout: &ring_shadow_layout,
+                radii_binding: 0,
+                binding_view: 1,
+                binding_sampler: 2,
             },
-            // If the pipeline will be used with a multiview render pass, this
-            // indicates how many array layers the attachments will have.
-            multiview: None,
-            // Useful for optimizing shader compilation on Android
-            cache: None,
-        });
+            &RgbaTexture::from_color(device, queue, [0, 0, 0, 0]),
+            0.0,
+            0.0,
+        );
 
         ModelRenderPass {
             render_pipeline,
+            layer_pipeline,
             view_proj_bind_group,
             model_normal_mat_layout,
             texture_bind_group_layout,
+            ring_shadow_layout,
+            no_ring_shadow_bind_group,
         }
     }
 
     pub fn texture_layout(&self) -> TextureBindGroupDescriptor<'_> {
         TextureBindGroupDescriptor {
             layout: &self.texture_bind_group_layout,
-            binding_view: 0,
-            binding_sampler: 1,
+            bindings: &TEXTURE_BINDINGS,
         }
     }
 
@@ -172,31 +301,36 @@ impl ModelRenderPass {
         }
     }
 
+    pub fn ring_shadow_layout(&self) -> RingShadowBindGroupDescriptor<'_> {
+        RingShadowBindGroupDescriptor {
+            layout: &self.ring_shadow_layout,
+            radii_binding: 0,
+            binding_view: 1,
+            binding_sampler: 2,
+        }
+    }
+
     pub fn record_draw_commands<'a>(
         &self,
         encoder: &mut CommandEncoder,
         render_target: &RenderTarget,
         models: impl Iterator<Item = &'a SceneModel>,
+        layers: impl Iterator<Item = &'a SceneModel>,
     ) {
         let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
             label: Some("Render Pass"),
             color_attachments: &[Some(RenderPassColorAttachment {
-                view: &render_target.surface_texture_view(),
-                resolve_target: None,
+                view: render_target.scene_view(),
+                resolve_target: render_target.resolve_target(),
                 ops: Operations {
-                    load: LoadOp::Clear(Color {
-                        r: 0.0,
-                        g: 0.0,
-                        b: 0.0,
-                        a: 1.0,
-                    }),
+                    load: LoadOp::Load,
                     store: StoreOp::Store,
                 },
             })],
             depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-                view: render_target.config.depth_texture_view(),
+                view: render_target.depth_texture_view(),
                 depth_ops: Some(Operations {
-                    load: LoadOp::Clear(1.0),
+                    load: LoadOp::Clear(DEPTH_CLEAR_VALUE),
                     store: StoreOp::Store,
                 }),
                 stencil_ops: None,
@@ -205,25 +339,41 @@ impl ModelRenderPass {
             timestamp_writes: None,
         });
 
-        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_pipeline(self.render_pipeline.get(render_target));
         render_pass.set_bind_group(0, &self.view_proj_bind_group, &[]);
         for scene_model in models {
-            render_pass.set_bind_group(1, &scene_model.model_bind_group, &[]);
-            render_pass.set_bind_group(2, scene_model.model.texture_bind_group(), &[]);
-            for MeshBuffers {
-                texture_bind_group,
-                vertex_buffer,
-                index_buffer,
-                index_format,
-            } in scene_model.model.meshes()
-            {
-                render_pass.set_bind_group(2, texture_bind_group, &[]);
-                render_pass.set_vertex_buffer(0, vertex_buffer);
-                render_pass.set_index_buffer(index_buffer, index_format);
-                // Index buffer contains u16 indices stored in u8 array. The number of elements is
-                // therefore half of its size.
-                render_pass.draw_indexed(0..index_buffer.size().get() as u32 / 2, 0, 0..1);
-            }
+            self.draw_model(&mut render_pass, scene_model);
+        }
+        render_pass.set_pipeline(self.layer_pipeline.get(render_target));
+        for scene_model in layers {
+            self.draw_model(&mut render_pass, scene_model);
+        }
+    }
+
+    fn draw_model(&self, render_pass: &mut RenderPass, scene_model: &SceneModel) {
+        render_pass.set_bind_group(1, &scene_model.model_bind_group, &[]);
+        render_pass.set_bind_group(2, scene_model.model.texture_bind_group(), &[]);
+        render_pass.set_bind_group(
+            3,
+            scene_model
+                .ring_shadow_bind_group
+                .as_ref()
+                .unwrap_or(&self.no_ring_shadow_bind_group),
+            &[],
+        );
+        for MeshBuffers {
+            texture_bind_group,
+            vertex_buffer,
+            index_buffer,
+            index_format,
+        } in scene_model.model.meshes()
+        {
+            render_pass.set_bind_group(2, texture_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer);
+            render_pass.set_index_buffer(index_buffer, index_format);
+            // Index buffer contains u16 indices stored in u8 array. The number of elements is
+            // therefore half of its size.
+            render_pass.draw_indexed(0..index_buffer.size().get() as u32 / 2, 0, 0..1);
         }
     }
 }
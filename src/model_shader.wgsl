@@ -1,7 +1,15 @@
 // Vertex shader
 
+struct Camera {
+    view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+    inverse_view_proj: mat4x4<f32>,
+    // position of the Sun relative to the camera
+    light_position: vec4<f32>,
+}
+
 @group(0) @binding(0)
-var<uniform> view_proj_mat: mat4x4<f32>;
+var<uniform> camera: Camera;
 @group(1) @binding(0)
 var<uniform> model_mat: mat4x4<f32>;
 @group(1) @binding(1)
@@ -11,6 +19,7 @@ struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
     @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
 }
 
 struct VertexOutput {
@@ -18,6 +27,7 @@ struct VertexOutput {
     @location(0) position: vec3<f32>,
     @location(1) tex_coords: vec2<f32>,
     @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
 }
 
 @vertex
@@ -26,36 +36,123 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
     var world_position = model_mat * vec4<f32>(model.position, 1.0);
-    out.clip_position = view_proj_mat * world_position;
+    out.clip_position = camera.view_proj * world_position;
     // ?? world position.z should be always 1, since model matrix should not change W
     out.position = world_position.xyz / world_position.w;
     out.tex_coords = model.tex_coords;
     out.normal = normal_mat * model.normal;
+    out.tangent = vec4<f32>((model_mat * vec4<f32>(model.tangent.xyz, 0.0)).xyz, model.tangent.w);
     return out;
 }
 
 // Fragment shader
 
-const LIGHT_POS: vec3<f32> = vec3<f32>(0.0, 0.0, 0.0);
+const SHININESS: f32 = 40.0;
+const SPECULAR_STRENGTH: f32 = 0.5;
 
 @group(2) @binding(0)
 var tex_data: texture_2d<f32>;
 @group(2) @binding(1)
 var tex_sampler: sampler;
+// Emitted light on the unlit side, e.g. city lights. Black for bodies without one.
+@group(2) @binding(2)
+var night_tex: texture_2d<f32>;
+@group(2) @binding(3)
+var night_sampler: sampler;
+// Mask of surfaces with specular reflection, e.g. oceans. Black for bodies without one.
+@group(2) @binding(4)
+var specular_tex: texture_2d<f32>;
+@group(2) @binding(5)
+var specular_sampler: sampler;
+// Tangent space normals of the surface relief. Flat for bodies without one.
+@group(2) @binding(6)
+var normal_tex: texture_2d<f32>;
+@group(2) @binding(7)
+var normal_sampler: sampler;
+// Inner and outer radius of rings in units of the body radius. Both are zero for bodies without
+// rings.
+@group(3) @binding(0)
+var<uniform> ring_radii: vec4<f32>;
+@group(3) @binding(1)
+var ring_tex: texture_2d<f32>;
+@group(3) @binding(2)
+var ring_sampler: sampler;
 // // TODO lightning coefficients
-// @group(2) @binding(2)
+// @group(2) @binding(8)
 // var ambient_coef: f32;
-// @group(2) @binding(3)
+// @group(2) @binding(9)
 // var diffuse_coef: f32;
-// @group(2) @binding(4)
+// @group(2) @binding(10)
 // var specular_coef: f32;
 
+// Returns how much light passes through rings of the body on the way to the given point.
+fn ring_transmittance(position: vec3<f32>, light_dir: vec3<f32>) -> f32 {
+    if ring_radii.y <= 0.0 {
+        return 1.0;
+    }
+    // Rings lie in the equatorial plane of the body. Rotation of the body does not change it.
+    let center = (model_mat * vec4<f32>(0.0, 0.0, 0.0, 1.0)).xyz;
+    let ring_normal = normalize((model_mat * vec4<f32>(0.0, 1.0, 0.0, 0.0)).xyz);
+    let body_radius = length((model_mat * vec4<f32>(1.0, 0.0, 0.0, 0.0)).xyz);
+    let denom = dot(light_dir, ring_normal);
+    if abs(denom) < 1e-5 {
+        return 1.0;
+    }
+    let t = dot(center - position, ring_normal) / denom;
+    if t <= 0.0 {
+        return 1.0;
+    }
+    let radius = length(position + light_dir * t - center) / body_radius;
+    if radius < ring_radii.x || radius > ring_radii.y {
+        return 1.0;
+    }
+    let u = (radius - ring_radii.x) / (ring_radii.y - ring_radii.x);
+    return 1.0 - textureSampleLevel(ring_tex, ring_sampler, vec2<f32>(u, 0.5), 0.0).a;
+}
+
+// Perturbs the geometric normal by the normal map.
+fn surface_normal(normal: vec3<f32>, tangent: vec4<f32>, tex_coords: vec2<f32>) -> vec3<f32> {
+    let t = normalize(tangent.xyz - normal * dot(normal, tangent.xyz));
+    let b = cross(normal, t) * tangent.w;
+    let mapped = textureSample(normal_tex, normal_sampler, tex_coords).xyz * 2.0 - 1.0;
+    return normalize(t * mapped.x + b * mapped.y + normal * mapped.z);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     var normal = normalize(in.normal);
-    var light_dir = normalize(LIGHT_POS - in.position);
-    var diffuse = max(dot(normal, light_dir), 0.0);
+    var light_dir = normalize(camera.light_position.xyz - in.position);
+    var view_dir = normalize(camera.position.xyz - in.position);
+    // Terminator is given by the shape of the body, relief only shades the lit side.
+    var incidence = dot(normal, light_dir);
+    var shadow = ring_transmittance(in.position, light_dir) * clamp(incidence * 10.0, 0.0, 1.0);
+    var relief_normal = surface_normal(normal, in.tangent, in.tex_coords);
+    var diffuse = max(dot(relief_normal, light_dir), 0.0) * shadow;
+
+    var texel = textureSample(tex_data, tex_sampler, in.tex_coords);
+    var night = textureSample(night_tex, night_sampler, in.tex_coords).rgb;
+    var specular_mask = textureSample(specular_tex, specular_sampler, in.tex_coords).r;
+
+    // Night side emission fades in smoothly across the terminator.
+    var night_weight = smoothstep(0.1, -0.1, incidence);
+    var half_dir = normalize(light_dir + view_dir);
+    var specular = pow(max(dot(relief_normal, half_dir), 0.0), SHININESS)
+        * SPECULAR_STRENGTH
+        * specular_mask
+        * shadow;
+
+    return vec4<f32>(texel.rgb * diffuse + night * night_weight + vec3<f32>(specular), texel.a);
+}
+
+// Semi-transparent layer above the body, e.g. clouds. Layer textures usually do not have alpha,
+// so brightness of the texel is used as its opacity.
+@fragment
+fn fs_layer(in: VertexOutput) -> @location(0) vec4<f32> {
+    var normal = normalize(in.normal);
+    var light_dir = normalize(camera.light_position.xyz - in.position);
+    var diffuse = max(dot(normal, light_dir), 0.0) * ring_transmittance(in.position, light_dir);
 
     var texel = textureSample(tex_data, tex_sampler, in.tex_coords);
-    return vec4<f32>(texel.rgb * diffuse, texel.a);
+    var opacity = texel.a * max(texel.r, max(texel.g, texel.b));
+    return vec4<f32>(vec3<f32>(diffuse), opacity);
 }
\ No newline at end of file
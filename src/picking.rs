@@ -0,0 +1,56 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::solar_object::render_solar_object::RenderSolarObject;
+
+/// Ray in world coordinates with unit direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f64>,
+    pub direction: Vector3<f64>,
+}
+
+impl Ray {
+    /// Distance along the ray to the first intersection with the sphere, if it is in front of the
+    /// ray origin. Origin inside the sphere returns the exit point.
+    pub fn intersect_sphere(&self, centre: Point3<f64>, radius: f64) -> Option<f64> {
+        let to_centre = centre - self.origin;
+        let closest = to_centre.dot(self.direction);
+        let distance2 = to_centre.magnitude2() - closest * closest;
+        let half_chord2 = radius * radius - distance2;
+        if half_chord2 < 0.0 {
+            return None;
+        }
+        let half_chord = half_chord2.sqrt();
+        [closest - half_chord, closest + half_chord]
+            .into_iter()
+            .find(|t| *t >= 0.0)
+    }
+}
+
+/// Body hit by a picking ray.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pick {
+    pub name: String,
+    /// Point on the bounding sphere of the body, where the ray hit it.
+    pub hit_point: Point3<f64>,
+}
+
+/// Finds the nearest body hit by `ray`. Bodies are tested against bounding spheres of their
+/// rendered size, rings are not pickable.
+pub fn pick_body(solar_object: &RenderSolarObject, ray: Ray) -> Option<Pick> {
+    nearest_hit(solar_object, ray).map(|(t, body)| Pick {
+        name: body.name.clone(),
+        hit_point: ray.origin + ray.direction * t,
+    })
+}
+
+fn nearest_hit(body: &RenderSolarObject, ray: Ray) -> Option<(f64, &RenderSolarObject)> {
+    let hit = ray
+        .intersect_sphere(body.world_position, body.rendered_radius())
+        .map(|t| (t, body));
+    body.children
+        .iter()
+        .filter_map(|child| nearest_hit(child, ray))
+        .chain(hit)
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+}
@@ -1,14 +1,59 @@
+use std::{fs, sync::mpsc};
+
+use image::RgbaImage;
+use serde::Deserialize;
 use wgpu::*;
 use winit::dpi::PhysicalSize;
 
+use crate::fxaa_render_pass::FxaaRenderPass;
+
+/// When FXAA post-process is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FxaaMode {
+    Off,
+    /// Only when the requested multisampling is not supported.
+    #[default]
+    Fallback,
+    On,
+}
+
+/// Anti-aliasing of frames. It is set when the window is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct AntialiasingConfig {
+    /// Samples per pixel, 1, 2, 4 or 8. Unsupported counts fall back to the nearest lower one.
+    pub msaa: u32,
+    pub fxaa: FxaaMode,
+}
+
+impl Default for AntialiasingConfig {
+    fn default() -> Self {
+        Self {
+            msaa: 4,
+            fxaa: FxaaMode::default(),
+        }
+    }
+}
+
+pub fn load_antialiasing_config(path: &str) -> AntialiasingConfig {
+    let toml_str = fs::read_to_string(path).unwrap();
+    toml::from_str(&toml_str).unwrap()
+}
+
 #[derive(Debug)]
 pub struct RenderTargetConfig<'window> {
     surface: Surface<'window>,
     config: SurfaceConfiguration,
-    depth_texture: (Texture, TextureView),
+    sample_count: u32,
+    fxaa: Option<FxaaRenderPass>,
+    buffers: SceneBuffers,
 }
 
+// Floating point depth is required by reversed depth of the projection.
 const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+/// Depth of infinitely far points.
+pub const DEPTH_CLEAR_VALUE: f32 = 0.0;
 
 impl<'window> RenderTargetConfig<'window> {
     pub fn new(
@@ -16,8 +61,9 @@ impl<'window> RenderTargetConfig<'window> {
         device: &Device,
         surface: Surface<'window>,
         adapter: &Adapter,
+        antialiasing: AntialiasingConfig,
     ) -> Result<Self, SurfaceError> {
-        let surface_caps = surface.get_capabilities(&adapter);
+        let surface_caps = surface.get_capabilities(adapter);
 
         // Shader code in this project assumes an Srgb surface texture. Using a different one will
         // result all the colors comming out darker. If you want to support non Srgb surfaces,
@@ -53,18 +99,34 @@ impl<'window> RenderTargetConfig<'window> {
             view_formats: vec![],
         };
 
-        let depth_texture = create_depth_texture(
-            &device,
-            PhysicalSize {
-                width: config.width,
-                height: config.height,
-            },
+        let sample_count =
+            supported_sample_count(adapter, antialiasing.msaa, &[format, DEPTH_FORMAT]);
+        if sample_count < antialiasing.msaa {
+            eprintln!(
+                "MSAA {}x is not supported, using {sample_count}x",
+                antialiasing.msaa
+            );
+        }
+        let fxaa = match antialiasing.fxaa {
+            FxaaMode::Off => false,
+            FxaaMode::Fallback => sample_count == 1 && antialiasing.msaa > 1,
+            FxaaMode::On => true,
+        }
+        .then(|| FxaaRenderPass::new(device, format));
+        let buffers = SceneBuffers::new(
+            device,
+            format,
+            sample_count,
+            fxaa.as_ref(),
+            PhysicalSize::new(config.width, config.height),
         );
 
         Ok(RenderTargetConfig {
             surface,
             config,
-            depth_texture,
+            sample_count,
+            fxaa,
+            buffers,
         })
     }
 
@@ -72,7 +134,13 @@ impl<'window> RenderTargetConfig<'window> {
         self.config.width = new_size.width.max(1);
         self.config.height = new_size.height.max(1);
         self.surface.configure(device, &self.config);
-        self.depth_texture = create_depth_texture(&device, new_size);
+        self.buffers = SceneBuffers::new(
+            device,
+            self.config.format,
+            self.sample_count,
+            self.fxaa.as_ref(),
+            self.size(),
+        );
     }
 
     /// Gets new render target with surface colour buffer attached to it.
@@ -80,16 +148,21 @@ impl<'window> RenderTargetConfig<'window> {
     /// # Panics
     ///
     /// Panics, if previous result is not yet dropped.
-    pub fn next_frame(&self) -> Result<RenderTarget<'_>, SurfaceError> {
+    pub fn next_frame(&self) -> Result<RenderTarget, SurfaceError> {
         let surface_texture = self.surface.get_current_texture()?;
-        Ok(RenderTarget {
-            config: self,
-            surface_texture,
-        })
+        let color_view = surface_texture
+            .texture
+            .create_view(&TextureViewDescriptor::default());
+        Ok(self.buffers.render_target(
+            Some(surface_texture),
+            color_view,
+            self.fxaa.as_ref(),
+            self.size(),
+        ))
     }
 
-    pub fn depth_texture_view(&self) -> &TextureView {
-        &self.depth_texture.1
+    pub fn size(&self) -> PhysicalSize<u32> {
+        PhysicalSize::new(self.config.width, self.config.height)
     }
 
     pub fn target_texture_format(&self) -> TextureFormat {
@@ -99,26 +172,358 @@ impl<'window> RenderTargetConfig<'window> {
     pub fn depth_texture_format(&self) -> TextureFormat {
         DEPTH_FORMAT
     }
+
+    /// Closer fragments have greater depth.
+    pub fn depth_compare(&self) -> CompareFunction {
+        CompareFunction::Greater
+    }
 }
 
-pub struct RenderTarget<'window> {
-    pub config: &'window RenderTargetConfig<'window>,
-    pub surface_texture: SurfaceTexture,
+/// Highest sample count up to `requested` supported by all `formats`. Counts other than 1 and 4
+/// need adapter specific format features.
+fn supported_sample_count(adapter: &Adapter, requested: u32, formats: &[TextureFormat]) -> u32 {
+    let adapter_specific = adapter
+        .features()
+        .contains(Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES);
+    [8, 4, 2, 1]
+        .into_iter()
+        .filter(|count| *count <= requested)
+        .find(|count| {
+            *count == 1
+                || formats.iter().all(|format| {
+                    if adapter_specific {
+                        adapter
+                            .get_texture_format_features(*format)
+                            .flags
+                            .sample_count_supported(*count)
+                    } else {
+                        *count == 4
+                    }
+                })
+        })
+        .unwrap_or(1)
 }
 
-impl<'window> RenderTarget<'window> {
+/// Buffers the scene is drawn into before it gets to the frame. They have the size of the frame.
+#[derive(Debug)]
+struct SceneBuffers {
+    sample_count: u32,
+    /// Multisampled colour, resolved after each pass.
+    msaa_color: Option<TextureView>,
+    /// Resolved scene filtered by FXAA into the frame, with the bind group reading it.
+    fxaa_input: Option<(TextureView, BindGroup)>,
+    depth_texture: (Texture, TextureView),
+}
+
+impl SceneBuffers {
+    fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        fxaa: Option<&FxaaRenderPass>,
+        size: PhysicalSize<u32>,
+    ) -> Self {
+        let color_texture = |label: &str, sample_count: u32, usage: TextureUsages| {
+            device
+                .create_texture(&TextureDescriptor {
+                    label: Some(label),
+                    size: Extent3d {
+                        width: size.width.max(1),
+                        height: size.height.max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: TextureDimension::D2,
+                    format,
+                    usage,
+                    view_formats: &[],
+                })
+                .create_view(&TextureViewDescriptor::default())
+        };
+        let msaa_color = (sample_count > 1).then(|| {
+            color_texture(
+                "Multisampled Texture",
+                sample_count,
+                TextureUsages::RENDER_ATTACHMENT,
+            )
+        });
+        let fxaa_input = fxaa.map(|fxaa| {
+            let view = color_texture(
+                "FXAA Input Texture",
+                1,
+                TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            );
+            let bind_group = fxaa.bind_group(device, &view);
+            (view, bind_group)
+        });
+        Self {
+            sample_count,
+            msaa_color,
+            fxaa_input,
+            depth_texture: create_depth_texture(device, size, sample_count),
+        }
+    }
+
+    fn render_target(
+        &self,
+        surface_texture: Option<SurfaceTexture>,
+        color_view: TextureView,
+        fxaa: Option<&FxaaRenderPass>,
+        size: PhysicalSize<u32>,
+    ) -> RenderTarget {
+        RenderTarget {
+            surface_texture,
+            color_view,
+            sample_count: self.sample_count,
+            msaa_color: self.msaa_color.clone(),
+            fxaa: fxaa
+                .cloned()
+                .zip(self.fxaa_input.clone())
+                .map(|(fxaa, (view, bind_group))| (fxaa, view, bind_group)),
+            depth_view: self.depth_texture.1.clone(),
+            size,
+        }
+    }
+}
+
+/// Colour and depth buffers of a frame. Colour is either the next surface texture of the window or
+/// an [`OffscreenTarget`].
+///
+/// Scene passes draw into the scene view, which is multisampled or post-processed before it gets to
+/// the colour view, if anti-aliasing needs it. Overlays draw into the colour view directly.
+pub struct RenderTarget {
+    surface_texture: Option<SurfaceTexture>,
+    color_view: TextureView,
+    sample_count: u32,
+    msaa_color: Option<TextureView>,
+    fxaa: Option<(FxaaRenderPass, TextureView, BindGroup)>,
+    depth_view: TextureView,
+    size: PhysicalSize<u32>,
+}
+
+impl RenderTarget {
+    /// Shows the frame in the window. Offscreen frames are not presented.
     pub fn present(self) {
-        self.surface_texture.present();
+        if let Some(surface_texture) = self.surface_texture {
+            surface_texture.present();
+        }
     }
 
-    pub fn surface_texture_view(&self) -> TextureView {
-        self.surface_texture
-            .texture
-            .create_view(&TextureViewDescriptor::default())
+    pub fn color_view(&self) -> &TextureView {
+        &self.color_view
+    }
+
+    /// Colour view scene passes draw into.
+    pub fn scene_view(&self) -> &TextureView {
+        self.msaa_color
+            .as_ref()
+            .or(self.resolved_scene_view())
+            .unwrap_or(&self.color_view)
+    }
+
+    /// View multisampled scene passes resolve into, none without multisampling.
+    pub fn resolve_target(&self) -> Option<&TextureView> {
+        self.msaa_color
+            .as_ref()
+            .map(|_| self.resolved_scene_view().unwrap_or(&self.color_view))
+    }
+
+    fn resolved_scene_view(&self) -> Option<&TextureView> {
+        self.fxaa.as_ref().map(|(_, view, _)| view)
     }
+
+    /// Post-processes the finished scene into the colour view. Runs after all scene passes.
+    pub fn post_process(&self, encoder: &mut CommandEncoder) {
+        if let Some((fxaa, _, bind_group)) = &self.fxaa {
+            fxaa.record_draw_commands(encoder, bind_group, &self.color_view);
+        }
+    }
+
+    pub fn depth_texture_view(&self) -> &TextureView {
+        &self.depth_view
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+}
+
+/// Pipeline of a scene pass for each sample count of render targets. Targets have the samples of
+/// the window, or a single one when they are drawn without anti-aliasing.
+#[derive(Debug)]
+pub struct ScenePipeline {
+    pipeline: RenderPipeline,
+    single_sampled: Option<RenderPipeline>,
+}
+
+impl ScenePipeline {
+    /// Creates the pipeline by `create` with a sample count.
+    pub fn new(config: &RenderTargetConfig, create: impl Fn(u32) -> RenderPipeline) -> Self {
+        Self {
+            pipeline: create(config.sample_count),
+            single_sampled: (config.sample_count > 1).then(|| create(1)),
+        }
+    }
+
+    /// Pipeline drawing into the scene view of `render_target`.
+    pub fn get(&self, render_target: &RenderTarget) -> &RenderPipeline {
+        match &self.single_sampled {
+            Some(single_sampled) if render_target.sample_count == 1 => single_sampled,
+            _ => &self.pipeline,
+        }
+    }
+}
+
+/// Texture frames are drawn into instead of the window. It has the same formats as the window, so
+/// scene pipelines draw into it, and it can be read back.
+#[derive(Debug)]
+pub struct OffscreenTarget {
+    color_texture: Texture,
+    buffers: SceneBuffers,
+    fxaa: Option<FxaaRenderPass>,
+    /// Rows of the colour texture padded to the copy alignment.
+    readback: Buffer,
+    size: PhysicalSize<u32>,
+}
+
+impl OffscreenTarget {
+    /// Creates target anti-aliased like the window.
+    pub fn new(device: &Device, config: &RenderTargetConfig, size: PhysicalSize<u32>) -> Self {
+        Self::with_antialiasing(
+            device,
+            config,
+            size,
+            config.sample_count,
+            config.fxaa.clone(),
+        )
+    }
+
+    /// Creates target without multisampling and FXAA, for frames, which are supersampled instead.
+    pub fn without_antialiasing(
+        device: &Device,
+        config: &RenderTargetConfig,
+        size: PhysicalSize<u32>,
+    ) -> Self {
+        Self::with_antialiasing(device, config, size, 1, None)
+    }
+
+    fn with_antialiasing(
+        device: &Device,
+        config: &RenderTargetConfig,
+        size: PhysicalSize<u32>,
+        sample_count: u32,
+        fxaa: Option<FxaaRenderPass>,
+    ) -> Self {
+        let size = PhysicalSize::new(size.width.max(1), size.height.max(1));
+        let color_texture = device.create_texture(&TextureDescriptor {
+            label: Some("Offscreen Texture"),
+            size: Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: config.target_texture_format(),
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let readback = device.create_buffer(&BufferDescriptor {
+            label: Some("Offscreen Readback Buffer"),
+            size: padded_bytes_per_row(size.width) as u64 * size.height as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Self {
+            color_texture,
+            buffers: SceneBuffers::new(
+                device,
+                config.target_texture_format(),
+                sample_count,
+                fxaa.as_ref(),
+                size,
+            ),
+            fxaa,
+            readback,
+            size,
+        }
+    }
+
+    pub fn render_target(&self) -> RenderTarget {
+        let color_view = self
+            .color_texture
+            .create_view(&TextureViewDescriptor::default());
+        self.buffers
+            .render_target(None, color_view, self.fxaa.as_ref(), self.size)
+    }
+
+    pub fn size(&self) -> PhysicalSize<u32> {
+        self.size
+    }
+
+    /// Copies the drawn frame to an image. Blocks until the GPU finishes all submitted work.
+    ///
+    /// The surface format is expected to have 8 bit channels, which holds for the sRGB formats the
+    /// window prefers.
+    pub fn read(&self, device: &Device, queue: &Queue) -> RgbaImage {
+        let bytes_per_row = padded_bytes_per_row(self.size.width);
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            TexelCopyTextureInfo {
+                texture: &self.color_texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            TexelCopyBufferInfo {
+                buffer: &self.readback,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(self.size.height),
+                },
+            },
+            self.color_texture.size(),
+        );
+        queue.submit([encoder.finish()]);
+
+        let slice = self.readback.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(MapMode::Read, move |result| sender.send(result).unwrap());
+        device.poll(PollType::Wait).unwrap();
+        receiver.recv().unwrap().unwrap();
+        let row_len = self.size.width as usize * 4;
+        let bgra = matches!(
+            self.color_texture.format(),
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        );
+        let mut pixels = Vec::with_capacity(row_len * self.size.height as usize);
+        for row in slice.get_mapped_range().chunks(bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..row_len]);
+        }
+        self.readback.unmap();
+        if bgra {
+            pixels.chunks_mut(4).for_each(|pixel| pixel.swap(0, 2));
+        }
+        RgbaImage::from_raw(self.size.width, self.size.height, pixels)
+            .expect("pixels match size of the image")
+    }
+}
+
+/// Bytes of a row of RGBA texels, padded to the alignment required by texture copies.
+fn padded_bytes_per_row(width: u32) -> u32 {
+    (width * 4).div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT
 }
 
-fn create_depth_texture(device: &Device, size: PhysicalSize<u32>) -> (Texture, TextureView) {
+fn create_depth_texture(
+    device: &Device,
+    size: PhysicalSize<u32>,
+    sample_count: u32,
+) -> (Texture, TextureView) {
     let size = Extent3d {
         width: size.width.max(1),
         height: size.height.max(1),
@@ -128,7 +533,7 @@ fn create_depth_texture(device: &Device, size: PhysicalSize<u32>) -> (Texture, T
         label: Some("Depth Texture"),
         size,
         mip_level_count: 1,
-        sample_count: 1,
+        sample_count,
         dimension: TextureDimension::D2,
         format: DEPTH_FORMAT,
         usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
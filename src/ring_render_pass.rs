@@ -0,0 +1,183 @@
+use wgpu::*;
+
+use crate::{
+    model::{MeshBuffers, ModelNormalBindGroupDescriptor, Vertex},
+    render_target::{RenderTarget, RenderTargetConfig, ScenePipeline},
+    scene::SceneModel,
+    texture::texture::{TextureBindGroupDescriptor, TextureBinding},
+};
+
+const TEXTURE_BINDINGS: [TextureBinding; 1] = [TextureBinding {
+    binding_view: 0,
+    binding_sampler: 1,
+}];
+
+/// Draws semi-transparent rings after all opaque models. Rings do not write depth, so they have to
+/// be drawn from the farthest to the nearest one.
+#[derive(Debug)]
+pub struct RingRenderPass {
+    render_pipeline: ScenePipeline,
+    camera_bind_group: BindGroup,
+    texture_bind_group_layout: BindGroupLayout,
+}
+
+impl RingRenderPass {
+    pub fn new(
+        device: &Device,
+        render_target: &RenderTargetConfig,
+        camera_buffer: &Buffer,
+        model_normal_matrix_layout: ModelNormalBindGroupDescriptor,
+    ) -> RingRenderPass {
+        let camera_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("ring camera layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("Ring Texture Bind Group Layout"),
+                entries: &TextureBindGroupDescriptor::layout_entries(&TEXTURE_BINDINGS),
+            });
+
+        let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("ring camera bind group"),
+            layout: &camera_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+        });
+
+        let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Ring Pipeline Layout"),
+            bind_group_layouts: &[
+                &camera_layout,
+                model_normal_matrix_layout.layout,
+                &texture_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Ring Shader"),
+            source: ShaderSource::Wgsl(include_str!("ring_shader.wgsl").into()),
+        });
+
+        let render_pipeline = ScenePipeline::new(render_target, |sample_count| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("Ring Pipeline"),
+                layout: Some(&render_pipeline_layout),
+                vertex: VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[Vertex::desc().clone()],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(ColorTargetState {
+                        format: render_target.target_texture_format(),
+                        blend: Some(BlendState::ALPHA_BLENDING),
+                        write_mask: ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: PrimitiveState {
+                    topology: PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: FrontFace::Ccw,
+                    // rings are visible from both sides
+                    cull_mode: None,
+                    polygon_mode: PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(DepthStencilState {
+                    format: render_target.depth_texture_format(),
+                    depth_write_enabled: false,
+                    depth_compare: render_target.depth_compare(),
+                    stencil: StencilState::default(),
+                    bias: DepthBiasState::default(),
+                }),
+                multisample: MultisampleState {
+                    count: sample_count,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        });
+
+        RingRenderPass {
+            render_pipeline,
+            camera_bind_group,
+            texture_bind_group_layout,
+        }
+    }
+
+    pub fn texture_layout(&self) -> TextureBindGroupDescriptor<'_> {
+        TextureBindGroupDescriptor {
+            layout: &self.texture_bind_group_layout,
+            bindings: &TEXTURE_BINDINGS,
+        }
+    }
+
+    /// Rings are expected to be sorted from the farthest to the nearest one.
+    pub fn record_draw_commands<'a>(
+        &self,
+        encoder: &mut CommandEncoder,
+        render_target: &RenderTarget,
+        rings: impl Iterator<Item = &'a SceneModel>,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Ring Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: render_target.scene_view(),
+                resolve_target: render_target.resolve_target(),
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: render_target.depth_texture_view(),
+                depth_ops: Some(Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_pipeline(self.render_pipeline.get(render_target));
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        for scene_model in rings {
+            render_pass.set_bind_group(1, &scene_model.model_bind_group, &[]);
+            for MeshBuffers {
+                texture_bind_group,
+                vertex_buffer,
+                index_buffer,
+                index_format,
+            } in scene_model.model.meshes()
+            {
+                render_pass.set_bind_group(2, texture_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, vertex_buffer);
+                render_pass.set_index_buffer(index_buffer, index_format);
+                render_pass.draw_indexed(0..index_buffer.size().get() as u32 / 2, 0, 0..1);
+            }
+        }
+    }
+}
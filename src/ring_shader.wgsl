@@ -0,0 +1,74 @@
+// Vertex shader
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+    inverse_view_proj: mat4x4<f32>,
+    // position of the Sun relative to the camera
+    light_position: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(1) @binding(0)
+var<uniform> model_mat: mat4x4<f32>;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    // centre of the parent body, whose shadow falls on the rings
+    @location(2) body_center: vec3<f32>,
+    @location(3) body_radius: f32,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+    var world_position = model_mat * vec4<f32>(model.position, 1.0);
+    out.clip_position = camera.view_proj * world_position;
+    out.position = world_position.xyz / world_position.w;
+    out.tex_coords = model.tex_coords;
+    // Ring mesh is generated in units of the body radius, so the model matrix scale is the
+    // radius of the body.
+    out.body_center = (model_mat * vec4<f32>(0.0, 0.0, 0.0, 1.0)).xyz;
+    out.body_radius = length((model_mat * vec4<f32>(1.0, 0.0, 0.0, 0.0)).xyz);
+    return out;
+}
+
+// Fragment shader
+
+// Rings in the shadow of the body are still faintly lit by the body itself
+const SHADOW_BRIGHTNESS: f32 = 0.05;
+
+@group(2) @binding(0)
+var tex_data: texture_2d<f32>;
+@group(2) @binding(1)
+var tex_sampler: sampler;
+
+fn in_body_shadow(position: vec3<f32>, light_dir: vec3<f32>, center: vec3<f32>, radius: f32) -> bool {
+    let oc = position - center;
+    let b = dot(oc, light_dir);
+    let c = dot(oc, oc) - radius * radius;
+    return b < 0.0 && b * b - c > 0.0;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    var light_dir = normalize(camera.light_position.xyz - in.position);
+    var lit = select(
+        1.0,
+        SHADOW_BRIGHTNESS,
+        in_body_shadow(in.position, light_dir, in.body_center, in.body_radius),
+    );
+
+    var texel = textureSample(tex_data, tex_sampler, in.tex_coords);
+    return vec4<f32>(texel.rgb * lit, texel.a);
+}
@@ -1,9 +1,10 @@
 use std::{
     sync::{Arc, Mutex},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use bytemuck::cast_slice;
+use bytemuck::{Zeroable, cast_slice};
+use cgmath::{InnerSpace, Point3, Vector2};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     *,
@@ -11,12 +12,31 @@ use wgpu::{
 use winit::dpi::PhysicalSize;
 
 use crate::{
-    camera::{camera::Camera, camera_control::CameraControl, projection::Projection},
+    atmosphere_render_pass::{
+        AtmosphereBindGroupDescriptor, AtmosphereRenderPass, AtmosphereUniform,
+    },
+    camera::{
+        camera::{Camera, CameraUniform},
+        camera_control::TargetFrame,
+        projection::Tile,
+    },
+    glyph_atlas::GlyphAtlas,
+    label_render_pass::LabelRenderPass,
+    labels::{LabelConfig, layout_labels},
     matrix::{Matrix3x3, Matrix4x4},
+    measurement::{Measurement, measure},
     model::{Model, ModelNormalBindGroupDescriptor},
     model_render_pass::ModelRenderPass,
+    picking::{Pick, pick_body},
     render_target::{RenderTarget, RenderTargetConfig},
-    solar_object::{render_solar_object::RenderSolarObject, solar_object::SolarObject},
+    ring_render_pass::RingRenderPass,
+    sky::Sky,
+    sky_render_pass::SkyRenderPass,
+    solar_object::{
+        render_solar_object::{RenderSolarObject, SolarObjectLayouts},
+        scale_model::ScaleModel,
+        solar_object::SolarObject,
+    },
 };
 
 #[derive(Debug)]
@@ -25,6 +45,8 @@ pub struct SceneModel {
     pub model_matrix_buffer: Buffer,
     pub model_bind_group: BindGroup,
     pub normal_matrix_buffer: Buffer,
+    /// Shadow of rings cast on this model. Models without rings use shared empty bind group.
+    pub ring_shadow_bind_group: Option<BindGroup>,
 }
 
 impl SceneModel {
@@ -45,7 +67,7 @@ impl SceneModel {
         });
         let model_matrix_bind_group = device.create_bind_group(&BindGroupDescriptor {
             label: Some("model bind group"),
-            layout: &model_normal_matrix_layout.layout,
+            layout: model_normal_matrix_layout.layout,
             entries: &[
                 BindGroupEntry {
                     binding: model_normal_matrix_layout.model_binding,
@@ -62,17 +84,74 @@ impl SceneModel {
             model_matrix_buffer,
             model_bind_group: model_matrix_bind_group,
             normal_matrix_buffer,
+            ring_shadow_bind_group: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SceneAtmosphere {
+    pub uniform_buffer: Buffer,
+    pub bind_group: BindGroup,
+}
+
+impl SceneAtmosphere {
+    pub fn new(device: &Device, atmosphere_layout: AtmosphereBindGroupDescriptor) -> Self {
+        let uniform_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("atmosphere buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            contents: cast_slice(&[AtmosphereUniform::zeroed()]),
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("atmosphere bind group"),
+            layout: atmosphere_layout.layout,
+            entries: &[BindGroupEntry {
+                binding: atmosphere_layout.atmosphere_binding,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        Self {
+            uniform_buffer,
+            bind_group,
+        }
+    }
+}
+
+/// Parts of the scene, which can be changed while running.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    pub scale_model: ScaleModel,
+    pub clouds: bool,
+    pub rings: bool,
+    pub atmospheres: bool,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            scale_model: ScaleModel::default(),
+            clouds: true,
+            rings: true,
+            atmospheres: true,
         }
     }
 }
 
 #[derive(Debug)]
 pub struct Scene {
-    init_time: Instant,
+    sky_render_pass: SkyRenderPass,
     model_render_pass: ModelRenderPass,
+    atmosphere_render_pass: AtmosphereRenderPass,
+    ring_render_pass: RingRenderPass,
+    label_render_pass: LabelRenderPass,
+    label_config: Arc<Mutex<LabelConfig>>,
     camera: Camera,
-    view_proj_buffer: Buffer,
+    camera_buffer: Buffer,
     solar_object: RenderSolarObject,
+    settings: RenderSettings,
+    viewport: PhysicalSize<u32>,
+    /// Part of the viewport drawn next, the whole viewport if missing.
+    tile: Option<Tile>,
 }
 
 impl Scene {
@@ -80,65 +159,212 @@ impl Scene {
         device: &Device,
         queue: &Queue,
         render_target: &RenderTargetConfig,
-        now: Instant,
-        camera_control: Arc<Mutex<CameraControl>>,
+        camera: Camera,
+        label_config: Arc<Mutex<LabelConfig>>,
         solar_object: SolarObject,
+        sky: Sky,
     ) -> Scene {
-        let mut camera = Camera::new(camera_control, Projection::default());
-        let view_proj_mat = camera.view_proj_matrix(now);
-        let view_proj_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("view-proj buffer"),
+        // the camera is written before every frame
+        let camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("camera buffer"),
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
-            contents: cast_slice(&[view_proj_mat]),
+            contents: cast_slice(&[CameraUniform::zeroed()]),
         });
 
-        let model_render_pass = ModelRenderPass::new(device, render_target, &view_proj_buffer);
+        let sky_render_pass = SkyRenderPass::new(device, queue, render_target, &camera_buffer, sky);
+        let model_render_pass = ModelRenderPass::new(device, queue, render_target, &camera_buffer);
+        let atmosphere_render_pass =
+            AtmosphereRenderPass::new(device, render_target, &camera_buffer);
 
         let texture_layout = model_render_pass.texture_layout();
         let model_normal_matrix_layout = model_render_pass.model_normal_matrix_layout();
 
+        let ring_render_pass = RingRenderPass::new(
+            device,
+            render_target,
+            &camera_buffer,
+            model_normal_matrix_layout,
+        );
+
         let solar_object = RenderSolarObject::new(
             solar_object,
             queue,
             device,
-            model_normal_matrix_layout,
-            texture_layout,
+            SolarObjectLayouts {
+                model_normal_matrix: model_normal_matrix_layout,
+                texture: texture_layout,
+                ring_texture: ring_render_pass.texture_layout(),
+                ring_shadow: model_render_pass.ring_shadow_layout(),
+                atmosphere: atmosphere_render_pass.atmosphere_layout(),
+            },
         );
 
+        let atlas = {
+            let label_config = label_config.lock().unwrap();
+            // printable ASCII and whatever else the names of bodies need
+            let names = solar_object
+                .bodies()
+                .into_iter()
+                .map(|body| body.name.as_str());
+            let chars = (' '..='~')
+                .chain(names.flat_map(str::chars))
+                .collect::<Vec<_>>();
+            GlyphAtlas::new(&label_config.font, label_config.font_size, chars)
+        };
+        let label_render_pass = LabelRenderPass::new(device, queue, render_target, atlas);
+
         Scene {
-            init_time: now,
+            sky_render_pass,
             model_render_pass,
+            atmosphere_render_pass,
+            ring_render_pass,
+            label_render_pass,
+            label_config,
             camera,
-            view_proj_buffer,
+            camera_buffer,
             solar_object,
+            settings: RenderSettings::default(),
+            viewport: render_target.size(),
+            tile: None,
         }
     }
 
     pub fn resize(&mut self, queue: &Queue, new_size: PhysicalSize<u32>, now: Instant) {
         self.camera.resize(new_size);
+        self.viewport = new_size;
+        self.sky_render_pass.resize(queue, new_size, self.tile);
+        self.label_render_pass.resize(queue, new_size, self.tile);
         queue.write_buffer(
-            &self.view_proj_buffer,
+            &self.camera_buffer,
             0,
-            cast_slice(&[self.camera.view_proj_matrix(now)]),
+            cast_slice(&[self.camera.uniform(now)]),
         );
     }
 
-    pub fn update_buffers(&mut self, queue: &Queue, now: Instant) {
+    /// Restricts drawing to `tile` of the viewport, or the whole viewport. Tiles are drawn into
+    /// targets of their own, which together give an image bigger than a single texture can hold.
+    pub fn set_tile(&mut self, queue: &Queue, tile: Option<Tile>, now: Instant) {
+        self.tile = tile;
+        self.camera.set_tile(tile);
+        self.resize(queue, self.viewport, now);
+    }
+
+    pub fn viewport(&self) -> PhysicalSize<u32> {
+        self.viewport
+    }
+
+    /// Updates buffers for simulation `time` and the camera at real time `now`.
+    pub fn update_buffers(&mut self, queue: &Queue, now: Instant, time: Duration) {
+        // bodies are moved first, so the camera can follow them in the same frame
+        self.solar_object
+            .update_positions(time, self.settings.scale_model);
+        {
+            let mut camera_control = self.camera.camera_control.lock().unwrap();
+            let surface_distance = self
+                .solar_object
+                .surface_distance(camera_control.position());
+            camera_control.set_surface_distance(surface_distance);
+            camera_control.update_target(now, |name| {
+                self.solar_object.find(name).map(|body| TargetFrame {
+                    position: body.world_position,
+                    radius: body.rendered_radius(),
+                    rotation: body.rotation(),
+                })
+            });
+        }
+
         queue.write_buffer(
-            &self.view_proj_buffer,
+            &self.camera_buffer,
             0,
-            cast_slice(&[self.camera.view_proj_matrix(now)]),
+            cast_slice(&[self.camera.uniform(now)]),
         );
 
-        self.solar_object
-            .update_buffers(now - self.init_time, queue);
+        let camera_position = self.camera.camera_control.lock().unwrap().position();
+        self.solar_object.update_buffers(queue, camera_position);
+
+        let labels = layout_labels(
+            &self.label_config.lock().unwrap(),
+            &self.solar_object,
+            camera_position,
+            self.camera.view_proj_matrix(now),
+            self.viewport,
+            |text| self.label_render_pass.measure(text),
+        );
+        self.label_render_pass.update(queue, &labels);
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut RenderSettings {
+        &mut self.settings
+    }
+
+    pub fn solar_object(&self) -> &RenderSolarObject {
+        &self.solar_object
+    }
+
+    /// Bodies of the scene. Their catalog values can be changed and take effect with the next
+    /// update of buffers.
+    pub fn solar_object_mut(&mut self) -> &mut RenderSolarObject {
+        &mut self.solar_object
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn camera_position(&self) -> Point3<f64> {
+        self.camera.camera_control.lock().unwrap().position()
+    }
+
+    /// Measures the `target` body seen from the `observer` body as of the last update of buffers.
+    pub fn measure(&self, observer: &str, target: &str) -> Option<Measurement> {
+        measure(&self.solar_object, observer, target)
+    }
+
+    /// Finds the body under the point of the screen in normalized device coordinates.
+    pub fn pick(&mut self, now: Instant, ndc: Vector2<f32>) -> Option<Pick> {
+        pick_body(&self.solar_object, self.camera.pick_ray(now, ndc))
     }
 
     pub fn record_draw_commands(&self, encoder: &mut CommandEncoder, render_target: &RenderTarget) {
+        self.sky_render_pass
+            .record_draw_commands(encoder, render_target);
         self.model_render_pass.record_draw_commands(
             encoder,
             render_target,
             self.solar_object.models().into_iter(),
+            self.solar_object
+                .layers()
+                .into_iter()
+                .filter(|_| self.settings.clouds),
+        );
+        let camera_position = self.camera.camera_control.lock().unwrap().position();
+        // hidden rings still cast their shadows
+        let mut rings = self.solar_object.rings();
+        rings.retain(|_| self.settings.rings);
+        rings.sort_by(|(a, _), (b, _)| {
+            (b - camera_position)
+                .magnitude2()
+                .total_cmp(&(a - camera_position).magnitude2())
+        });
+        self.ring_render_pass.record_draw_commands(
+            encoder,
+            render_target,
+            rings.into_iter().map(|(_, ring)| ring),
+        );
+        self.atmosphere_render_pass.record_draw_commands(
+            encoder,
+            render_target,
+            self.solar_object
+                .atmospheres()
+                .into_iter()
+                .filter(|_| self.settings.atmospheres),
         );
+        self.label_render_pass
+            .record_draw_commands(encoder, render_target);
+        render_target.post_process(encoder);
     }
 }
@@ -0,0 +1,189 @@
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    iter::once,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use image::{
+    RgbaImage,
+    imageops::{self, FilterType},
+};
+use serde::Deserialize;
+use wgpu::{CommandEncoderDescriptor, Device, Queue};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    camera::projection::Tile,
+    render_target::{OffscreenTarget, RenderTargetConfig},
+    scene::Scene,
+};
+
+/// Settings of screenshots.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ScreenshotConfig {
+    /// Screenshots are drawn at this many times the window resolution in each direction and
+    /// downsampled to it.
+    pub supersample: u32,
+    /// Directory screenshots are saved to.
+    pub directory: String,
+}
+
+impl Default for ScreenshotConfig {
+    fn default() -> Self {
+        Self {
+            supersample: 4,
+            directory: "screenshots".to_string(),
+        }
+    }
+}
+
+pub fn load_screenshot_config(path: &str) -> ScreenshotConfig {
+    let toml_str = fs::read_to_string(path).unwrap();
+    toml::from_str(&toml_str).unwrap()
+}
+
+/// Draws the scene at `supersample` times its viewport resolution and downsamples it to the
+/// viewport. Images bigger than the maximal texture size are drawn in tiles.
+///
+/// The scene is drawn for the same `now` and simulation `time` as the last frame, so the capture
+/// matches it.
+pub fn capture(
+    device: &Device,
+    queue: &Queue,
+    render_target: &RenderTargetConfig,
+    scene: &mut Scene,
+    supersample: u32,
+    now: Instant,
+    time: Duration,
+) -> RgbaImage {
+    let viewport = scene.viewport();
+    let supersample = supersample.max(1);
+    let max_size = device.limits().max_texture_dimension_2d;
+    let (width, height) = (viewport.width * supersample, viewport.height * supersample);
+    let (columns, rows) = (width.div_ceil(max_size), height.div_ceil(max_size));
+    // tiles are equal, so the image may be a few pixels bigger
+    let tile_size = PhysicalSize::new(width.div_ceil(columns), height.div_ceil(rows));
+    // supersampling smooths edges by itself, multisampling on top of it would only multiply memory
+    let target = if supersample > 1 {
+        OffscreenTarget::without_antialiasing(device, render_target, tile_size)
+    } else {
+        OffscreenTarget::new(device, render_target, tile_size)
+    };
+
+    let mut image = RgbaImage::new(tile_size.width * columns, tile_size.height * rows);
+    for row in 0..rows {
+        for column in 0..columns {
+            let tile = Tile {
+                columns,
+                rows,
+                column,
+                row,
+            };
+            scene.set_tile(queue, Some(tile), now);
+            scene.update_buffers(queue, now, time);
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Screenshot Encoder"),
+            });
+            scene.record_draw_commands(&mut encoder, &target.render_target());
+            queue.submit(once(encoder.finish()));
+            imageops::replace(
+                &mut image,
+                &target.read(device, queue),
+                (column * tile_size.width) as i64,
+                (row * tile_size.height) as i64,
+            );
+        }
+    }
+    scene.set_tile(queue, None, now);
+    imageops::resize(
+        &image,
+        viewport.width,
+        viewport.height,
+        FilterType::Triangle,
+    )
+}
+
+/// Saves `image` as PNG named by the current UTC time into `directory`. `metadata` is stored as
+/// text chunks of the PNG.
+pub fn save_screenshot(directory: &str, image: &RgbaImage, metadata: &[(&str, String)]) {
+    let path = PathBuf::from(directory).join(format!("screenshot-{}.png", utc_timestamp()));
+    match write_png(&path, image, metadata) {
+        Ok(()) => println!("Screenshot saved to {}", path.display()),
+        Err(e) => eprintln!("Failed to save screenshot {}: {e}", path.display()),
+    }
+}
+
+fn write_png(
+    path: &Path,
+    image: &RgbaImage,
+    metadata: &[(&str, String)],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        image.width(),
+        image.height(),
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, text) in metadata {
+        encoder.add_text_chunk(keyword.to_string(), text.clone())?;
+    }
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image.as_raw())?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Current UTC time formatted as `YYYYMMDD-HHMMSS`.
+fn utc_timestamp() -> String {
+    format_timestamp(
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs()),
+    )
+}
+
+/// Formats seconds since the Unix epoch as `YYYYMMDD-HHMMSS`.
+fn format_timestamp(seconds: u64) -> String {
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+    // civil date from days since the Unix epoch, shifted to eras starting on 1 March
+    let days = days as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(format_timestamp(0), "19700101-000000");
+        assert_eq!(format_timestamp(951_827_696), "20000229-123456");
+        assert_eq!(format_timestamp(1_709_251_199), "20240229-235959");
+        assert_eq!(format_timestamp(1_709_251_200), "20240301-000000");
+    }
+}
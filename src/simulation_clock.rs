@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+
+/// Slowest and fastest allowed speed of the simulation relative to real time.
+pub const SPEED_RANGE: (f64, f64) = (1.0 / 64.0, 1024.0);
+const SPEED_STEP: f64 = 2.0;
+
+/// Limits `speed` to [`SPEED_RANGE`].
+pub fn clamp_speed(speed: f64) -> f64 {
+    speed.clamp(SPEED_RANGE.0, SPEED_RANGE.1)
+}
+
+/// Simulation time, which can run faster or slower than real time, or be paused.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulationClock {
+    time: Duration,
+    speed: f64,
+    paused: bool,
+    last_update: Instant,
+}
+
+impl SimulationClock {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            time: Duration::ZERO,
+            speed: 1.0,
+            paused: false,
+            last_update: now,
+        }
+    }
+
+    /// Advances the simulation by time elapsed since the last update and returns the simulation
+    /// time.
+    pub fn advance(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last_update);
+        self.last_update = now;
+        if !self.paused {
+            self.time += elapsed.mul_f64(self.speed);
+        }
+        self.time
+    }
+
+    /// Makes `now` the last update without advancing the simulation. Time elapsed before it is
+    /// skipped.
+    pub fn sync(&mut self, now: Instant) {
+        self.last_update = now;
+    }
+
+    /// Simulation time as of the last update.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn faster(&mut self) {
+        self.set_speed(self.speed * SPEED_STEP);
+    }
+
+    pub fn slower(&mut self) {
+        self.set_speed(self.speed / SPEED_STEP);
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = clamp_speed(speed);
+    }
+
+    /// Jumps to simulation `time`.
+    pub fn set_time(&mut self, time: Duration) {
+        self.time = time;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+}
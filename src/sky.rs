@@ -0,0 +1,211 @@
+use std::{f64::consts::PI, fs};
+
+use image::DynamicImage;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use serde::Deserialize;
+
+/// Obliquity of the ecliptic at J2000 in degrees.
+const OBLIQUITY_DEG: f64 = 23.4393;
+
+/// Rotation from J2000 equatorial to galactic coordinates.
+const EQUATORIAL_TO_GALACTIC: [[f64; 3]; 3] = [
+    [-0.054_875_560_4, -0.873_437_090_2, -0.483_835_015_5],
+    [0.494_109_427_9, -0.444_829_630_0, 0.746_982_244_5],
+    [-0.867_666_149_0, -0.198_076_373_4, 0.455_983_776_2],
+];
+
+/// Faint stars are generated between these magnitudes, below the brightest catalog stars and
+/// above the naked eye limit.
+const FAINT_MAGNITUDE_RANGE: (f64, f64) = (3.0, 6.5);
+
+/// Background of the scene drawn behind all bodies.
+#[derive(Debug, Clone)]
+pub enum Sky {
+    Stars(Vec<Star>),
+    /// Texture in equatorial coordinates, right ascension 0h in the middle and growing to the
+    /// left.
+    Equirectangular(DynamicImage),
+    /// Faces +X, -X, +Y, -Y, +Z, -Z in the ecliptic frame of the scene.
+    Cubemap([DynamicImage; 6]),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Star {
+    /// Unit vector towards the star in the ecliptic frame of the scene. Y axis points to the
+    /// north ecliptic pole, X axis to the vernal equinox.
+    pub direction: [f32; 3],
+    pub magnitude: f32,
+    /// Linear RGB colour normalized to the brightest channel.
+    pub color: [f32; 3],
+}
+
+impl Star {
+    /// Creates star from J2000 equatorial coordinates and B-V colour index.
+    pub fn from_equatorial(
+        ra_hours: f64,
+        dec_degrees: f64,
+        magnitude: f64,
+        color_index: f64,
+    ) -> Self {
+        let ra = ra_hours * PI / 12.0;
+        let dec = dec_degrees.to_radians();
+        Self::from_equatorial_vector(
+            [dec.cos() * ra.cos(), dec.cos() * ra.sin(), dec.sin()],
+            magnitude,
+            color_index,
+        )
+    }
+
+    fn from_equatorial_vector(equatorial: [f64; 3], magnitude: f64, color_index: f64) -> Self {
+        let [x, y, z] = equatorial;
+        let (sin_e, cos_e) = OBLIQUITY_DEG.to_radians().sin_cos();
+        let ecliptic = [x, y * cos_e + z * sin_e, -y * sin_e + z * cos_e];
+        Self {
+            // ecliptic pole is the scene's up axis, bodies orbit from +X towards -Z
+            direction: [ecliptic[0], ecliptic[2], -ecliptic[1]].map(|c| c as f32),
+            magnitude: magnitude as f32,
+            color: color_index_to_rgb(color_index),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct SkyRaw {
+    catalog: String, // Path to CSV catalog, also used when the sky texture cannot be loaded
+    #[serde(default)]
+    faint_stars: usize,
+    #[serde(flatten)]
+    background: BackgroundRaw,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum BackgroundRaw {
+    Stars,
+    Equirectangular {
+        texture: String, // Path to image
+    },
+    Cubemap {
+        faces: [String; 6], // Paths to images
+    },
+}
+
+pub fn load_sky(path: &str) -> Sky {
+    let toml_str = fs::read_to_string(path).unwrap();
+    let raw: SkyRaw = toml::from_str(&toml_str).unwrap();
+    let textured = match &raw.background {
+        BackgroundRaw::Stars => None,
+        BackgroundRaw::Equirectangular { texture } => {
+            load_sky_texture(texture).map(Sky::Equirectangular)
+        }
+        BackgroundRaw::Cubemap { faces } => faces
+            .iter()
+            .map(|face| load_sky_texture(face))
+            .collect::<Option<Vec<_>>>()
+            .and_then(|faces| faces.try_into().ok())
+            .map(Sky::Cubemap),
+    };
+    textured.unwrap_or_else(|| Sky::Stars(load_stars(&raw.catalog, raw.faint_stars)))
+}
+
+/// Sky textures are not required. When they cannot be loaded, the starfield is drawn instead.
+fn load_sky_texture(path: &str) -> Option<DynamicImage> {
+    image::open(format!("resources/{path}"))
+        .inspect_err(|e| eprintln!("Failed to load sky texture {path}: {e}"))
+        .ok()
+}
+
+/// Loads stars from the catalog and adds `faint_stars` randomly generated stars along the Milky
+/// Way. The random stars are not real, but they are always the same.
+fn load_stars(path: &str, faint_stars: usize) -> Vec<Star> {
+    let csv = fs::read_to_string(path).unwrap();
+    let mut stars = csv
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        // header
+        .skip(1)
+        .map(|line| {
+            let columns = line.split(',').map(str::trim).collect::<Vec<_>>();
+            let [_name, ra_hours, dec_degrees, magnitude, color_index] = columns[..] else {
+                panic!("Invalid star catalog line: {line}");
+            };
+            let parse = |value: &str| {
+                value
+                    .parse::<f64>()
+                    .unwrap_or_else(|e| panic!("Invalid star catalog line {line}: {e}"))
+            };
+            Star::from_equatorial(
+                parse(ra_hours),
+                parse(dec_degrees),
+                parse(magnitude),
+                parse(color_index),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut rng = StdRng::seed_from_u64(0x5747);
+    let (brightest, faintest) = FAINT_MAGNITUDE_RANGE;
+    stars.extend((0..faint_stars).map(|_| {
+        // number of stars grows roughly 10^(0.5 m) with magnitude
+        let min = 10f64.powf(0.5 * (brightest - faintest));
+        let magnitude = faintest + 2.0 * rng.random_range(min..1.0f64).log10();
+        // half of the stars are concentrated around the galactic plane
+        let longitude = rng.random_range(0.0..2.0 * PI);
+        let sin_latitude = if rng.random_bool(0.5) {
+            rng.random_range(-1.0..1.0f64).powi(5)
+        } else {
+            rng.random_range(-1.0..1.0)
+        };
+        let cos_latitude = (1.0 - sin_latitude * sin_latitude).sqrt();
+        let galactic = [
+            cos_latitude * longitude.cos(),
+            cos_latitude * longitude.sin(),
+            sin_latitude,
+        ];
+        // inverse of the rotation is its transpose
+        let equatorial = [0, 1, 2].map(|i| {
+            (0..3)
+                .map(|j| EQUATORIAL_TO_GALACTIC[j][i] * galactic[j])
+                .sum()
+        });
+        Star::from_equatorial_vector(equatorial, magnitude, rng.random_range(-0.2..1.6))
+    }));
+    stars
+}
+
+/// Converts B-V colour index to colour of black body with the corresponding temperature.
+fn color_index_to_rgb(color_index: f64) -> [f32; 3] {
+    // Ballesteros' formula
+    let temperature =
+        4600.0 * (1.0 / (0.92 * color_index + 1.7) + 1.0 / (0.92 * color_index + 0.62));
+    // approximation of black body colour in sRGB, temperature in hundreds of kelvins
+    let t = temperature / 100.0;
+    let red = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_727_446 * (t - 60.0).powf(-0.133_204_759_2)
+    };
+    let green = if t <= 66.0 {
+        99.470_802_586_1 * t.ln() - 161.119_568_166_1
+    } else {
+        288.122_169_528_3 * (t - 60.0).powf(-0.075_514_849_2)
+    };
+    let blue = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_731_223_1 * (t - 10.0).ln() - 305.044_792_730_7
+    };
+    let srgb_to_linear = |c: f64| {
+        let c = (c / 255.0).clamp(0.0, 1.0);
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let [r, g, b] = [red, green, blue].map(srgb_to_linear);
+    let max = r.max(g).max(b);
+    [r, g, b].map(|c| (c / max) as f32)
+}
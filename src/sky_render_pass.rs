@@ -0,0 +1,379 @@
+use std::mem::size_of;
+
+use bytemuck::{Pod, Zeroable, cast_slice};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    *,
+};
+use winit::dpi::PhysicalSize;
+
+use crate::{
+    camera::projection::{NO_TILE, Tile},
+    render_target::{RenderTarget, RenderTargetConfig, ScenePipeline},
+    sky::{Sky, Star},
+    texture::{
+        sampler::{SamplerConfig, Wrap},
+        texture::RgbaTexture,
+    },
+};
+
+/// Brightness of stars. Star of magnitude 0 is this many times brighter than full white pixel.
+const STAR_EXPOSURE: f32 = 2.5;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct SkyUniform {
+    viewport: [f32; 4],
+    tile: [f32; 4],
+}
+
+impl SkyUniform {
+    fn new(size: PhysicalSize<u32>, tile: Option<Tile>) -> Self {
+        SkyUniform {
+            viewport: [size.width as f32, size.height as f32, STAR_EXPOSURE, 0.0],
+            tile: tile.map_or(NO_TILE, |tile| tile.clip_scale_offset()),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct StarInstance {
+    direction: [f32; 3],
+    magnitude: f32,
+    color: [f32; 3],
+}
+
+impl From<&Star> for StarInstance {
+    fn from(star: &Star) -> Self {
+        StarInstance {
+            direction: star.direction,
+            magnitude: star.magnitude,
+            color: star.color,
+        }
+    }
+}
+
+impl StarInstance {
+    const ATTRIBUTES: [VertexAttribute; 3] = [
+        VertexAttribute {
+            offset: 0,
+            shader_location: 0,
+            format: VertexFormat::Float32x3,
+        },
+        VertexAttribute {
+            offset: size_of::<[f32; 3]>() as BufferAddress,
+            shader_location: 1,
+            format: VertexFormat::Float32,
+        },
+        VertexAttribute {
+            offset: size_of::<[f32; 4]>() as BufferAddress,
+            shader_location: 2,
+            format: VertexFormat::Float32x3,
+        },
+    ];
+
+    fn desc() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: size_of::<StarInstance>() as BufferAddress,
+            step_mode: VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SkyBackground {
+    Texture {
+        render_pipeline: ScenePipeline,
+        texture_bind_group: BindGroup,
+        // kept alive for the bind group
+        #[allow(unused)]
+        texture: RgbaTexture,
+    },
+    Stars {
+        render_pipeline: ScenePipeline,
+        instance_buffer: Buffer,
+        star_count: u32,
+    },
+}
+
+/// Clears the frame and draws background of the scene. Runs before all other passes and does not
+/// use depth buffer.
+#[derive(Debug)]
+pub struct SkyRenderPass {
+    sky_buffer: Buffer,
+    uniform_bind_group: BindGroup,
+    background: SkyBackground,
+}
+
+impl SkyRenderPass {
+    pub fn new(
+        device: &Device,
+        queue: &Queue,
+        render_target: &RenderTargetConfig,
+        camera_buffer: &Buffer,
+        sky: Sky,
+    ) -> SkyRenderPass {
+        let uniform_bind_group_entry = |binding: u32| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::VERTEX_FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let uniform_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("sky uniform layout"),
+            entries: &[uniform_bind_group_entry(0), uniform_bind_group_entry(1)],
+        });
+
+        let sky_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("sky buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            contents: cast_slice(&[SkyUniform::new(render_target.size(), None)]),
+        });
+        let uniform_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("sky uniform bind group"),
+            layout: &uniform_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: sky_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Sky Shader"),
+            source: ShaderSource::Wgsl(include_str!("sky_shader.wgsl").into()),
+        });
+
+        let create_pipeline = |label: &str,
+                               bind_group_layouts: &[&BindGroupLayout],
+                               vertex_entry_point: &str,
+                               fragment_entry_point: &str,
+                               buffers: &[VertexBufferLayout],
+                               topology: PrimitiveTopology,
+                               blend: BlendState| {
+            let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+            ScenePipeline::new(render_target, |sample_count| {
+                device.create_render_pipeline(&RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&layout),
+                    vertex: VertexState {
+                        module: &shader,
+                        entry_point: Some(vertex_entry_point),
+                        buffers,
+                        compilation_options: Default::default(),
+                    },
+                    fragment: Some(FragmentState {
+                        module: &shader,
+                        entry_point: Some(fragment_entry_point),
+                        targets: &[Some(ColorTargetState {
+                            format: render_target.target_texture_format(),
+                            blend: Some(blend),
+                            write_mask: ColorWrites::ALL,
+                        })],
+                        compilation_options: Default::default(),
+                    }),
+                    primitive: PrimitiveState {
+                        topology,
+                        strip_index_format: None,
+                        front_face: FrontFace::Ccw,
+                        cull_mode: None,
+                        polygon_mode: PolygonMode::Fill,
+                        unclipped_depth: false,
+                        conservative: false,
+                    },
+                    depth_stencil: None,
+                    multisample: MultisampleState {
+                        count: sample_count,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                    cache: None,
+                })
+            })
+        };
+
+        let texture_layout = |view_binding: u32, view_dimension: TextureViewDimension| {
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("sky texture layout"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: view_binding,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            })
+        };
+        let texture_background = |texture: RgbaTexture,
+                                  view_binding: u32,
+                                  view_dimension: TextureViewDimension,
+                                  fragment_entry_point: &str| {
+            let layout = texture_layout(view_binding, view_dimension);
+            let texture_bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("sky texture bind group"),
+                layout: &layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: view_binding,
+                        resource: BindingResource::TextureView(&texture.view),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&texture.sampler),
+                    },
+                ],
+            });
+            SkyBackground::Texture {
+                render_pipeline: create_pipeline(
+                    "Sky Texture Pipeline",
+                    &[&uniform_layout, &layout],
+                    "vs_background",
+                    fragment_entry_point,
+                    &[],
+                    PrimitiveTopology::TriangleList,
+                    BlendState::REPLACE,
+                ),
+                texture_bind_group,
+                texture,
+            }
+        };
+
+        let background = match sky {
+            Sky::Equirectangular(image) => texture_background(
+                // equirectangular texture wraps around horizontally
+                RgbaTexture::from_image(
+                    device,
+                    queue,
+                    image,
+                    SamplerConfig {
+                        wrap_u: Wrap::Repeat,
+                        ..Default::default()
+                    },
+                ),
+                0,
+                TextureViewDimension::D2,
+                "fs_equirect",
+            ),
+            Sky::Cubemap(faces) => texture_background(
+                RgbaTexture::cube_from_images(device, queue, faces, SamplerConfig::default()),
+                2,
+                TextureViewDimension::Cube,
+                "fs_cubemap",
+            ),
+            Sky::Stars(stars) => {
+                let instances = stars.iter().map(StarInstance::from).collect::<Vec<_>>();
+                SkyBackground::Stars {
+                    render_pipeline: create_pipeline(
+                        "Star Pipeline",
+                        &[&uniform_layout],
+                        "vs_star",
+                        "fs_star",
+                        &[StarInstance::desc()],
+                        PrimitiveTopology::TriangleStrip,
+                        // light of overlapping stars adds up
+                        BlendState {
+                            color: BlendComponent {
+                                src_factor: BlendFactor::One,
+                                dst_factor: BlendFactor::One,
+                                operation: BlendOperation::Add,
+                            },
+                            alpha: BlendComponent::OVER,
+                        },
+                    ),
+                    instance_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                        label: Some("star buffer"),
+                        usage: BufferUsages::VERTEX,
+                        contents: cast_slice(&instances),
+                    }),
+                    star_count: instances.len() as u32,
+                }
+            }
+        };
+
+        SkyRenderPass {
+            sky_buffer,
+            uniform_bind_group,
+            background,
+        }
+    }
+
+    /// Updates the viewport of the sky and the `tile` of it drawn next.
+    pub fn resize(&self, queue: &Queue, new_size: PhysicalSize<u32>, tile: Option<Tile>) {
+        queue.write_buffer(
+            &self.sky_buffer,
+            0,
+            cast_slice(&[SkyUniform::new(new_size, tile)]),
+        );
+    }
+
+    pub fn record_draw_commands(&self, encoder: &mut CommandEncoder, render_target: &RenderTarget) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Sky Render Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: render_target.scene_view(),
+                resolve_target: render_target.resolve_target(),
+                ops: Operations {
+                    load: LoadOp::Clear(Color {
+                        r: 0.0,
+                        g: 0.0,
+                        b: 0.0,
+                        a: 1.0,
+                    }),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None,
+        });
+
+        render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        match &self.background {
+            SkyBackground::Texture {
+                render_pipeline,
+                texture_bind_group,
+                ..
+            } => {
+                render_pass.set_pipeline(render_pipeline.get(render_target));
+                render_pass.set_bind_group(1, texture_bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            }
+            SkyBackground::Stars {
+                render_pipeline,
+                instance_buffer,
+                star_count,
+            } => {
+                render_pass.set_pipeline(render_pipeline.get(render_target));
+                render_pass.set_vertex_buffer(0, instance_buffer.slice(..));
+                render_pass.draw(0..4, 0..*star_count);
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+// Background of the scene, either a texture or a starfield. It is drawn first and without depth,
+// so everything else is drawn over it.
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+    inverse_view_proj: mat4x4<f32>,
+    light_position: vec4<f32>,
+}
+
+struct Sky {
+    // viewport width and height in pixels, star exposure
+    viewport: vec4<f32>,
+    // scale and offset of clip space of the drawn tile relative to the whole view
+    tile: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(0) @binding(1)
+var<uniform> sky: Sky;
+
+@group(1) @binding(0)
+var equirect_tex: texture_2d<f32>;
+@group(1) @binding(1)
+var sky_sampler: sampler;
+@group(1) @binding(2)
+var cube_tex: texture_cube<f32>;
+
+const PI: f32 = 3.14159265;
+// obliquity of the ecliptic at J2000
+const OBLIQUITY: f32 = 0.40909;
+
+struct BackgroundOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+// Single triangle covering the whole screen.
+@vertex
+fn vs_background(@builtin(vertex_index) index: u32) -> BackgroundOutput {
+    let ndc = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u)) * 2.0 - 1.0;
+    var out: BackgroundOutput;
+    out.clip_position = vec4<f32>(ndc, 0.5, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+// Direction of the view ray through the fragment in the ecliptic frame of the scene.
+// Depth is reversed with no far plane, so the ray goes from the near plane (depth 1) to a point
+// twice as far (depth 0.5).
+fn view_direction(ndc: vec2<f32>) -> vec3<f32> {
+    let near = camera.inverse_view_proj * vec4<f32>(ndc, 1.0, 1.0);
+    let far = camera.inverse_view_proj * vec4<f32>(ndc, 0.5, 1.0);
+    return normalize(far.xyz / far.w - near.xyz / near.w);
+}
+
+// Orthographic projection has no view directions, so the sky fades out with it. The last element
+// of the projection is 0 for perspective and 1 for orthographic projection and is interpolated
+// between them during transitions.
+fn sky_visibility() -> f32 {
+    return 1.0 - camera.view_proj[3][3];
+}
+
+@fragment
+fn fs_equirect(in: BackgroundOutput) -> @location(0) vec4<f32> {
+    let dir = view_direction(in.ndc);
+    // scene frame to ecliptic coordinates with Z to the north ecliptic pole
+    let ecliptic = vec3<f32>(dir.x, -dir.z, dir.y);
+    let equatorial = vec3<f32>(
+        ecliptic.x,
+        ecliptic.y * cos(OBLIQUITY) - ecliptic.z * sin(OBLIQUITY),
+        ecliptic.y * sin(OBLIQUITY) + ecliptic.z * cos(OBLIQUITY),
+    );
+    let ra = atan2(equatorial.y, equatorial.x);
+    let dec = asin(clamp(equatorial.z, -1.0, 1.0));
+    let uv = vec2<f32>(0.5 - ra / (2.0 * PI), 0.5 - dec / PI);
+    // explicit level avoids derivative discontinuity at the seam
+    let color = textureSampleLevel(equirect_tex, sky_sampler, uv, 0.0).rgb;
+    return vec4<f32>(color * sky_visibility(), 1.0);
+}
+
+@fragment
+fn fs_cubemap(in: BackgroundOutput) -> @location(0) vec4<f32> {
+    let dir = view_direction(in.ndc);
+    let color = textureSampleLevel(cube_tex, sky_sampler, dir, 0.0).rgb;
+    return vec4<f32>(color * sky_visibility(), 1.0);
+}
+
+struct StarInput {
+    @location(0) direction: vec3<f32>,
+    @location(1) magnitude: f32,
+    @location(2) color: vec3<f32>,
+}
+
+struct StarOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+// Each star is a screen aligned quad drawn as triangle strip.
+@vertex
+fn vs_star(@builtin(vertex_index) index: u32, star: StarInput) -> StarOutput {
+    let corner = vec2<f32>(f32(index & 1u), f32((index >> 1u) & 1u)) * 2.0 - 1.0;
+    // flux relative to star of magnitude 0
+    let flux = pow(10.0, -0.4 * star.magnitude) * sky.viewport.z;
+    // bright stars grow, faint stars only fade
+    let radius_px = 1.5 + 1.5 * sqrt(flux);
+
+    // stars are infinitely far, so camera translation does not apply
+    var clip = camera.view_proj * vec4<f32>(star.direction, 0.0);
+    // stars behind the camera end up outside of the depth range and are clipped
+    clip.z = 0.5 * clip.w;
+    // the radius is in pixels of the whole view, tiles are magnified parts of it
+    let radius_clip = radius_px * 2.0 / sky.viewport.xy * sky.tile.xy;
+    clip = vec4<f32>(clip.xy + corner * radius_clip * clip.w, clip.zw);
+
+    var out: StarOutput;
+    out.clip_position = clip;
+    out.corner = corner;
+    out.color = star.color * min(flux, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_star(in: StarOutput) -> @location(0) vec4<f32> {
+    let falloff = exp(-4.0 * dot(in.corner, in.corner));
+    return vec4<f32>(in.color * falloff, 0.0);
+}
@@ -1,2 +1,4 @@
 pub mod render_solar_object;
+pub mod scale_model;
+#[allow(clippy::module_inception)]
 pub mod solar_object;
@@ -1,35 +1,105 @@
 use std::{f64::consts::PI, time::Duration};
 
 use bytemuck::cast_slice;
-use cgmath::Vector3;
-use image::DynamicImage;
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix3, Matrix4, Point3, Quaternion, Rad, SquareMatrix, Transform,
+    Vector3,
+};
+use image::{DynamicImage, Rgba, RgbaImage};
 use wgpu::*;
 
 use crate::{
+    atmosphere_render_pass::{AtmosphereBindGroupDescriptor, AtmosphereUniform},
     camera::camera_control::UP,
     matrix::{Matrix3x3, Matrix4x4},
-    model::{ModelNormalBindGroupDescriptor, sphere::create_sphere},
-    scene::SceneModel,
-    solar_object::solar_object::SolarObject,
-    texture::texture::{RgbaTexture, TextureBindGroupDescriptor},
+    model::{ModelNormalBindGroupDescriptor, ring::create_ring, sphere::create_sphere},
+    model_render_pass::{
+        BASE_COLOR_SLOT, NIGHT_SLOT, NORMAL_SLOT, RingShadowBindGroupDescriptor, SPECULAR_SLOT,
+        TEXTURE_SLOTS, create_ring_shadow_bind_group,
+    },
+    scene::{SceneAtmosphere, SceneModel},
+    solar_object::{
+        scale_model::ScaleModel,
+        solar_object::{Atmosphere, BodyKind, Clouds, Rings, SolarObject},
+    },
+    texture::{
+        sampler::SamplerConfig,
+        texture::{RgbaTexture, TextureBindGroupDescriptor, TextureImage},
+    },
 };
 
-/// This function makes things in solar system reasonably in vision range. Otherwise all bodies are
-/// so far they are not visible.
-pub fn distance_scaling(distance: f64) -> f32 {
-    (distance / 100000.0).powf(0.6) as f32
+pub fn time_scaling(time: f64) -> f64 {
+    time * 10.0
+}
+
+/// Real atmospheres are only a few kilometres thick and would not be visible at all. Their scale
+/// height is therefore exaggerated.
+const ATMOSPHERE_EXAGGERATION: f32 = 3.0;
+/// Height of the atmosphere shell in scale heights. Density above it is negligible.
+const ATMOSPHERE_SCALE_HEIGHTS: f32 = 6.0;
+
+/// Textures of a sphere model placed at their slots.
+fn slot_textures(
+    base_color: RgbaTexture,
+    night: RgbaTexture,
+    specular: RgbaTexture,
+    normal: RgbaTexture,
+) -> Vec<RgbaTexture> {
+    let mut textures: [Option<RgbaTexture>; TEXTURE_SLOTS] = Default::default();
+    textures[BASE_COLOR_SLOT] = Some(base_color);
+    textures[NIGHT_SLOT] = Some(night);
+    textures[SPECULAR_SLOT] = Some(specular);
+    textures[NORMAL_SLOT] = Some(normal);
+    textures
+        .map(|texture| texture.expect("Every slot has a texture"))
+        .into()
 }
 
-pub fn radius_scaling(radius: f64) -> f32 {
-    (radius / 10000.0).powf(0.4) as f32
+fn atmosphere_uniform(
+    atmosphere: &Atmosphere,
+    radius_km: f64,
+    radius: f64,
+    center: Vector3<f32>,
+) -> AtmosphereUniform {
+    let radius = radius as f32;
+    let scale_height =
+        (atmosphere.scale_height_km / radius_km) as f32 * radius * ATMOSPHERE_EXAGGERATION;
+    // Coefficients are converted to rendered units, so the optical depth of the atmosphere stays
+    // the same as the real one, no matter how much the atmosphere is exaggerated.
+    let km_per_unit = atmosphere.scale_height_km as f32 / scale_height;
+    AtmosphereUniform::new(
+        center.into(),
+        radius,
+        radius + scale_height * ATMOSPHERE_SCALE_HEIGHTS,
+        scale_height,
+        atmosphere.rayleigh.map(|coef| coef * km_per_unit),
+        atmosphere.mie * km_per_unit,
+        atmosphere.mie_asymmetry,
+        atmosphere.tint,
+    )
 }
 
-pub fn time_scaling(time: f64) -> f32 {
-    (time * 10.0) as f32
+/// Bind group layouts of all render passes, which draw solar objects.
+#[derive(Debug, Clone, Copy)]
+pub struct SolarObjectLayouts<'a> {
+    pub model_normal_matrix: ModelNormalBindGroupDescriptor<'a>,
+    pub texture: TextureBindGroupDescriptor<'a>,
+    pub ring_texture: TextureBindGroupDescriptor<'a>,
+    pub ring_shadow: RingShadowBindGroupDescriptor<'a>,
+    pub atmosphere: AtmosphereBindGroupDescriptor<'a>,
+}
+
+#[derive(Debug)]
+pub struct CloudLayer {
+    pub rotation_period_days: f64,
+    pub scene_model: SceneModel,
+    pub world_matrix: Matrix4<f64>,
 }
 
 #[derive(Debug)]
 pub struct RenderSolarObject {
+    pub name: String,
+    pub kind: BodyKind,
     pub radius_km: f64,
     pub distance_from_parent_km: f64,
     pub orbital_period_days: Option<f64>,
@@ -37,28 +107,60 @@ pub struct RenderSolarObject {
     pub tilt: f64,
     pub children: Vec<RenderSolarObject>,
     pub scene_model: SceneModel,
+    pub clouds: Option<CloudLayer>,
+    pub atmosphere: Option<(Atmosphere, SceneAtmosphere)>,
+    pub rings: Option<SceneModel>,
     pub inverse_normals: bool,
+    /// Model matrix of the body in the world as of the last position update.
+    pub world_matrix: Matrix4<f64>,
+    /// Model matrix of rings without the rotation of the body.
+    pub ring_world_matrix: Matrix4<f64>,
+    /// Centre of the body in the world as of the last position update.
+    pub world_position: Point3<f64>,
+    /// Real position of the centre in kilometres relative to the root body as of the last
+    /// position update. It does not depend on the scale model.
+    pub position_km: Point3<f64>,
+    /// Angle travelled on the orbit since time zero in radians, as of the last position update.
+    pub orbit_angle: f64,
+    /// Radius in rendered units as of the last position update.
+    radius: f64,
 }
 
 struct SolarObjectInner {
+    name: String,
+    kind: BodyKind,
     radius_km: f64,
     distance_from_parent_km: f64,
     orbital_period_days: Option<f64>,
     rotation_period_days: f64,
     tilt: f64,
-    texture_image: Option<DynamicImage>,
+    texture_image: Option<TextureImage>,
+    night_texture_image: Option<TextureImage>,
+    specular_texture_image: Option<TextureImage>,
+    normal_texture_image: Option<TextureImage>,
+    clouds: Option<Clouds>,
+    atmosphere: Option<Atmosphere>,
+    rings: Option<Rings>,
     children: Vec<SolarObjectInner>,
 }
 
 impl SolarObjectInner {
     pub fn new(solar_object: SolarObject) -> Self {
         Self {
+            name: solar_object.name,
+            kind: solar_object.kind,
             radius_km: solar_object.radius_km,
             distance_from_parent_km: solar_object.distance_from_parent_km,
             orbital_period_days: solar_object.orbital_period_days,
             rotation_period_days: solar_object.rotation_period_days,
             tilt: solar_object.tilt,
             texture_image: Some(solar_object.texture_image),
+            night_texture_image: solar_object.night_texture_image,
+            specular_texture_image: solar_object.specular_texture_image,
+            normal_texture_image: solar_object.normal_texture_image,
+            clouds: solar_object.clouds,
+            atmosphere: solar_object.atmosphere,
+            rings: solar_object.rings,
             children: solar_object
                 .children
                 .into_iter()
@@ -73,15 +175,13 @@ impl RenderSolarObject {
         solar_object: SolarObject,
         queue: &Queue,
         device: &Device,
-        model_normal_matrix_layout: ModelNormalBindGroupDescriptor,
-        texture_layout: TextureBindGroupDescriptor,
+        layouts: SolarObjectLayouts,
     ) -> Self {
         RenderSolarObject::new_inner(
             SolarObjectInner::new(solar_object),
             queue,
             device,
-            model_normal_matrix_layout,
-            texture_layout,
+            layouts,
             true,
         )
     }
@@ -90,19 +190,127 @@ impl RenderSolarObject {
         mut solar_object: SolarObjectInner,
         queue: &Queue,
         device: &Device,
-        model_normal_matrix_layout: ModelNormalBindGroupDescriptor,
-        texture_layout: TextureBindGroupDescriptor,
+        layouts: SolarObjectLayouts,
         inverse_normals: bool,
     ) -> Self {
-        let texture = RgbaTexture::from_image(
+        let color_texture = |texture: TextureImage| {
+            RgbaTexture::load(device, queue, texture).expect("Failed to load texture")
+        };
+        // Optional textures are not required to render the body. When they are missing or cannot
+        // be loaded, they are replaced by black ones, which have no effect.
+        let optional_texture = |texture: Option<TextureImage>| {
+            texture
+                .and_then(|texture| {
+                    RgbaTexture::load(device, queue, texture)
+                        .inspect_err(|e| eprintln!("Failed to load optional texture {e}"))
+                        .ok()
+                })
+                .unwrap_or_else(|| RgbaTexture::from_color(device, queue, [0, 0, 0, 255]))
+        };
+        // Missing normal texture is replaced by flat surface.
+        let normal_texture = |texture: Option<TextureImage>| {
+            texture
+                .and_then(|texture| {
+                    RgbaTexture::load_linear(device, queue, texture)
+                        .inspect_err(|e| eprintln!("Failed to load optional texture {e}"))
+                        .ok()
+                })
+                .unwrap_or_else(|| {
+                    RgbaTexture::from_image_linear(
+                        device,
+                        queue,
+                        DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+                            1,
+                            1,
+                            Rgba([128, 128, 255, 255]),
+                        )),
+                        SamplerConfig::default(),
+                    )
+                })
+        };
+        let textures = slot_textures(
+            color_texture(
+                solar_object
+                    .texture_image
+                    .take()
+                    .expect("Texture is present"),
+            ),
+            optional_texture(solar_object.night_texture_image.take()),
+            optional_texture(solar_object.specular_texture_image.take()),
+            normal_texture(solar_object.normal_texture_image.take()),
+        );
+        let mut scene_model = SceneModel::new(
             device,
-            queue,
-            solar_object
-                .texture_image
-                .take()
-                .expect("Texture is present"),
+            create_sphere(
+                device,
+                textures,
+                layouts.texture,
+                1.0,
+                64,
+                128,
+                Matrix4x4::identity(),
+            ),
+            layouts.model_normal_matrix,
         );
+        let rings = solar_object.rings.take().map(|rings| {
+            // Ring mesh is in units of the body radius, so it shares scaling with the body.
+            let inner_radius = (rings.inner_radius_km / solar_object.radius_km) as f32;
+            let outer_radius = (rings.outer_radius_km / solar_object.radius_km) as f32;
+            let texture = color_texture(rings.texture_image);
+            scene_model.ring_shadow_bind_group = Some(create_ring_shadow_bind_group(
+                device,
+                layouts.ring_shadow,
+                &texture,
+                inner_radius,
+                outer_radius,
+            ));
+            SceneModel::new(
+                device,
+                create_ring(
+                    device,
+                    vec![texture],
+                    layouts.ring_texture,
+                    inner_radius,
+                    outer_radius,
+                    256,
+                    Matrix4x4::identity(),
+                ),
+                layouts.model_normal_matrix,
+            )
+        });
+        // clouds are optional, the body is rendered without them, if their texture cannot be loaded
+        let clouds = solar_object.clouds.take().and_then(|clouds| {
+            let texture = RgbaTexture::load(device, queue, clouds.texture_image)
+                .inspect_err(|e| eprintln!("Failed to load optional texture {e}"))
+                .ok()?;
+            let textures = slot_textures(
+                texture,
+                optional_texture(None),
+                optional_texture(None),
+                normal_texture(None),
+            );
+            let altitude = (clouds.altitude_km / solar_object.radius_km) as f32;
+            Some(CloudLayer {
+                rotation_period_days: clouds.rotation_period_days,
+                world_matrix: Matrix4::identity(),
+                scene_model: SceneModel::new(
+                    device,
+                    create_sphere(
+                        device,
+                        textures,
+                        layouts.texture,
+                        1.0 + altitude * ATMOSPHERE_EXAGGERATION,
+                        64,
+                        128,
+                        Matrix4x4::identity(),
+                    ),
+                    layouts.model_normal_matrix,
+                ),
+            })
+        });
         Self {
+            name: solar_object.name,
+            kind: solar_object.kind,
             radius_km: solar_object.radius_km,
             distance_from_parent_km: solar_object.distance_from_parent_km,
             orbital_period_days: solar_object.orbital_period_days,
@@ -111,75 +319,166 @@ impl RenderSolarObject {
             children: solar_object
                 .children
                 .into_iter()
-                .map(|child| {
-                    RenderSolarObject::new_inner(
-                        child,
-                        queue,
-                        device,
-                        model_normal_matrix_layout,
-                        texture_layout,
-                        false,
-                    )
-                })
+                .map(|child| RenderSolarObject::new_inner(child, queue, device, layouts, false))
                 .collect(),
-            scene_model: SceneModel::new(
-                device,
-                create_sphere(
-                    device,
-                    texture,
-                    texture_layout,
-                    1.0,
-                    64,
-                    128,
-                    Matrix4x4::identity(),
-                ),
-                model_normal_matrix_layout,
-            ),
+            scene_model,
+            clouds,
+            atmosphere: solar_object
+                .atmosphere
+                .map(|atmosphere| (atmosphere, SceneAtmosphere::new(device, layouts.atmosphere))),
+            rings,
             inverse_normals,
+            world_matrix: Matrix4::identity(),
+            ring_world_matrix: Matrix4::identity(),
+            world_position: Point3::origin(),
+            position_km: Point3::origin(),
+            orbit_angle: 0.0,
+            radius: ScaleModel::default().radius(solar_object.radius_km),
+        }
+    }
+
+    /// Radius of the body in rendered units.
+    pub fn rendered_radius(&self) -> f64 {
+        self.radius
+    }
+
+    /// Orientation of the body including its tilt and spin, as of the last position update.
+    pub fn rotation(&self) -> Quaternion<f64> {
+        let axis = |column: Vector3<f64>| column.normalize();
+        Quaternion::from(Matrix3::from_cols(
+            axis(self.world_matrix.x.truncate()),
+            axis(self.world_matrix.y.truncate()),
+            axis(self.world_matrix.z.truncate()),
+        ))
+        .normalize()
+    }
+
+    /// Distance from `point` to the nearest surface of this body or any of its satellites.
+    /// Negative inside a body.
+    pub fn surface_distance(&self, point: Point3<f64>) -> f64 {
+        self.children.iter().fold(
+            (point - self.world_position).magnitude() - self.rendered_radius(),
+            |distance, child| distance.min(child.surface_distance(point)),
+        )
+    }
+
+    /// Finds body with `name` among this body and all its descendants.
+    pub fn find(&self, name: &str) -> Option<&RenderSolarObject> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(name))
+    }
+
+    /// Finds parent of the body with `name` among this body and all its descendants.
+    pub fn parent_of(&self, name: &str) -> Option<&RenderSolarObject> {
+        if self.children.iter().any(|child| child.name == name) {
+            return Some(self);
         }
+        self.children.iter().find_map(|child| child.parent_of(name))
     }
 
-    pub fn update_buffers(&self, time: Duration, queue: &Queue) {
-        self.update_buffers_inner(time, queue, Matrix4x4::identity(), None);
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut RenderSolarObject> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.children
+            .iter_mut()
+            .find_map(|child| child.find_mut(name))
+    }
+
+    /// Moves all bodies to their positions at `time` in `scale_model`. Buffers are not updated
+    /// until [`RenderSolarObject::update_buffers`] is called.
+    pub fn update_positions(&mut self, time: Duration, scale_model: ScaleModel) {
+        self.update_positions_inner(
+            time,
+            scale_model,
+            Matrix4::identity(),
+            None,
+            Point3::origin(),
+        );
     }
 
-    fn update_buffers_inner(
-        &self,
+    fn update_positions_inner(
+        &mut self,
         time: Duration,
-        queue: &Queue,
-        parent_matrix: Matrix4x4,
-        parent_radius: Option<f32>,
+        scale_model: ScaleModel,
+        parent_matrix: Matrix4<f64>,
+        parent_radius_km: Option<f64>,
+        parent_position_km: Point3<f64>,
     ) {
-        let scale = radius_scaling(self.radius_km);
-        let scale = Matrix4x4::scale(Vector3::new(scale, scale, scale));
-        let rotate = Matrix4x4::rotate(
-            UP,
-            time_scaling(PI * 2.0 * time.as_secs_f64() / self.rotation_period_days),
+        let up = UP.cast::<f64>().expect("f32 vector fits into f64");
+        self.radius = scale_model.radius(self.radius_km);
+        let scale = Matrix4::from_scale(self.radius);
+        let rotate = Matrix4::from_axis_angle(
+            up,
+            Rad(
+                time_scaling(PI * 2.0 * time.as_secs_f64() / self.rotation_period_days)
+                    % (2.0 * PI),
+            ),
         );
-        let tilt = Matrix4x4::rotate(Vector3::unit_x(), self.tilt as f32);
-        let translate = Matrix4x4::translate(Vector3 {
-            x: distance_scaling(self.distance_from_parent_km)
-                + parent_radius
-                    .map(|r| radius_scaling(r as f64) + radius_scaling(self.radius_km))
-                    .unwrap_or(0.0),
+        let tilt = Matrix4::from_axis_angle(Vector3::unit_x(), Rad(self.tilt));
+        let translate = Matrix4::from_translation(Vector3 {
+            x: parent_radius_km.map_or(0.0, |parent_radius_km| {
+                scale_model.orbit_radius(
+                    self.distance_from_parent_km,
+                    self.radius_km,
+                    parent_radius_km,
+                )
+            }),
             y: 0.0,
             z: 0.0,
         });
-        let orbit = if let Some(orbital_period_days) = self.orbital_period_days {
-            Matrix4x4::rotate(
-                UP,
-                time_scaling(PI * 2.0 * time.as_secs_f64() / orbital_period_days),
-            )
-        } else {
-            Matrix4x4::identity()
-        };
-        let model_matrix = parent_matrix
+        self.orbit_angle = self.orbital_period_days.map_or(0.0, |orbital_period_days| {
+            time_scaling(PI * 2.0 * time.as_secs_f64() / orbital_period_days) % (2.0 * PI)
+        });
+        let orbit = Matrix4::from_axis_angle(up, Rad(self.orbit_angle));
+        // orbits of children are in the orbital frame of their parent
+        self.position_km = parent_position_km
+            + (parent_matrix * orbit).transform_vector(Vector3::new(
+                self.distance_from_parent_km,
+                0.0,
+                0.0,
+            ));
+        self.world_matrix = parent_matrix
             * orbit
             * translate
             * tilt
             * rotate
             * scale
-            * *self.scene_model.model.model_matrix();
+            * Matrix4::from(*self.scene_model.model.model_matrix());
+        self.ring_world_matrix = parent_matrix * orbit * translate * tilt * scale;
+        if let Some(clouds) = &mut self.clouds {
+            let cloud_rotate = Matrix4::from_axis_angle(
+                up,
+                Rad(
+                    time_scaling(PI * 2.0 * time.as_secs_f64() / clouds.rotation_period_days)
+                        % (2.0 * PI),
+                ),
+            );
+            clouds.world_matrix = parent_matrix
+                * orbit
+                * translate
+                * tilt
+                * cloud_rotate
+                * scale
+                * Matrix4::from(*clouds.scene_model.model.model_matrix());
+        }
+        self.world_position = Point3::from_vec(self.world_matrix.w.truncate());
+        for child in &mut self.children {
+            child.update_positions_inner(
+                time,
+                scale_model,
+                parent_matrix * orbit * translate,
+                Some(self.radius_km),
+                self.position_km,
+            );
+        }
+    }
+
+    /// Updates buffers of all bodies. Model matrices are relative to `camera_position`.
+    pub fn update_buffers(&self, queue: &Queue, camera_position: Point3<f64>) {
+        let model_matrix = Matrix4x4::relative_to(self.world_matrix, camera_position);
         let mut normal_matrix = Matrix3x3::to_mat3_inverse_transpose(model_matrix);
         if self.inverse_normals {
             normal_matrix = Matrix3x3::scale(Vector3::new(-1.0, -1.0, -1.0)) * normal_matrix;
@@ -194,14 +493,58 @@ impl RenderSolarObject {
             0,
             cast_slice(&[normal_matrix.byte_aligned()]),
         );
-        for child in &self.children {
-            child.update_buffers_inner(
-                time,
-                queue,
-                parent_matrix * orbit * translate,
-                Some(self.radius_km as f32),
+        if let Some(rings) = &self.rings {
+            let ring_matrix = self.ring_world_matrix * Matrix4::from(*rings.model.model_matrix());
+            queue.write_buffer(
+                &rings.model_matrix_buffer,
+                0,
+                cast_slice(&[Matrix4x4::relative_to(ring_matrix, camera_position)]),
+            );
+        }
+        if let Some(clouds) = &self.clouds {
+            let cloud_matrix = Matrix4x4::relative_to(clouds.world_matrix, camera_position);
+            queue.write_buffer(
+                &clouds.scene_model.model_matrix_buffer,
+                0,
+                cast_slice(&[cloud_matrix]),
+            );
+            queue.write_buffer(
+                &clouds.scene_model.normal_matrix_buffer,
+                0,
+                cast_slice(&[Matrix3x3::to_mat3_inverse_transpose(cloud_matrix).byte_aligned()]),
+            );
+        }
+        if let Some((atmosphere, scene_atmosphere)) = &self.atmosphere {
+            queue.write_buffer(
+                &scene_atmosphere.uniform_buffer,
+                0,
+                cast_slice(&[atmosphere_uniform(
+                    atmosphere,
+                    self.radius_km,
+                    self.radius,
+                    (self.world_position - camera_position)
+                        .cast()
+                        .expect("f64 vector fits into f32"),
+                )]),
             );
         }
+        for child in &self.children {
+            child.update_buffers(queue, camera_position);
+        }
+    }
+
+    /// This body and all its descendants.
+    pub fn bodies(&self) -> Vec<&RenderSolarObject> {
+        let mut data = Vec::new();
+        self.collect_bodies(&mut data);
+        data
+    }
+
+    fn collect_bodies<'a>(&'a self, data: &mut Vec<&'a RenderSolarObject>) {
+        data.push(self);
+        for child in &self.children {
+            child.collect_bodies(data);
+        }
     }
 
     pub fn models(&self) -> Vec<&SceneModel> {
@@ -210,6 +553,56 @@ impl RenderSolarObject {
         models
     }
 
+    /// Returns semi-transparent layers drawn right above the bodies, such as clouds.
+    pub fn layers(&self) -> Vec<&SceneModel> {
+        let mut layers = Vec::new();
+        self.collect_layers(&mut layers);
+        layers
+    }
+
+    #[inline]
+    fn collect_layers<'a>(&'a self, data: &mut Vec<&'a SceneModel>) {
+        if let Some(clouds) = &self.clouds {
+            data.push(&clouds.scene_model);
+        }
+        for child in &self.children {
+            child.collect_layers(data);
+        }
+    }
+
+    /// Returns rings together with the position of their body.
+    pub fn rings(&self) -> Vec<(Point3<f64>, &SceneModel)> {
+        let mut rings = Vec::new();
+        self.collect_rings(&mut rings);
+        rings
+    }
+
+    #[inline]
+    fn collect_rings<'a>(&'a self, data: &mut Vec<(Point3<f64>, &'a SceneModel)>) {
+        if let Some(rings) = &self.rings {
+            data.push((self.world_position, rings));
+        }
+        for child in &self.children {
+            child.collect_rings(data);
+        }
+    }
+
+    pub fn atmospheres(&self) -> Vec<&SceneAtmosphere> {
+        let mut atmospheres = Vec::new();
+        self.collect_atmospheres(&mut atmospheres);
+        atmospheres
+    }
+
+    #[inline]
+    fn collect_atmospheres<'a>(&'a self, data: &mut Vec<&'a SceneAtmosphere>) {
+        if let Some((_, scene_atmosphere)) = &self.atmosphere {
+            data.push(scene_atmosphere);
+        }
+        for child in &self.children {
+            child.collect_atmospheres(data);
+        }
+    }
+
     #[inline]
     fn collect_models<'a>(&'a self, data: &mut Vec<&'a SceneModel>) {
         data.push(&self.scene_model);
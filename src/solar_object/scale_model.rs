@@ -0,0 +1,53 @@
+/// Kilometres in a rendered unit of the true scale model. Compressed model uses the same unit
+/// before compression.
+const KM_PER_UNIT: f64 = 100000.0;
+
+/// Mapping of real sizes and distances of bodies to rendered units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScaleModel {
+    /// Distances and radii are compressed, each by a different power. This makes things in solar
+    /// system reasonably in vision range. Otherwise all bodies are so far they are not visible.
+    #[default]
+    Compressed,
+    /// Distances and radii share the same linear scale.
+    True,
+}
+
+impl ScaleModel {
+    pub const ALL: [ScaleModel; 2] = [ScaleModel::Compressed, ScaleModel::True];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScaleModel::Compressed => "Compressed",
+            ScaleModel::True => "True scale",
+        }
+    }
+
+    /// Kilometres in a rendered unit, if the scale is linear.
+    pub fn km_per_unit(self) -> Option<f64> {
+        match self {
+            ScaleModel::Compressed => None,
+            ScaleModel::True => Some(KM_PER_UNIT),
+        }
+    }
+
+    pub fn radius(self, radius_km: f64) -> f64 {
+        match self {
+            ScaleModel::Compressed => (radius_km / 10000.0).powf(0.4),
+            ScaleModel::True => radius_km / KM_PER_UNIT,
+        }
+    }
+
+    /// Distance between centres of a body and its parent. Compressed distances are measured
+    /// between surfaces, so satellites stay outside of their compressed parents.
+    pub fn orbit_radius(self, distance_km: f64, radius_km: f64, parent_radius_km: f64) -> f64 {
+        match self {
+            ScaleModel::Compressed => {
+                (distance_km / KM_PER_UNIT).powf(0.6)
+                    + self.radius(parent_radius_km)
+                    + self.radius(radius_km)
+            }
+            ScaleModel::True => distance_km / KM_PER_UNIT,
+        }
+    }
+}
@@ -1,20 +1,200 @@
-use std::{collections::HashMap, f64::consts::PI, fs, hash::RandomState};
+use std::{collections::HashMap, f64::consts::PI, fs, hash::RandomState, path::PathBuf};
 
-use image::DynamicImage;
+use image::{DynamicImage, Rgba, RgbaImage};
 use serde::Deserialize;
 
+use crate::texture::{
+    sampler::{Filter, SamplerConfig, Wrap},
+    texture::{TextureImage, TextureSource},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyKind {
+    Star,
+    Planet,
+    DwarfPlanet,
+    Moon,
+}
+
 #[derive(Debug, Clone)]
 pub struct SolarObject {
     pub name: String,
+    pub kind: BodyKind,
     pub radius_km: f64,
     pub distance_from_parent_km: f64,
     pub orbital_period_days: Option<f64>,
     pub rotation_period_days: f64,
     pub tilt: f64,
-    pub texture_image: DynamicImage,
+    pub texture_image: TextureImage,
+    /// Light emitted from the unlit side, e.g. city lights.
+    pub night_texture_image: Option<TextureImage>,
+    /// Mask of surfaces with specular reflection, e.g. oceans.
+    pub specular_texture_image: Option<TextureImage>,
+    /// Tangent space normal map of the surface relief.
+    pub normal_texture_image: Option<TextureImage>,
+    pub clouds: Option<Clouds>,
+    pub atmosphere: Option<Atmosphere>,
+    pub rings: Option<Rings>,
     pub children: Vec<SolarObject>,
 }
 
+/// Cloud layer above the surface, which rotates independently of the body.
+#[derive(Debug, Clone)]
+pub struct Clouds {
+    pub altitude_km: f64,
+    pub rotation_period_days: f64,
+    pub texture_image: TextureImage,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rings {
+    pub inner_radius_km: f64,
+    pub outer_radius_km: f64,
+    /// Radial colour and alpha profile. Left edge is the inner edge of the rings.
+    pub texture_image: TextureImage,
+}
+
+/// Optional description of body's atmosphere. Scattering coefficients are given at the surface
+/// level per kilometre and fall off exponentially with altitude.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct Atmosphere {
+    pub scale_height_km: f64,
+    /// Rayleigh scattering coefficients for red, green and blue light.
+    pub rayleigh: [f32; 3],
+    /// Mie (aerosol and haze) scattering coefficient.
+    pub mie: f32,
+    /// Mie phase asymmetry. Values close to 1 scatter light mostly forward.
+    #[serde(default = "default_mie_asymmetry")]
+    pub mie_asymmetry: f32,
+    /// Colour multiplier of the scattered light.
+    #[serde(default = "default_tint")]
+    pub tint: [f32; 3],
+}
+
+/// Path to an image in `resources/`, either alone or with sampler settings of the texture.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum TextureRaw {
+    Path(String),
+    Configured {
+        path: String,
+        #[serde(flatten)]
+        sampler: SamplerRaw,
+    },
+}
+
+/// Sampler settings of a texture. Missing ones are taken from the defaults of the texture's use.
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct SamplerRaw {
+    filter: Option<Filter>,
+    anisotropy: Option<u16>,
+    mipmaps: Option<bool>,
+    wrap_u: Option<Wrap>,
+    wrap_v: Option<Wrap>,
+}
+
+impl TextureRaw {
+    fn path(&self) -> &str {
+        match self {
+            TextureRaw::Path(path) | TextureRaw::Configured { path, .. } => path,
+        }
+    }
+
+    fn sampler(&self, defaults: SamplerConfig) -> SamplerConfig {
+        match self {
+            TextureRaw::Path(_) => defaults,
+            TextureRaw::Configured { sampler, .. } => SamplerConfig {
+                filter: sampler.filter.unwrap_or(defaults.filter),
+                anisotropy: sampler.anisotropy.unwrap_or(defaults.anisotropy),
+                mipmaps: sampler.mipmaps.unwrap_or(defaults.mipmaps),
+                wrap_u: sampler.wrap_u.unwrap_or(defaults.wrap_u),
+                wrap_v: sampler.wrap_v.unwrap_or(defaults.wrap_v),
+            },
+        }
+    }
+}
+
+/// Body textures are equirectangular maps, which wrap around horizontally.
+fn body_sampler() -> SamplerConfig {
+    SamplerConfig {
+        wrap_u: Wrap::Repeat,
+        ..SamplerConfig::default()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct CloudsRaw {
+    altitude_km: f64,
+    rotation_period_hours: f64,
+    texture: TextureRaw,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct RingsRaw {
+    inner_radius_km: f64,
+    outer_radius_km: f64,
+    texture: Option<TextureRaw>, // Radial profile image
+    #[serde(default, rename = "band")]
+    bands: Vec<RingBandRaw>,
+}
+
+/// Part of rings with uniform colour. Used to generate radial profile when there is no texture.
+#[derive(Debug, Clone, Deserialize)]
+struct RingBandRaw {
+    inner_radius_km: f64,
+    outer_radius_km: f64,
+    color: [f32; 3],
+    opacity: f32,
+}
+
+/// Width of generated radial profile in texels.
+const RING_PROFILE_WIDTH: u32 = 1024;
+
+impl From<RingsRaw> for Rings {
+    fn from(raw: RingsRaw) -> Self {
+        let texture_image = match raw.texture {
+            Some(texture) => load_texture(&texture, SamplerConfig::default()),
+            None => {
+                let width = raw.outer_radius_km - raw.inner_radius_km;
+                let image = RgbaImage::from_fn(RING_PROFILE_WIDTH, 1, |x, _| {
+                    let radius =
+                        raw.inner_radius_km + width * (x as f64 + 0.5) / RING_PROFILE_WIDTH as f64;
+                    raw.bands
+                        .iter()
+                        .find(|band| (band.inner_radius_km..band.outer_radius_km).contains(&radius))
+                        .map(|band| {
+                            let [r, g, b] = band.color.map(|c| (c * 255.0) as u8);
+                            Rgba([r, g, b, (band.opacity * 255.0) as u8])
+                        })
+                        .unwrap_or(Rgba([0, 0, 0, 0]))
+                });
+                TextureImage {
+                    source: TextureSource::Image(DynamicImage::ImageRgba8(image)),
+                    sampler: SamplerConfig::default(),
+                }
+            }
+        };
+        Self {
+            inner_radius_km: raw.inner_radius_km,
+            outer_radius_km: raw.outer_radius_km,
+            texture_image,
+        }
+    }
+}
+
+fn default_bump_scale() -> f32 {
+    4.0
+}
+
+fn default_mie_asymmetry() -> f32 {
+    0.76
+}
+
+fn default_tint() -> [f32; 3] {
+    [1.0, 1.0, 1.0]
+}
+
 #[derive(Debug, Clone, Deserialize)]
 struct SolarObjectListRaw {
     #[serde(rename = "Body")]
@@ -24,23 +204,40 @@ struct SolarObjectListRaw {
 #[derive(Debug, Clone, Deserialize)]
 struct SolarObjectRaw {
     name: String,
+    #[serde(rename = "type")]
+    kind: BodyKind,
     parent: Option<String>,
     radius_km: f64,
     avg_distance_km: Option<f64>,
     orbital_period_days: Option<f64>,
     rotation_period_hours: f64,
     tilt: f64,
-    texture: String, // Path to image
+    texture: TextureRaw,
+    night_texture: Option<TextureRaw>,
+    specular_texture: Option<TextureRaw>,
+    normal_texture: Option<TextureRaw>,
+    // Normal map is generated from height map, if there is no normal map.
+    height_texture: Option<TextureRaw>,
+    #[serde(default = "default_bump_scale")]
+    bump_scale: f32,
+    clouds: Option<CloudsRaw>,
+    atmosphere: Option<Atmosphere>,
+    rings: Option<RingsRaw>,
 }
 
 fn load_recursive(parent: &mut SolarObject, map: &mut HashMap<String, SolarObjectRaw>) {
-    let names = map
+    let mut names = map
         .iter()
         .filter(|(_, raw)| {
             raw.parent.as_ref().expect("Only the sun is without parent") == &parent.name
         })
         .map(|(name, _)| name.clone())
         .collect::<Vec<_>>();
+    // children are ordered from the closest one, so planets keep their usual order
+    names.sort_by(|a, b| {
+        let distance = |name: &String| map[name].avg_distance_km.unwrap_or(0.0);
+        distance(a).total_cmp(&distance(b))
+    });
     for name in names {
         let body = map.remove(&name).expect("It exists");
         parent.children.push(body.clone().into());
@@ -65,18 +262,80 @@ pub fn load_solar_objects(path: &str) -> SolarObject {
     sun
 }
 
+/// Textures are read only when they are created, a precompressed container of the image may be
+/// used then.
+fn load_texture(raw: &TextureRaw, sampler: SamplerConfig) -> TextureImage {
+    TextureImage {
+        source: TextureSource::File(PathBuf::from("resources").join(raw.path())),
+        sampler: raw.sampler(sampler),
+    }
+}
+
+/// Height maps are converted to normal maps while loading. When they cannot be loaded, the body is
+/// rendered without relief.
+fn load_height_map(path: &str) -> Option<DynamicImage> {
+    image::open(format!("resources/{path}"))
+        .inspect_err(|e| eprintln!("Failed to load optional texture {path}: {e}"))
+        .ok()
+}
+
+/// Converts height map into tangent space normal map. Steepness of the relief is multiplied by
+/// `bump_scale`. The map wraps around horizontally, as it covers the whole body.
+fn height_to_normal_map(height: &DynamicImage, bump_scale: f32) -> DynamicImage {
+    let height = height.to_luma32f();
+    let (width, rows) = height.dimensions();
+    let sample = |x: i64, y: i64| {
+        height
+            .get_pixel(
+                x.rem_euclid(width as i64) as u32,
+                y.clamp(0, rows as i64 - 1) as u32,
+            )
+            .0[0]
+    };
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, rows, |x, y| {
+        let (x, y) = (x as i64, y as i64);
+        let dx = (sample(x + 1, y) - sample(x - 1, y)) * bump_scale;
+        // image rows grow downwards, while normal map Y axis points up
+        let dy = (sample(x, y - 1) - sample(x, y + 1)) * bump_scale;
+        let length = (dx * dx + dy * dy + 1.0).sqrt();
+        let [r, g, b] =
+            [-dx / length, -dy / length, 1.0 / length].map(|c| ((c * 0.5 + 0.5) * 255.0) as u8);
+        Rgba([r, g, b, 255])
+    }))
+}
+
 impl From<SolarObjectRaw> for SolarObject {
     fn from(raw: SolarObjectRaw) -> Self {
-        let texture_image =
-            image::open(format!("resources/{}", raw.texture)).expect("Failed to load texture");
+        let body_texture = |raw: &TextureRaw| load_texture(raw, body_sampler());
+        let texture_image = body_texture(&raw.texture);
         Self {
             name: raw.name,
+            kind: raw.kind,
             radius_km: raw.radius_km,
             distance_from_parent_km: raw.avg_distance_km.unwrap_or(0.0),
             orbital_period_days: raw.orbital_period_days,
             rotation_period_days: raw.rotation_period_hours / 24.0,
             tilt: raw.tilt * PI / 180.0,
             texture_image,
+            night_texture_image: raw.night_texture.as_ref().map(body_texture),
+            specular_texture_image: raw.specular_texture.as_ref().map(body_texture),
+            normal_texture_image: raw.normal_texture.as_ref().map(body_texture).or_else(|| {
+                let height = raw.height_texture.as_ref()?;
+                Some(TextureImage {
+                    source: TextureSource::Image(height_to_normal_map(
+                        &load_height_map(height.path())?,
+                        raw.bump_scale,
+                    )),
+                    sampler: height.sampler(body_sampler()),
+                })
+            }),
+            clouds: raw.clouds.map(|clouds| Clouds {
+                altitude_km: clouds.altitude_km,
+                rotation_period_days: clouds.rotation_period_hours / 24.0,
+                texture_image: body_texture(&clouds.texture),
+            }),
+            atmosphere: raw.atmosphere,
+            rings: raw.rings.map(Rings::from),
             children: Vec::new(),
         }
     }
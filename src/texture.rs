@@ -1 +1,6 @@
+pub mod bc7;
+pub mod container;
+pub mod convert;
+pub mod sampler;
+#[allow(clippy::module_inception)]
 pub mod texture;
@@ -0,0 +1,601 @@
+use image::{Rgba, RgbaImage};
+
+/// Interpolation weights of 2, 3 and 4-bit indices out of 64.
+const WEIGHTS_2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS_3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS_4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Compresses `image` into BC7 blocks in row-major order. Partial blocks at the edges repeat the
+/// edge texels.
+///
+/// Every block is encoded in mode 6, a single RGBA line with 4-bit indices. Endpoints are the
+/// extremes of texels projected on the principal axis of the block, which is fast and good enough
+/// for photographic planet maps, though not as precise as searching all modes.
+pub fn encode_bc7(image: &RgbaImage) -> Vec<u8> {
+    let (width, height) = image.dimensions();
+    let mut blocks = Vec::with_capacity((width.div_ceil(4) * height.div_ceil(4) * 16) as usize);
+    for block_y in 0..height.div_ceil(4) {
+        for block_x in 0..width.div_ceil(4) {
+            let texels: [[f32; 4]; 16] = std::array::from_fn(|i| {
+                let x = (block_x * 4 + i as u32 % 4).min(width - 1);
+                let y = (block_y * 4 + i as u32 / 4).min(height - 1);
+                image.get_pixel(x, y).0.map(f32::from)
+            });
+            blocks.extend_from_slice(&encode_block(&texels));
+        }
+    }
+    blocks
+}
+
+fn encode_block(texels: &[[f32; 4]; 16]) -> [u8; 16] {
+    let (low, high) = principal_extremes(texels);
+    let mut endpoints = [quantize(low), quantize(high)];
+    let colors = endpoints.map(|(values, p_bit)| values.map(|value| (value << 1 | p_bit) as f32));
+    let palette: [[f32; 4]; 16] = std::array::from_fn(|i| {
+        std::array::from_fn(|c| {
+            let value =
+                (64 - WEIGHTS_4[i]) * colors[0][c] as u32 + WEIGHTS_4[i] * colors[1][c] as u32;
+            ((value + 32) >> 6) as f32
+        })
+    });
+    let mut indices = texels.map(|texel| {
+        (0..16)
+            .min_by(|a, b| {
+                distance2(&texel, &palette[*a]).total_cmp(&distance2(&texel, &palette[*b]))
+            })
+            .expect("Palette is not empty")
+    });
+    // the most significant bit of the first index is implied zero
+    if indices[0] >= 8 {
+        endpoints.swap(0, 1);
+        indices = indices.map(|index| 15 - index);
+    }
+
+    let mut bits = BitWriter::default();
+    // mode 6 is encoded as six zero bits followed by one
+    bits.write(1 << 6, 7);
+    for channel in 0..4 {
+        for (values, _) in endpoints {
+            bits.write(values[channel], 7);
+        }
+    }
+    for (_, p_bit) in endpoints {
+        bits.write(p_bit, 1);
+    }
+    for (i, index) in indices.into_iter().enumerate() {
+        bits.write(index as u8, if i == 0 { 3 } else { 4 });
+    }
+    bits.0.to_le_bytes()
+}
+
+/// Extremes of `texels` along their principal axis.
+fn principal_extremes(texels: &[[f32; 4]; 16]) -> ([f32; 4], [f32; 4]) {
+    let mean: [f32; 4] =
+        std::array::from_fn(|c| texels.iter().map(|texel| texel[c]).sum::<f32>() / 16.0);
+    let centered = texels.map(|texel| std::array::from_fn::<f32, 4, _>(|c| texel[c] - mean[c]));
+    let covariance: [[f32; 4]; 4] = std::array::from_fn(|a| {
+        std::array::from_fn(|b| centered.iter().map(|texel| texel[a] * texel[b]).sum())
+    });
+    // power iteration starting from the axis of the largest variance
+    let largest = (0..4)
+        .max_by(|a, b| covariance[*a][*a].total_cmp(&covariance[*b][*b]))
+        .expect("There are four channels");
+    let mut axis = covariance[largest];
+    for _ in 0..8 {
+        let next: [f32; 4] =
+            std::array::from_fn(|a| (0..4).map(|b| covariance[a][b] * axis[b]).sum());
+        let length = next.iter().map(|c| c * c).sum::<f32>().sqrt();
+        if length < f32::EPSILON {
+            // uniform block
+            return (mean, mean);
+        }
+        axis = next.map(|c| c / length);
+    }
+    let projections = centered.map(|texel| (0..4).map(|c| texel[c] * axis[c]).sum::<f32>());
+    let min = projections.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = projections
+        .iter()
+        .copied()
+        .fold(f32::NEG_INFINITY, f32::max);
+    let point = |t: f32| std::array::from_fn(|c| (mean[c] + axis[c] * t).clamp(0.0, 255.0));
+    (point(min), point(max))
+}
+
+/// Quantizes an endpoint into 7-bit channels and a shared lowest bit, which is chosen by the
+/// smaller error.
+fn quantize(endpoint: [f32; 4]) -> ([u8; 4], u8) {
+    (0..2)
+        .map(|p_bit| {
+            let values =
+                endpoint.map(|c| ((c - p_bit as f32) / 2.0).round().clamp(0.0, 127.0) as u8);
+            (values, p_bit)
+        })
+        .min_by(|(a, a_bit), (b, b_bit)| {
+            let error = |values: &[u8; 4], p_bit: u8| {
+                distance2(&endpoint, &values.map(|value| (value << 1 | p_bit) as f32))
+            };
+            error(a, *a_bit).total_cmp(&error(b, *b_bit))
+        })
+        .expect("There are two choices")
+}
+
+fn distance2(a: &[f32; 4], b: &[f32; 4]) -> f32 {
+    (0..4).map(|c| (a[c] - b[c]) * (a[c] - b[c])).sum()
+}
+
+/// Bits of a block written from the least significant one.
+#[derive(Debug, Default)]
+struct BitWriter(u128, u32);
+
+impl BitWriter {
+    fn write(&mut self, value: u8, bits: u32) {
+        self.0 |= u128::from(value) << self.1;
+        self.1 += bits;
+    }
+}
+
+/// Decodes BC7 `blocks` in row-major order into an image of `width` and `height`, which cuts off
+/// partial blocks at the edges. Reserved blocks decode to transparent black.
+pub fn decode_bc7(blocks: &[u8], width: u32, height: u32) -> RgbaImage {
+    let blocks_per_row = width.div_ceil(4) as usize;
+    let mut image = RgbaImage::new(width, height);
+    for (i, block) in blocks
+        .chunks_exact(16)
+        .take(blocks_per_row * height.div_ceil(4) as usize)
+        .enumerate()
+    {
+        let texels = decode_block(block.try_into().expect("Blocks have 16 bytes"));
+        let (block_x, block_y) = (
+            (i % blocks_per_row) as u32 * 4,
+            (i / blocks_per_row) as u32 * 4,
+        );
+        for (j, texel) in texels.into_iter().enumerate() {
+            let (x, y) = (block_x + j as u32 % 4, block_y + j as u32 / 4);
+            if x < width && y < height {
+                image.put_pixel(x, y, Rgba(texel));
+            }
+        }
+    }
+    image
+}
+
+/// Layout of a block in one of the BC7 modes.
+#[derive(Debug)]
+struct Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    /// Each endpoint has its own lowest bit.
+    endpoint_p_bits: bool,
+    /// Both endpoints of a subset share their lowest bit.
+    shared_p_bits: bool,
+    index_bits: u32,
+    /// Alpha has separate indices, or colour, if the index selection bit is set.
+    index2_bits: u32,
+}
+
+const MODES: [Mode; 8] = [
+    Mode {
+        subsets: 3,
+        partition_bits: 4,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 4,
+        alpha_bits: 0,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 3,
+        index2_bits: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 6,
+        alpha_bits: 0,
+        endpoint_p_bits: false,
+        shared_p_bits: true,
+        index_bits: 3,
+        index2_bits: 0,
+    },
+    Mode {
+        subsets: 3,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 0,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        index2_bits: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 0,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 2,
+        index2_bits: 0,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 1,
+        color_bits: 5,
+        alpha_bits: 6,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        index2_bits: 3,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 2,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 8,
+        endpoint_p_bits: false,
+        shared_p_bits: false,
+        index_bits: 2,
+        index2_bits: 2,
+    },
+    Mode {
+        subsets: 1,
+        partition_bits: 0,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 7,
+        alpha_bits: 7,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 4,
+        index2_bits: 0,
+    },
+    Mode {
+        subsets: 2,
+        partition_bits: 6,
+        rotation_bits: 0,
+        index_selection_bits: 0,
+        color_bits: 5,
+        alpha_bits: 5,
+        endpoint_p_bits: true,
+        shared_p_bits: false,
+        index_bits: 2,
+        index2_bits: 0,
+    },
+];
+
+fn decode_block(block: &[u8; 16]) -> [[u8; 4]; 16] {
+    let mut bits = BitReader(u128::from_le_bytes(*block), 0);
+    // mode is given by the position of the lowest set bit
+    let Some(mode) = MODES.get(bits.0.trailing_zeros() as usize) else {
+        return [[0; 4]; 16];
+    };
+    bits.1 = bits.0.trailing_zeros() + 1;
+    let partition = bits.read(mode.partition_bits) as usize;
+    let rotation = bits.read(mode.rotation_bits);
+    let index_selection = bits.read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u8; 4]; 6];
+    for channel in 0..4 {
+        let channel_bits = if channel < 3 {
+            mode.color_bits
+        } else {
+            mode.alpha_bits
+        };
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[channel] = bits.read(channel_bits) as u8;
+        }
+    }
+    let p_bits: [u8; 6] = if mode.endpoint_p_bits {
+        std::array::from_fn(|i| {
+            if i < endpoint_count {
+                bits.read(1) as u8
+            } else {
+                0
+            }
+        })
+    } else if mode.shared_p_bits {
+        let shared: [u8; 3] = std::array::from_fn(|i| {
+            if i < mode.subsets {
+                bits.read(1) as u8
+            } else {
+                0
+            }
+        });
+        std::array::from_fn(|i| shared[i / 2])
+    } else {
+        [0; 6]
+    };
+    let has_p_bits = mode.endpoint_p_bits || mode.shared_p_bits;
+    for (endpoint, p_bit) in endpoints.iter_mut().zip(p_bits) {
+        for (channel, value) in endpoint.iter_mut().enumerate() {
+            let mut value_bits = if channel < 3 {
+                mode.color_bits
+            } else {
+                mode.alpha_bits
+            };
+            if value_bits == 0 {
+                *value = 255;
+                continue;
+            }
+            if has_p_bits {
+                *value = *value << 1 | p_bit;
+                value_bits += 1;
+            }
+            // the highest bits are repeated below the value to span the full range
+            if value_bits < 8 {
+                *value = *value << (8 - value_bits) | *value >> (2 * value_bits - 8);
+            }
+        }
+    }
+
+    let subset = |texel: usize| match mode.subsets {
+        1 => 0,
+        2 => (PARTITIONS_2[partition] >> texel & 1) as usize,
+        _ => PARTITIONS_3[partition][texel] as usize,
+    };
+    // the most significant bit of the first index of each subset is implied zero
+    let is_anchor = |texel: usize| match subset(texel) {
+        0 => texel == 0,
+        1 if mode.subsets == 2 => texel == ANCHORS_2[partition] as usize,
+        1 => texel == ANCHORS_3_SECOND[partition] as usize,
+        _ => texel == ANCHORS_3_THIRD[partition] as usize,
+    };
+    let indices: [u32; 16] =
+        std::array::from_fn(|texel| bits.read(mode.index_bits - is_anchor(texel) as u32));
+    let indices2: [u32; 16] = std::array::from_fn(|texel| {
+        if mode.index2_bits == 0 {
+            0
+        } else {
+            bits.read(mode.index2_bits - (texel == 0) as u32)
+        }
+    });
+    let (color_indices, alpha_indices) = match (mode.index2_bits, index_selection) {
+        (0, _) => ((indices, mode.index_bits), (indices, mode.index_bits)),
+        (_, 0) => ((indices, mode.index_bits), (indices2, mode.index2_bits)),
+        _ => ((indices2, mode.index2_bits), (indices, mode.index_bits)),
+    };
+
+    std::array::from_fn(|texel| {
+        let low = endpoints[subset(texel) * 2];
+        let high = endpoints[subset(texel) * 2 + 1];
+        let mut color: [u8; 4] = std::array::from_fn(|channel| {
+            let (indices, index_bits) = if channel < 3 {
+                color_indices
+            } else {
+                alpha_indices
+            };
+            let weight = match index_bits {
+                2 => WEIGHTS_2[indices[texel] as usize],
+                3 => WEIGHTS_3[indices[texel] as usize],
+                _ => WEIGHTS_4[indices[texel] as usize],
+            };
+            (((64 - weight) * low[channel] as u32 + weight * high[channel] as u32 + 32) >> 6) as u8
+        });
+        // rotation swaps alpha with one of the colour channels
+        if rotation > 0 {
+            color.swap(rotation as usize - 1, 3);
+        }
+        color
+    })
+}
+
+/// Bits of a block read from the least significant one.
+#[derive(Debug)]
+struct BitReader(u128, u32);
+
+impl BitReader {
+    fn read(&mut self, bits: u32) -> u32 {
+        let value = (self.0 >> self.1) as u32 & ((1u64 << bits) - 1) as u32;
+        self.1 += bits;
+        value
+    }
+}
+
+/// Subsets of texels in partitions of two subsets, a bit per texel.
+const PARTITIONS_2: [u16; 64] = [
+    0xCCCC, 0x8888, 0xEEEE, 0xECC8, 0xC880, 0xFEEC, 0xFEC8, 0xEC80, 0xC800, 0xFFEC, 0xFE80, 0xE800,
+    0xFFE8, 0xFF00, 0xFFF0, 0xF000, 0xF710, 0x008E, 0x7100, 0x08CE, 0x008C, 0x7310, 0x3100, 0x8CCE,
+    0x088C, 0x3110, 0x6666, 0x366C, 0x17E8, 0x0FF0, 0x718E, 0x399C, 0xAAAA, 0xF0F0, 0x5A5A, 0x33CC,
+    0x3C3C, 0x55AA, 0x9696, 0xA55A, 0x73CE, 0x13C8, 0x324C, 0x3BDC, 0x6996, 0xC33C, 0x9966, 0x0660,
+    0x0272, 0x04E4, 0x4E40, 0x2720, 0xC936, 0x936C, 0x39C6, 0x639C, 0x9336, 0x9CC6, 0x817E, 0xE718,
+    0xCCF0, 0x0FCC, 0x7744, 0xEE22,
+];
+
+/// Subsets of texels in partitions of three subsets.
+const PARTITIONS_3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Anchor texel of the second subset of partitions of two subsets.
+const ANCHORS_2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 2, 8, 2, 2, 8, 8, 15, 2, 8,
+    2, 2, 8, 8, 2, 2, 15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6, 6, 2, 6, 8, 15, 15, 2,
+    2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor texels of the second and third subset of partitions of three subsets.
+const ANCHORS_3_SECOND: [u8; 64] = [
+    3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3, 3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5,
+    15, 15, 8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15, 3, 15, 5, 5, 5, 8, 5, 10, 5,
+    10, 8, 13, 15, 12, 3, 3,
+];
+const ANCHORS_3_THIRD: [u8; 64] = [
+    15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8, 15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6,
+    10, 15, 15, 10, 8, 15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8, 15, 3, 15, 15, 15,
+    15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes a mode 6 block from the bit positions of the format specification.
+    fn decode_mode_6(block: &[u8]) -> [[u8; 4]; 16] {
+        let bits = u128::from_le_bytes(block.try_into().unwrap());
+        let field = |start: u32, len: u32| (bits >> start) as u32 & ((1 << len) - 1);
+        assert_eq!(field(0, 7), 1 << 6, "mode 6 bits");
+        // R0 R1 G0 G1 B0 B1 A0 A1 of 7 bits, then P0 P1
+        let endpoint = |e: u32| -> [u32; 4] {
+            std::array::from_fn(|c| field(7 + 14 * c as u32 + 7 * e, 7) << 1 | field(63 + e, 1))
+        };
+        let (low, high) = (endpoint(0), endpoint(1));
+        std::array::from_fn(|texel| {
+            // the first index has 3 bits, the others 4
+            let index = match texel {
+                0 => field(65, 3),
+                _ => field(68 + 4 * (texel as u32 - 1), 4),
+            };
+            let weight = WEIGHTS_4[index as usize];
+            std::array::from_fn(|c| (((64 - weight) * low[c] + weight * high[c] + 32) >> 6) as u8)
+        })
+    }
+
+    fn assert_round_trip(image: &RgbaImage, tolerance: u8) {
+        let blocks = encode_bc7(image);
+        let (width, height) = image.dimensions();
+        assert_eq!(
+            blocks.len(),
+            (width.div_ceil(4) * height.div_ceil(4) * 16) as usize
+        );
+        let decoded = decode_bc7(&blocks, width, height);
+        for (i, block) in blocks.chunks_exact(16).enumerate() {
+            let texels = decode_mode_6(block);
+            let (block_x, block_y) = (i as u32 % width.div_ceil(4), i as u32 / width.div_ceil(4));
+            for (j, texel) in texels.into_iter().enumerate() {
+                let (x, y) = (block_x * 4 + j as u32 % 4, block_y * 4 + j as u32 / 4);
+                if x >= width || y >= height {
+                    continue;
+                }
+                assert_eq!(
+                    decoded.get_pixel(x, y).0,
+                    texel,
+                    "decoders differ at {x}, {y}"
+                );
+                let expected = image.get_pixel(x, y).0;
+                for c in 0..4 {
+                    assert!(
+                        texel[c].abs_diff(expected[c]) <= tolerance,
+                        "{texel:?} is not {expected:?} at {x}, {y}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn solid_color() {
+        for color in [
+            [0, 0, 0, 0],
+            [255; 4],
+            [200, 100, 51, 128],
+            [1, 254, 127, 255],
+        ] {
+            assert_round_trip(&RgbaImage::from_pixel(4, 4, Rgba(color)), 1);
+        }
+    }
+
+    #[test]
+    fn gradient() {
+        let image = RgbaImage::from_fn(8, 8, |x, y| {
+            let t = (x + y * 8) as u8 * 4;
+            Rgba([t, 255 - t, t / 2 + 64, 255 - t / 4])
+        });
+        assert_round_trip(&image, 4);
+    }
+
+    #[test]
+    fn endpoint_order() {
+        // the first texel is at an end of the line, so its index is 0 only with the right order
+        let image = RgbaImage::from_fn(4, 4, |x, _| Rgba([255 - x as u8 * 60, 0, 0, 255]));
+        let first = decode_mode_6(&encode_bc7(&image))[0];
+        // a shared lowest bit cannot give both 0 and 255 exactly
+        assert!(first[0] >= 254 && first[3] >= 254, "{first:?}");
+        assert_round_trip(&image, 2);
+    }
+
+    #[test]
+    fn partial_blocks() {
+        let image = RgbaImage::from_fn(6, 3, |x, y| {
+            let t = (x + y) as u8 * 30;
+            Rgba([t, t / 2, 255 - t, 255])
+        });
+        assert_round_trip(&image, 4);
+    }
+}
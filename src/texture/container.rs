@@ -0,0 +1,345 @@
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{BufWriter, Read},
+    path::{Path, PathBuf},
+};
+
+use ddsfile::{AlphaMode, D3D10ResourceDimension, D3DFormat, Dds, DxgiFormat, NewDxgiParams};
+use wgpu::{AstcBlock, AstcChannel, Extent3d, Features, TextureDimension, TextureFormat};
+
+use crate::texture::bc7::decode_bc7;
+
+/// Longest side of textures the loader uses. Devices are requested with it as their texture size
+/// limit, when the adapter allows it.
+pub const MAX_TEXTURE_DIMENSION: u32 = 16384;
+
+/// Pre-processed texture read from a KTX2 or DDS file. Its levels are uploaded as they are, so
+/// block compressed textures are not mipmapped at startup, and decoded only when the adapter cannot
+/// sample them.
+#[derive(Debug, Clone)]
+pub struct ContainerImage {
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Mip levels from the full size one.
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl ContainerImage {
+    pub fn size(&self) -> Extent3d {
+        Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: 1,
+        }
+    }
+}
+
+/// Physical size of `level` of `format` texture of `size`, its bytes per row of blocks and its
+/// length in bytes.
+pub fn level_layout(format: TextureFormat, size: Extent3d, level: u32) -> (Extent3d, u32, usize) {
+    let (block_width, block_height) = format.block_dimensions();
+    let block_size = format
+        .block_copy_size(None)
+        .expect("Colour formats have block size");
+    let physical = size
+        .mip_level_size(level, TextureDimension::D2)
+        .physical_size(format);
+    let bytes_per_row = physical.width / block_width * block_size;
+    let rows = physical.height / block_height;
+    (physical, bytes_per_row, (bytes_per_row * rows) as usize)
+}
+
+/// Finds the best container of the image at `source`, which is sRGB or linear as `srgb` says.
+/// Containers are files next to the image with the same name and `.ktx2` or `.dds` extension,
+/// optionally with another extension in between, e.g. `2k_earth_daymap.bc7.dds`. `source` may also
+/// be a container itself.
+///
+/// Containers `features` can sample are preferred, block compressed ones first. BC7 containers
+/// without adapter support are decoded into RGBA levels. Levels with a side longer than
+/// `max_dimension` are left out. Containers, which cannot be read or have no level small enough, are
+/// reported and skipped.
+pub fn find_container(
+    source: &Path,
+    features: Features,
+    max_dimension: u32,
+    srgb: bool,
+) -> Option<ContainerImage> {
+    let mut candidates = container_candidates(source)
+        .into_iter()
+        .filter_map(|path| match read_format(&path) {
+            Ok((format, width, height)) => Some((path, format, width, height)),
+            Err(e) => {
+                eprintln!("Failed to read texture container {}: {e}", path.display());
+                None
+            }
+        })
+        .filter(|(_, format, width, height)| {
+            let (block_width, block_height) = format.block_dimensions();
+            format.is_srgb() == srgb
+                && (features.contains(format.required_features()) || is_decodable(*format))
+                && width % block_width == 0
+                && height % block_height == 0
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by_key(|(_, format, _, _)| {
+        (
+            !features.contains(format.required_features()),
+            !format.is_compressed(),
+        )
+    });
+    candidates.into_iter().find_map(|(path, format, _, _)| {
+        read_container(&path)
+            .and_then(|container| fit_levels(container, max_dimension))
+            .map(|container| {
+                if features.contains(format.required_features()) {
+                    container
+                } else {
+                    decode(container)
+                }
+            })
+            .inspect_err(|e| eprintln!("Failed to load texture {}: {e}", path.display()))
+            .ok()
+    })
+}
+
+/// Leaves out the largest levels of `container` until its sides are at most `max_dimension`.
+fn fit_levels(
+    mut container: ContainerImage,
+    max_dimension: u32,
+) -> Result<ContainerImage, Box<dyn Error>> {
+    let size = container.size();
+    let first = (0..container.levels.len() as u32)
+        .find(|level| {
+            let size = size.mip_level_size(*level, TextureDimension::D2);
+            size.width <= max_dimension && size.height <= max_dimension
+        })
+        .ok_or(format!("no level fits texture size limit {max_dimension}"))?;
+    if first == 0 {
+        return Ok(container);
+    }
+    let size = size.mip_level_size(first, TextureDimension::D2);
+    let (block_width, block_height) = container.format.block_dimensions();
+    if !size.width.is_multiple_of(block_width) || !size.height.is_multiple_of(block_height) {
+        return Err(format!(
+            "level fitting texture size limit {max_dimension} is not whole blocks"
+        )
+        .into());
+    }
+    container.levels.drain(..first as usize);
+    Ok(ContainerImage {
+        width: size.width,
+        height: size.height,
+        ..container
+    })
+}
+
+/// Formats decoded in software, when the adapter cannot sample them.
+fn is_decodable(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::Bc7RgbaUnorm | TextureFormat::Bc7RgbaUnormSrgb
+    )
+}
+
+/// Decodes levels of a BC7 `container` into RGBA of the same colour space.
+fn decode(container: ContainerImage) -> ContainerImage {
+    let size = container.size();
+    ContainerImage {
+        format: if container.format.is_srgb() {
+            TextureFormat::Rgba8UnormSrgb
+        } else {
+            TextureFormat::Rgba8Unorm
+        },
+        levels: container
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(level, blocks)| {
+                let level_size = size.mip_level_size(level as u32, TextureDimension::D2);
+                decode_bc7(blocks, level_size.width, level_size.height).into_raw()
+            })
+            .collect(),
+        ..container
+    }
+}
+
+fn is_container(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "ktx2" || extension == "dds")
+}
+
+fn container_candidates(source: &Path) -> Vec<PathBuf> {
+    if is_container(source) {
+        return vec![source.to_path_buf()];
+    }
+    let (Some(directory), Some(stem)) = (source.parent(), source.file_stem()) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    let mut candidates = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            is_container(path)
+                && path.file_stem().is_some_and(|name| {
+                    let name = Path::new(name);
+                    name == Path::new(stem) || name.file_stem() == Some(stem)
+                })
+        })
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates
+}
+
+/// Reads format and size of a container from its header only.
+fn read_format(path: &Path) -> Result<(TextureFormat, u32, u32), Box<dyn Error>> {
+    let mut header = Vec::new();
+    // DDS magic, header and DX10 header are the longest
+    File::open(path)?.take(148).read_to_end(&mut header)?;
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "ktx2")
+    {
+        let header = ktx2::Header::from_bytes(
+            header
+                .get(..ktx2::Header::LENGTH)
+                .ok_or("truncated header")?
+                .try_into()?,
+        )?;
+        let format = header
+            .format
+            .and_then(ktx2_format)
+            .ok_or("unsupported format")?;
+        Ok((format, header.pixel_width, header.pixel_height.max(1)))
+    } else {
+        let dds = Dds::read(header.as_slice())?;
+        Ok((dds_format(&dds)?, dds.get_width(), dds.get_height()))
+    }
+}
+
+/// Reads a single 2D texture from a KTX2 or DDS file.
+pub fn read_container(path: &Path) -> Result<ContainerImage, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    if path
+        .extension()
+        .is_some_and(|extension| extension == "ktx2")
+    {
+        let reader = ktx2::Reader::new(bytes.as_slice())?;
+        let header = reader.header();
+        if header.supercompression_scheme.is_some() {
+            return Err("supercompressed KTX2 is not supported".into());
+        }
+        if header.face_count != 1 || header.layer_count > 1 || header.pixel_depth > 1 {
+            return Err("only single 2D textures are supported".into());
+        }
+        Ok(ContainerImage {
+            format: header
+                .format
+                .and_then(ktx2_format)
+                .ok_or("unsupported format")?,
+            width: header.pixel_width,
+            height: header.pixel_height.max(1),
+            levels: reader.levels().map(|level| level.data.to_vec()).collect(),
+        })
+    } else {
+        let dds = Dds::read(bytes.as_slice())?;
+        if dds.get_num_array_layers() > 1 || dds.get_depth() > 1 {
+            return Err("only single 2D textures are supported".into());
+        }
+        let mut image = ContainerImage {
+            format: dds_format(&dds)?,
+            width: dds.get_width(),
+            height: dds.get_height(),
+            levels: Vec::new(),
+        };
+        // levels follow each other from the full size one
+        let mut data = dds.data.as_slice();
+        for level in 0..dds.get_num_mipmap_levels() {
+            let (_, _, length) = level_layout(image.format, image.size(), level);
+            let (level, rest) = data
+                .split_at_checked(length)
+                .ok_or("data is shorter than its levels")?;
+            image.levels.push(level.to_vec());
+            data = rest;
+        }
+        Ok(image)
+    }
+}
+
+/// Writes a single 2D texture with its mip levels into a DDS file.
+pub fn write_dds(path: &Path, image: &ContainerImage) -> Result<(), Box<dyn Error>> {
+    let format = match image.format {
+        TextureFormat::Rgba8Unorm => DxgiFormat::R8G8B8A8_UNorm,
+        TextureFormat::Rgba8UnormSrgb => DxgiFormat::R8G8B8A8_UNorm_sRGB,
+        TextureFormat::Bc7RgbaUnorm => DxgiFormat::BC7_UNorm,
+        TextureFormat::Bc7RgbaUnormSrgb => DxgiFormat::BC7_UNorm_sRGB,
+        format => return Err(format!("{format:?} cannot be written").into()),
+    };
+    let mut dds = Dds::new_dxgi(NewDxgiParams {
+        height: image.height,
+        width: image.width,
+        depth: None,
+        format,
+        mipmap_levels: Some(image.levels.len() as u32),
+        array_layers: None,
+        caps2: None,
+        is_cubemap: false,
+        resource_dimension: D3D10ResourceDimension::Texture2D,
+        alpha_mode: AlphaMode::Unknown,
+    })?;
+    dds.data = image.levels.concat();
+    dds.write(&mut BufWriter::new(File::create(path)?))?;
+    Ok(())
+}
+
+fn ktx2_format(format: ktx2::Format) -> Option<TextureFormat> {
+    use ktx2::Format;
+    let astc = |block, channel| TextureFormat::Astc { block, channel };
+    Some(match format {
+        Format::R8G8B8A8_UNORM => TextureFormat::Rgba8Unorm,
+        Format::R8G8B8A8_SRGB => TextureFormat::Rgba8UnormSrgb,
+        Format::BC1_RGB_UNORM_BLOCK | Format::BC1_RGBA_UNORM_BLOCK => TextureFormat::Bc1RgbaUnorm,
+        Format::BC1_RGB_SRGB_BLOCK | Format::BC1_RGBA_SRGB_BLOCK => TextureFormat::Bc1RgbaUnormSrgb,
+        Format::BC3_UNORM_BLOCK => TextureFormat::Bc3RgbaUnorm,
+        Format::BC3_SRGB_BLOCK => TextureFormat::Bc3RgbaUnormSrgb,
+        Format::BC7_UNORM_BLOCK => TextureFormat::Bc7RgbaUnorm,
+        Format::BC7_SRGB_BLOCK => TextureFormat::Bc7RgbaUnormSrgb,
+        Format::ETC2_R8G8B8_UNORM_BLOCK => TextureFormat::Etc2Rgb8Unorm,
+        Format::ETC2_R8G8B8_SRGB_BLOCK => TextureFormat::Etc2Rgb8UnormSrgb,
+        Format::ETC2_R8G8B8A8_UNORM_BLOCK => TextureFormat::Etc2Rgba8Unorm,
+        Format::ETC2_R8G8B8A8_SRGB_BLOCK => TextureFormat::Etc2Rgba8UnormSrgb,
+        Format::ASTC_4x4_UNORM_BLOCK => astc(AstcBlock::B4x4, AstcChannel::Unorm),
+        Format::ASTC_4x4_SRGB_BLOCK => astc(AstcBlock::B4x4, AstcChannel::UnormSrgb),
+        Format::ASTC_6x6_UNORM_BLOCK => astc(AstcBlock::B6x6, AstcChannel::Unorm),
+        Format::ASTC_6x6_SRGB_BLOCK => astc(AstcBlock::B6x6, AstcChannel::UnormSrgb),
+        Format::ASTC_8x8_UNORM_BLOCK => astc(AstcBlock::B8x8, AstcChannel::Unorm),
+        Format::ASTC_8x8_SRGB_BLOCK => astc(AstcBlock::B8x8, AstcChannel::UnormSrgb),
+        _ => return None,
+    })
+}
+
+fn dds_format(dds: &Dds) -> Result<TextureFormat, Box<dyn Error>> {
+    if let Some(format) = dds.get_dxgi_format() {
+        return Ok(match format {
+            DxgiFormat::R8G8B8A8_UNorm => TextureFormat::Rgba8Unorm,
+            DxgiFormat::R8G8B8A8_UNorm_sRGB => TextureFormat::Rgba8UnormSrgb,
+            DxgiFormat::BC1_UNorm => TextureFormat::Bc1RgbaUnorm,
+            DxgiFormat::BC1_UNorm_sRGB => TextureFormat::Bc1RgbaUnormSrgb,
+            DxgiFormat::BC3_UNorm => TextureFormat::Bc3RgbaUnorm,
+            DxgiFormat::BC3_UNorm_sRGB => TextureFormat::Bc3RgbaUnormSrgb,
+            DxgiFormat::BC7_UNorm => TextureFormat::Bc7RgbaUnorm,
+            DxgiFormat::BC7_UNorm_sRGB => TextureFormat::Bc7RgbaUnormSrgb,
+            format => return Err(format!("unsupported format {format:?}").into()),
+        });
+    }
+    // legacy files without DX10 header do not tell colour space, they are taken as sRGB
+    match dds.get_d3d_format() {
+        Some(D3DFormat::A8B8G8R8) => Ok(TextureFormat::Rgba8UnormSrgb),
+        Some(D3DFormat::DXT1) => Ok(TextureFormat::Bc1RgbaUnormSrgb),
+        Some(D3DFormat::DXT5) => Ok(TextureFormat::Bc3RgbaUnormSrgb),
+        format => Err(format!("unsupported format {format:?}").into()),
+    }
+}
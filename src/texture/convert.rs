@@ -0,0 +1,132 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image::imageops::{self, FilterType};
+use wgpu::TextureFormat;
+
+use crate::texture::{
+    bc7::encode_bc7,
+    container::{ContainerImage, write_dds},
+    texture::mip_chain,
+};
+
+const USAGE: &str = "\
+Usage: solar-system convert-textures [--format bc7|rgba8] [--linear] [IMAGE...]
+
+Converts images into DDS containers with full mip chains, which are loaded instead of the images.
+Containers are written next to the images as NAME.FORMAT.dds. Without images, all JPEGs in
+resources/ are converted.
+
+  --format bc7    block compressed, 4 times smaller than RGBA in memory (default)
+  --format rgba8  uncompressed, for adapters without BC support, skips decoding only
+  --linear        data is not sRGB colour, e.g. normal maps";
+
+/// Format of converted containers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Bc7,
+    Rgba8,
+}
+
+impl Format {
+    fn name(self) -> &'static str {
+        match self {
+            Format::Bc7 => "bc7",
+            Format::Rgba8 => "rgba8",
+        }
+    }
+
+    fn texture_format(self, linear: bool) -> TextureFormat {
+        match (self, linear) {
+            (Format::Bc7, false) => TextureFormat::Bc7RgbaUnormSrgb,
+            (Format::Bc7, true) => TextureFormat::Bc7RgbaUnorm,
+            (Format::Rgba8, false) => TextureFormat::Rgba8UnormSrgb,
+            (Format::Rgba8, true) => TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+/// Runs texture conversion with command line `args` following the subcommand. Returns `false`, if
+/// any image failed to convert.
+pub fn run(args: impl IntoIterator<Item = String>) -> bool {
+    let mut format = Format::Bc7;
+    let mut linear = false;
+    let mut images = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().as_deref() {
+                Some("bc7") => format = Format::Bc7,
+                Some("rgba8") => format = Format::Rgba8,
+                _ => {
+                    eprintln!("{USAGE}");
+                    return false;
+                }
+            },
+            "--linear" => linear = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return true;
+            }
+            _ => images.push(PathBuf::from(arg)),
+        }
+    }
+    if images.is_empty() {
+        images = fs::read_dir("resources")
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "jpg" || extension == "jpeg")
+            })
+            .collect();
+        images.sort();
+    }
+
+    let mut success = true;
+    for image in images {
+        let container = image.with_extension(format!("{}.dds", format.name()));
+        match convert(&image, &container, format, linear) {
+            Ok(()) => println!("{} -> {}", image.display(), container.display()),
+            Err(e) => {
+                eprintln!("Failed to convert {}: {e}", image.display());
+                success = false;
+            }
+        }
+    }
+    success
+}
+
+fn convert(
+    image: &Path,
+    container: &Path,
+    format: Format,
+    linear: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut image = image::open(image)?.into_rgba8();
+    // block compressed textures must be made of whole blocks
+    let (width, height) = image.dimensions();
+    if format == Format::Bc7 && (width % 4 != 0 || height % 4 != 0) {
+        let (width, height) = (width.next_multiple_of(4), height.next_multiple_of(4));
+        println!("Resizing to {width}x{height} to fit BC7 blocks");
+        image = imageops::resize(&image, width, height, FilterType::Lanczos3);
+    }
+    let (width, height) = image.dimensions();
+    let levels = mip_chain(image, !linear);
+    write_dds(
+        container,
+        &ContainerImage {
+            format: format.texture_format(linear),
+            width,
+            height,
+            levels: match format {
+                Format::Bc7 => levels.iter().map(encode_bc7).collect(),
+                Format::Rgba8 => levels.into_iter().map(|level| level.into_raw()).collect(),
+            },
+        },
+    )
+}
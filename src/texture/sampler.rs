@@ -0,0 +1,84 @@
+use serde::Deserialize;
+use wgpu::{AddressMode, FilterMode, SamplerDescriptor};
+
+/// Filtering of texels and mip levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    /// Closest texel of the closest mip level. Keeps pixel art sharp.
+    Nearest,
+    /// Bilinear filtering within the closest mip level.
+    Linear,
+    /// Bilinear filtering blended between two mip levels.
+    #[default]
+    Trilinear,
+}
+
+/// Addressing of texture coordinates outside of the texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Wrap {
+    #[default]
+    ClampToEdge,
+    Repeat,
+    MirrorRepeat,
+}
+
+impl From<Wrap> for AddressMode {
+    fn from(wrap: Wrap) -> Self {
+        match wrap {
+            Wrap::ClampToEdge => AddressMode::ClampToEdge,
+            Wrap::Repeat => AddressMode::Repeat,
+            Wrap::MirrorRepeat => AddressMode::MirrorRepeat,
+        }
+    }
+}
+
+/// Sampling of a single texture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplerConfig {
+    pub filter: Filter,
+    /// Maximal anisotropy of trilinear filtering, 1 disables it. It is at most 16, wgpu drops it to 1
+    /// on adapters without anisotropic filtering.
+    pub anisotropy: u16,
+    /// Generates full mip chain of the texture.
+    pub mipmaps: bool,
+    pub wrap_u: Wrap,
+    pub wrap_v: Wrap,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self {
+            filter: Filter::default(),
+            anisotropy: 16,
+            mipmaps: true,
+            wrap_u: Wrap::default(),
+            wrap_v: Wrap::default(),
+        }
+    }
+}
+
+impl SamplerConfig {
+    pub fn descriptor(&self) -> SamplerDescriptor<'static> {
+        let (filter, mipmap_filter) = match self.filter {
+            Filter::Nearest => (FilterMode::Nearest, FilterMode::Nearest),
+            Filter::Linear => (FilterMode::Linear, FilterMode::Nearest),
+            Filter::Trilinear => (FilterMode::Linear, FilterMode::Linear),
+        };
+        SamplerDescriptor {
+            label: Some("texture sampler"),
+            address_mode_u: self.wrap_u.into(),
+            address_mode_v: self.wrap_v.into(),
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_filter,
+            // anisotropy is valid only when all filters are linear
+            anisotropy_clamp: match self.filter {
+                Filter::Trilinear => self.anisotropy.clamp(1, 16),
+                _ => 1,
+            },
+            ..Default::default()
+        }
+    }
+}
@@ -1,6 +1,16 @@
-use image::DynamicImage;
+use std::{error::Error, path::PathBuf};
+
+use image::{
+    DynamicImage, Rgba, RgbaImage,
+    imageops::{self, FilterType},
+};
 use wgpu::*;
 
+use crate::texture::{
+    container::{ContainerImage, find_container, level_layout},
+    sampler::SamplerConfig,
+};
+
 #[derive(Debug)]
 pub struct RgbaTexture {
     #[allow(unused)]
@@ -10,19 +20,184 @@ pub struct RgbaTexture {
 }
 
 impl RgbaTexture {
-    pub fn from_image(device: &Device, queue: &Queue, image: DynamicImage) -> RgbaTexture {
-        let image = image.into_rgba8();
-
-        let size = image.dimensions();
-        let size = Extent3d {
-            width: size.0,
-            height: size.1,
-            depth_or_array_layers: 1,
+    pub fn from_image(
+        device: &Device,
+        queue: &Queue,
+        image: DynamicImage,
+        sampler: SamplerConfig,
+    ) -> RgbaTexture {
+        RgbaTexture::from_image_with_format(
+            device,
+            queue,
+            image,
+            sampler,
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    /// Creates texture for non-colour data, such as normal maps. Its values are not converted from
+    /// sRGB when sampled.
+    pub fn from_image_linear(
+        device: &Device,
+        queue: &Queue,
+        image: DynamicImage,
+        sampler: SamplerConfig,
+    ) -> RgbaTexture {
+        RgbaTexture::from_image_with_format(
+            device,
+            queue,
+            image,
+            sampler,
+            TextureFormat::Rgba8Unorm,
+        )
+    }
+
+    /// Creates colour texture from `texture`. Files are loaded from a container next to them, if
+    /// there is one the device can sample or decode, and decoded into RGBA otherwise.
+    pub fn load(
+        device: &Device,
+        queue: &Queue,
+        texture: TextureImage,
+    ) -> Result<RgbaTexture, Box<dyn Error>> {
+        RgbaTexture::load_with_format(device, queue, texture, TextureFormat::Rgba8UnormSrgb)
+    }
+
+    /// Creates texture for non-colour data like [`RgbaTexture::from_image_linear`]. Only linear
+    /// containers are used, sRGB ones are converted from colour textures.
+    pub fn load_linear(
+        device: &Device,
+        queue: &Queue,
+        texture: TextureImage,
+    ) -> Result<RgbaTexture, Box<dyn Error>> {
+        RgbaTexture::load_with_format(device, queue, texture, TextureFormat::Rgba8Unorm)
+    }
+
+    fn load_with_format(
+        device: &Device,
+        queue: &Queue,
+        texture: TextureImage,
+        format: TextureFormat,
+    ) -> Result<RgbaTexture, Box<dyn Error>> {
+        let sampler = texture.sampler;
+        let image = match texture.source {
+            TextureSource::Image(image) => image,
+            TextureSource::File(path) => {
+                if let Some(container) = find_container(
+                    &path,
+                    device.features(),
+                    device.limits().max_texture_dimension_2d,
+                    format.is_srgb(),
+                ) {
+                    return Ok(RgbaTexture::from_container(
+                        device, queue, container, sampler,
+                    ));
+                }
+                image::open(&path).map_err(|e| format!("{}: {e}", path.display()))?
+            }
         };
+        Ok(RgbaTexture::from_image_with_format(
+            device, queue, image, sampler, format,
+        ))
+    }
+
+    /// Creates texture from levels of `container`. It is not mipmapped further, only its own levels
+    /// are used.
+    fn from_container(
+        device: &Device,
+        queue: &Queue,
+        container: ContainerImage,
+        sampler: SamplerConfig,
+    ) -> RgbaTexture {
+        let level_count = if sampler.mipmaps {
+            container.levels.len()
+        } else {
+            1
+        };
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("RgbaTexture container"),
+            size: container.size(),
+            mip_level_count: level_count as u32,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: container.format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for (mip_level, data) in container.levels.iter().take(level_count).enumerate() {
+            let (size, bytes_per_row, _) =
+                level_layout(container.format, container.size(), mip_level as u32);
+            let (_, block_height) = container.format.block_dimensions();
+            queue.write_texture(
+                TexelCopyTextureInfo {
+                    texture: &texture,
+                    mip_level: mip_level as u32,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
+                },
+                data,
+                TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(size.height / block_height),
+                },
+                size,
+            );
+        }
+
+        RgbaTexture {
+            view: texture.create_view(&TextureViewDescriptor::default()),
+            sampler: device.create_sampler(&sampler.descriptor()),
+            texture,
+        }
+    }
+
+    fn from_image_with_format(
+        device: &Device,
+        queue: &Queue,
+        image: DynamicImage,
+        sampler: SamplerConfig,
+        format: TextureFormat,
+    ) -> RgbaTexture {
+        let image = fit_size(image.into_rgba8(), device.limits().max_texture_dimension_2d);
+        let levels = mip_levels(image, sampler.mipmaps, format.is_srgb());
         let texture = device.create_texture(&TextureDescriptor {
             label: Some("RgbaTexture"),
-            size,
-            mip_level_count: 1,
+            size: extent(&levels[0], 1),
+            mip_level_count: levels.len() as u32,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        write_levels(queue, &texture, 0, &levels);
+
+        RgbaTexture {
+            view: texture.create_view(&TextureViewDescriptor::default()),
+            sampler: device.create_sampler(&sampler.descriptor()),
+            texture,
+        }
+    }
+
+    /// Creates cube texture from six square faces in order +X, -X, +Y, -Y, +Z, -Z.
+    pub fn cube_from_images(
+        device: &Device,
+        queue: &Queue,
+        faces: [DynamicImage; 6],
+        sampler: SamplerConfig,
+    ) -> RgbaTexture {
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        let faces = faces.map(|face| {
+            mip_levels(
+                fit_size(face.into_rgba8(), max_dimension),
+                sampler.mipmaps,
+                true,
+            )
+        });
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("RgbaTexture cube"),
+            size: extent(&faces[0][0], 6),
+            mip_level_count: faces[0].len() as u32,
             sample_count: 1,
             dimension: TextureDimension::D2,
             format: TextureFormat::Rgba8UnormSrgb,
@@ -30,36 +205,244 @@ impl RgbaTexture {
             view_formats: &[],
         });
 
+        for (layer, levels) in faces.iter().enumerate() {
+            assert_eq!(
+                levels[0].dimensions(),
+                faces[0][0].dimensions(),
+                "All cube faces must have the same size"
+            );
+            write_levels(queue, &texture, layer as u32, levels);
+        }
+
+        RgbaTexture {
+            view: texture.create_view(&TextureViewDescriptor {
+                dimension: Some(TextureViewDimension::Cube),
+                ..Default::default()
+            }),
+            sampler: device.create_sampler(&sampler.descriptor()),
+            texture,
+        }
+    }
+
+    /// Creates 1x1 texture. Used in place of optional textures, which are missing.
+    pub fn from_color(device: &Device, queue: &Queue, color: [u8; 4]) -> RgbaTexture {
+        RgbaTexture::from_image(
+            device,
+            queue,
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba(color))),
+            SamplerConfig::default(),
+        )
+    }
+}
+
+/// Where texels of a texture come from.
+#[derive(Debug, Clone)]
+pub enum TextureSource {
+    /// Image or KTX2/DDS container file. It is read only when the texture is created, so big
+    /// images are not held in memory all at once.
+    File(PathBuf),
+    /// Image generated while loading.
+    Image(DynamicImage),
+}
+
+/// Texels of a texture with its sampling.
+#[derive(Debug, Clone)]
+pub struct TextureImage {
+    pub source: TextureSource,
+    pub sampler: SamplerConfig,
+}
+
+/// Full mip chain of `image` down to 1x1 texel, which starts with the image itself. Each level
+/// averages boxes of texels of the previous one. Colours of `srgb` images are averaged in linear
+/// space, so higher levels do not get darker.
+pub fn mip_chain(image: RgbaImage, srgb: bool) -> Vec<RgbaImage> {
+    let to_linear: [f32; 256] = std::array::from_fn(|value| {
+        let c = value as f32 / 255.0;
+        if !srgb {
+            c
+        } else if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    let from_linear = |c: f32| {
+        let c = if !srgb {
+            c
+        } else if c <= 0.0031308 {
+            c * 12.92
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        };
+        (c * 255.0).round().clamp(0.0, 255.0) as u8
+    };
+    let mut levels = vec![image];
+    loop {
+        let previous = levels.last().expect("Chain is not empty");
+        let (width, height) = previous.dimensions();
+        if width == 1 && height == 1 {
+            return levels;
+        }
+        let (level_width, level_height) = ((width / 2).max(1), (height / 2).max(1));
+        // boxes of odd sized levels overlap, so no texel is left out
+        let range = |i: u32, size: u32, level_size: u32| {
+            i * size / level_size..((i + 1) * size).div_ceil(level_size)
+        };
+        let level = RgbaImage::from_fn(level_width, level_height, |x, y| {
+            let mut sum = [0.0; 4];
+            let mut count = 0.0;
+            for source_y in range(y, height, level_height) {
+                for source_x in range(x, width, level_width) {
+                    let texel = previous.get_pixel(source_x, source_y).0;
+                    for c in 0..3 {
+                        sum[c] += to_linear[texel[c] as usize];
+                    }
+                    // alpha is linear
+                    sum[3] += texel[3] as f32 / 255.0;
+                    count += 1.0;
+                }
+            }
+            Rgba(std::array::from_fn(|c| {
+                if c == 3 {
+                    (sum[3] / count * 255.0).round() as u8
+                } else {
+                    from_linear(sum[c] / count)
+                }
+            }))
+        });
+        levels.push(level);
+    }
+}
+
+/// Scales `image` down, so its sides are at most `max_dimension`, keeping its aspect ratio.
+fn fit_size(image: RgbaImage, max_dimension: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    if width <= max_dimension && height <= max_dimension {
+        return image;
+    }
+    let scale = max_dimension as f64 / width.max(height) as f64;
+    imageops::resize(
+        &image,
+        ((width as f64 * scale) as u32).clamp(1, max_dimension),
+        ((height as f64 * scale) as u32).clamp(1, max_dimension),
+        FilterType::Triangle,
+    )
+}
+
+fn mip_levels(image: RgbaImage, mipmaps: bool, srgb: bool) -> Vec<RgbaImage> {
+    if mipmaps {
+        mip_chain(image, srgb)
+    } else {
+        vec![image]
+    }
+}
+
+fn extent(image: &RgbaImage, layers: u32) -> Extent3d {
+    Extent3d {
+        width: image.width(),
+        height: image.height(),
+        depth_or_array_layers: layers,
+    }
+}
+
+/// Writes mip `levels` into `layer` of `texture`.
+fn write_levels(queue: &Queue, texture: &Texture, layer: u32, levels: &[RgbaImage]) {
+    for (mip_level, level) in levels.iter().enumerate() {
         queue.write_texture(
             TexelCopyTextureInfo {
-                texture: &texture,
-                mip_level: 0,
-                origin: Origin3d::ZERO,
+                texture,
+                mip_level: mip_level as u32,
+                origin: Origin3d {
+                    x: 0,
+                    y: 0,
+                    z: layer,
+                },
                 aspect: TextureAspect::All,
             },
-            image.as_ref(),
+            level.as_ref(),
             TexelCopyBufferLayout {
                 offset: 0,
-                bytes_per_row: Some(4 * size.width),
-                rows_per_image: Some(size.height),
+                bytes_per_row: Some(4 * level.width()),
+                rows_per_image: Some(level.height()),
             },
-            size,
+            extent(level, 1),
         );
-
-        let view = texture.create_view(&TextureViewDescriptor::default());
-        let sampler = device.create_sampler(&SamplerDescriptor::default());
-
-        RgbaTexture {
-            texture,
-            view,
-            sampler,
-        }
     }
 }
 
+/// Bindings of a single texture and its sampler.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct TextureBindGroupDescriptor<'a> {
-    pub layout: &'a BindGroupLayout,
+pub struct TextureBinding {
     pub binding_view: u32,
     pub binding_sampler: u32,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureBindGroupDescriptor<'a> {
+    pub layout: &'a BindGroupLayout,
+    /// Bindings of all textures in the layout. Textures of the bind group must be in the same
+    /// order.
+    pub bindings: &'a [TextureBinding],
+}
+
+impl TextureBindGroupDescriptor<'_> {
+    /// # Panics
+    ///
+    /// Panics if the number of textures does not match the number of bindings.
+    pub fn create_bind_group(&self, device: &Device, textures: &[RgbaTexture]) -> BindGroup {
+        assert_eq!(
+            textures.len(),
+            self.bindings.len(),
+            "Each texture binding needs exactly one texture"
+        );
+        let entries = self
+            .bindings
+            .iter()
+            .zip(textures)
+            .flat_map(|(binding, texture)| {
+                [
+                    BindGroupEntry {
+                        binding: binding.binding_view,
+                        resource: BindingResource::TextureView(&texture.view),
+                    },
+                    BindGroupEntry {
+                        binding: binding.binding_sampler,
+                        resource: BindingResource::Sampler(&texture.sampler),
+                    },
+                ]
+            })
+            .collect::<Vec<_>>();
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Texture Bind Group"),
+            entries: &entries,
+            layout: self.layout,
+        })
+    }
+
+    /// Creates layout entries for all texture bindings, which are visible in fragment shader.
+    pub fn layout_entries(bindings: &[TextureBinding]) -> Vec<BindGroupLayoutEntry> {
+        bindings
+            .iter()
+            .flat_map(|binding| {
+                [
+                    BindGroupLayoutEntry {
+                        binding: binding.binding_view,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            sample_type: TextureSampleType::Float { filterable: true },
+                            view_dimension: TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: binding.binding_sampler,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ]
+            })
+            .collect()
+    }
+}
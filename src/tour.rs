@@ -0,0 +1,379 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use cgmath::{InnerSpace, Point3, Quaternion, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::{
+        camera::Camera,
+        camera_control::{CameraControl, UP, look_rotation},
+        easing::Easing,
+    },
+    labels::LabelConfig,
+    simulation_clock::{SimulationClock, clamp_speed},
+    solar_object::render_solar_object::time_scaling,
+};
+
+/// File a recorded tour is saved to, when recording stops.
+pub const RECORDED_TOUR_PATH: &str = "data/recorded_tour.toml";
+/// Step of seeking by keyboard.
+pub const SEEK_STEP: Duration = Duration::from_secs(5);
+
+/// State of the scene at a point of a tour. Missing values are kept from earlier keyframes or from
+/// the running scene.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keyframe {
+    /// Time from the start of the tour in seconds.
+    pub at: f64,
+    /// Camera position in the world. The camera moves from the previous keyframe to it.
+    pub position: Option<[f64; 3]>,
+    /// View direction of the camera at `position`.
+    pub direction: Option<[f32; 3]>,
+    /// Top of the screen at `position`, the world up by default.
+    pub up: Option<[f32; 3]>,
+    /// Body the camera flies to from the previous keyframe. The body is followed afterwards.
+    /// Ignored, if the keyframe has a position.
+    pub target: Option<String>,
+    /// Simulated day set at the keyframe.
+    pub day: Option<f64>,
+    /// Speed of the simulation relative to real time, limited like speed set in the app.
+    pub speed: Option<f64>,
+    pub paused: Option<bool>,
+    /// Visibility of labels.
+    pub labels: Option<bool>,
+    /// Progress curve of the camera movement towards this keyframe.
+    pub easing: Easing,
+}
+
+impl Keyframe {
+    /// Camera position and orientation, if the keyframe has a position.
+    fn pose(&self) -> Option<(Point3<f64>, Quaternion<f32>)> {
+        let position = self.position?;
+        let up = self.up.map_or(UP, Vector3::from);
+        let direction = self.direction.map_or(-Vector3::unit_z(), Vector3::from);
+        Some((
+            Point3::from(position),
+            look_rotation(direction, &[up, UP, -Vector3::unit_z()]),
+        ))
+    }
+}
+
+/// Timeline of keyframes, sorted by their time.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Tour {
+    #[serde(default, rename = "keyframe")]
+    pub keyframes: Vec<Keyframe>,
+}
+
+impl Tour {
+    pub fn duration(&self) -> Duration {
+        self.keyframes.last().map_or(Duration::ZERO, |keyframe| {
+            Duration::from_secs_f64(keyframe.at.max(0.0))
+        })
+    }
+
+    /// Adds `keyframe` after all keyframes with the same or earlier time.
+    pub fn insert(&mut self, keyframe: Keyframe) {
+        let index = self
+            .keyframes
+            .partition_point(|other| other.at <= keyframe.at);
+        self.keyframes.insert(index, keyframe);
+    }
+}
+
+/// Loads a tour. Tours are optional, so a missing or invalid file is reported and skipped.
+pub fn load_tour(path: &str) -> Option<Tour> {
+    let toml_str = fs::read_to_string(path).ok()?;
+    match toml::from_str::<Tour>(&toml_str) {
+        Ok(mut tour) => {
+            tour.keyframes.sort_by(|a, b| a.at.total_cmp(&b.at));
+            Some(tour)
+        }
+        Err(e) => {
+            eprintln!("Invalid tour {path}: {e}");
+            None
+        }
+    }
+}
+
+pub fn save_tour(path: &str, tour: &Tour) {
+    let result = toml::to_string(tour)
+        .map_err(|e| e.to_string())
+        .and_then(|toml_str| fs::write(path, toml_str).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        eprintln!("Failed to save tour to {path}: {e}");
+    }
+}
+
+/// Plays a [`Tour`] by driving the camera, the simulation clock and labels. Keyframes are applied
+/// once, when playback reaches them, so the user can still change things in between. Seeking and
+/// continuing paused playback derive the state from all keyframes before the current time.
+///
+/// Recording collects keyframes from the current state at real time since the recording started.
+pub struct TourPlayer {
+    camera_control: Arc<Mutex<CameraControl>>,
+    clock: Arc<Mutex<SimulationClock>>,
+    label_config: Arc<Mutex<LabelConfig>>,
+    tour: Tour,
+    /// Tour time as of the last update.
+    time: Duration,
+    playing: bool,
+    last_update: Option<Instant>,
+    /// The last keyframe reached by playback.
+    reached: Option<usize>,
+    /// Camera pose at the reached keyframe, the camera moves from it to the next pose.
+    from_pose: Option<(Point3<f64>, Quaternion<f32>)>,
+    /// Start of recording.
+    recording: Option<Instant>,
+}
+
+impl TourPlayer {
+    pub fn new(
+        camera: &Camera,
+        clock: Arc<Mutex<SimulationClock>>,
+        label_config: Arc<Mutex<LabelConfig>>,
+    ) -> Self {
+        Self {
+            camera_control: camera.camera_control.clone(),
+            clock,
+            label_config,
+            tour: Tour::default(),
+            time: Duration::ZERO,
+            playing: false,
+            last_update: None,
+            reached: None,
+            from_pose: None,
+            recording: None,
+        }
+    }
+
+    /// Replaces the tour and rewinds to its start.
+    pub fn load(&mut self, tour: Tour) {
+        self.tour = tour;
+        self.time = Duration::ZERO;
+        self.playing = false;
+        self.reached = None;
+    }
+
+    pub fn tour(&self) -> &Tour {
+        &self.tour
+    }
+
+    /// Tour time as of the last update.
+    pub fn time(&self) -> Duration {
+        self.time
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn toggle_playback(&mut self, now: Instant) {
+        if self.playing {
+            self.pause(now);
+        } else {
+            self.play(now);
+        }
+    }
+
+    /// Continues playback from the current time, or from the start, if the tour ended.
+    pub fn play(&mut self, now: Instant) {
+        if self.tour.keyframes.is_empty() || self.recording.is_some() {
+            return;
+        }
+        if self.time >= self.tour.duration() {
+            self.time = Duration::ZERO;
+        }
+        self.playing = true;
+        self.seek(now, self.time);
+    }
+
+    /// Stops the camera where it is. The simulation clock is shared with the rest of the app, so it
+    /// keeps its state.
+    pub fn pause(&mut self, now: Instant) {
+        if !self.playing {
+            return;
+        }
+        self.update(now);
+        self.playing = false;
+        let mut camera_control = self.camera_control.lock().unwrap();
+        let (position, orientation) = (camera_control.position(), camera_control.orientation());
+        camera_control.set_pose(now, position, orientation);
+    }
+
+    /// Moves playback to tour `time`. Paused playback only updates the time, the state is applied
+    /// when it continues.
+    pub fn seek(&mut self, now: Instant, time: Duration) {
+        self.time = time.min(self.tour.duration());
+        self.reached = None;
+        self.last_update = Some(now);
+        if self.playing {
+            self.apply(now);
+        }
+    }
+
+    /// Advances playback by real time elapsed since the last update.
+    pub fn update(&mut self, now: Instant) {
+        let elapsed = self.last_update.map_or(Duration::ZERO, |last_update| {
+            now.saturating_duration_since(last_update)
+        });
+        self.last_update = Some(now);
+        if !self.playing {
+            return;
+        }
+        self.time += elapsed;
+        self.apply(now);
+        if self.time >= self.tour.duration() {
+            self.time = self.tour.duration();
+            self.playing = false;
+        }
+    }
+
+    /// Applies the keyframe at the current time, if not applied yet, and moves the camera
+    /// towards the next one.
+    fn apply(&mut self, now: Instant) {
+        let t = self.time.as_secs_f64();
+        let Some(index) = self
+            .tour
+            .keyframes
+            .iter()
+            .rposition(|keyframe| keyframe.at <= t)
+        else {
+            return;
+        };
+        if self.reached != Some(index) {
+            self.reached = Some(index);
+            self.apply_state(t);
+            self.from_pose = Some(self.reach(now, index, t));
+        }
+
+        let keyframe = &self.tour.keyframes[index];
+        let next = self.tour.keyframes.get(index + 1);
+        let poses = self.from_pose.zip(next.and_then(Keyframe::pose));
+        if let (
+            Some(next),
+            Some(((from_position, from_orientation), (to_position, to_orientation))),
+        ) = (next, poses)
+        {
+            let progress = next
+                .easing
+                .apply(((t - keyframe.at) / (next.at - keyframe.at).max(f64::EPSILON)) as f32);
+            // the same rotation has two quaternions, the closer one gives the shorter path
+            let to_orientation = if from_orientation.dot(to_orientation) < 0.0 {
+                -to_orientation
+            } else {
+                to_orientation
+            };
+            let position = from_position + (to_position - from_position) * progress as f64;
+            self.camera_control.lock().unwrap().set_pose(
+                now,
+                position,
+                from_orientation.slerp(to_orientation, progress),
+            );
+        }
+    }
+
+    /// Places the camera at the pose of the keyframe at `index` or starts the flight to the target
+    /// of the next one. Returns the camera pose the movement to the next keyframe starts from.
+    fn reach(&self, now: Instant, index: usize, t: f64) -> (Point3<f64>, Quaternion<f32>) {
+        let mut camera_control = self.camera_control.lock().unwrap();
+        if let Some((position, orientation)) = self.tour.keyframes[index].pose() {
+            camera_control.set_pose(now, position, orientation);
+        }
+        let next = self.tour.keyframes.get(index + 1);
+        if let Some(next) = next.filter(|next| next.position.is_none())
+            && let Some(target) = &next.target
+        {
+            let remaining = Duration::from_secs_f64((next.at - t).max(0.0));
+            camera_control.fly_to(now, target, remaining, next.easing);
+        }
+        (camera_control.position(), camera_control.orientation())
+    }
+
+    /// Applies simulation state and overlays given by keyframes up to tour time `t`. Simulation
+    /// time continues from the last keyframe with a day.
+    fn apply_state(&self, t: f64) {
+        let mut clock = self.clock.lock().unwrap();
+        let mut day = None;
+        let mut speed = clock.speed();
+        let mut paused = clock.is_paused();
+        let mut labels = None;
+        let mut last_at = 0.0;
+        let mut advance = |day: &mut Option<f64>, at: f64, speed: f64, paused: bool| {
+            if let Some(day) = day.as_mut().filter(|_| !paused) {
+                *day += time_scaling((at - last_at) * speed);
+            }
+            last_at = at;
+        };
+        for keyframe in self
+            .tour
+            .keyframes
+            .iter()
+            .take_while(|keyframe| keyframe.at <= t)
+        {
+            advance(&mut day, keyframe.at, speed, paused);
+            day = keyframe.day.or(day);
+            speed = keyframe.speed.map_or(speed, clamp_speed);
+            paused = keyframe.paused.unwrap_or(paused);
+            labels = keyframe.labels.or(labels);
+        }
+        advance(&mut day, t, speed, paused);
+
+        if let Some(day) = day {
+            clock.set_time(Duration::from_secs_f64((day / time_scaling(1.0)).max(0.0)));
+        }
+        clock.set_speed(speed);
+        clock.set_paused(paused);
+        if let Some(labels) = labels {
+            self.label_config.lock().unwrap().visible = labels;
+        }
+    }
+
+    /// Starts recording a new tour, or stops it and saves the recorded tour. The recorded tour
+    /// replaces the loaded one.
+    pub fn toggle_recording(&mut self, now: Instant) {
+        if self.recording.take().is_some() {
+            save_tour(RECORDED_TOUR_PATH, &self.tour);
+            return;
+        }
+        self.pause(now);
+        self.load(Tour::default());
+        self.recording = Some(now);
+        self.record_keyframe(now);
+    }
+
+    /// Adds the current state as a keyframe of the recorded tour.
+    pub fn record_keyframe(&mut self, now: Instant) {
+        let Some(start) = self.recording else {
+            return;
+        };
+        let (position, orientation) = {
+            let camera_control = self.camera_control.lock().unwrap();
+            (camera_control.position(), camera_control.orientation())
+        };
+        let clock = self.clock.lock().unwrap();
+        let keyframe = Keyframe {
+            at: now.saturating_duration_since(start).as_secs_f64(),
+            position: Some(position.into()),
+            direction: Some((orientation * -Vector3::unit_z()).normalize().into()),
+            up: Some((orientation * Vector3::unit_y()).normalize().into()),
+            target: None,
+            day: Some(time_scaling(clock.time().as_secs_f64())),
+            speed: Some(clock.speed()),
+            paused: Some(clock.is_paused()),
+            labels: Some(self.label_config.lock().unwrap().visible),
+            easing: Easing::default(),
+        };
+        self.time = Duration::from_secs_f64(keyframe.at);
+        self.tour.insert(keyframe);
+    }
+}